@@ -15,18 +15,80 @@ pub fn parse_for_register(info: &RegisterInfo, val_str: &str) -> anyhow::Result<
 }
 
 pub fn parse_bytes(input: &str) -> anyhow::Result<Vec<u8>> {
+    parse_list(input)?.map(parse_u8).collect()
+}
+
+// Splits up a bracketed, comma-separated list like `[1, 2, 3]`
+fn parse_list(input: &str) -> anyhow::Result<impl Iterator<Item = &str>> {
     let trimmed_input = input.trim();
     let len = trimmed_input.len();
     if trimmed_input.get(..1) != Some("[") || trimmed_input.get(len - 1..) != Some("]") {
         bail!("missing opening/closing brackets");
     }
 
-    trimmed_input
+    Ok(trimmed_input
         .get(1..len - 1)
         .ok_or(anyhow!("parse error: {}", trimmed_input))?
-        .split(",")
-        .map(parse_u8)
-        .collect::<anyhow::Result<Vec<u8>>>()
+        .split(","))
+}
+
+// Splits a register name like `xmm0[2]` into the register and the lane
+pub fn parse_register_lane(input: &str) -> anyhow::Result<(&str, Option<usize>)> {
+    let Some((name, rest)) = input.split_once('[') else {
+        return Ok((input, None));
+    };
+    let index = rest.strip_suffix(']').ok_or(anyhow!("missing closing bracket: {input}"))?;
+    Ok((name, Some(parse_u64(index)? as usize)))
+}
+
+// Integer lanes take either a signed or an unsigned value, as long as it fits in the lane; e.g.,
+// -1 and 0xff both work for an 8-bit lane, since they're the same bits
+pub fn parse_lane(input: &str, lane: LaneType) -> anyhow::Result<RegisterValue> {
+    let trimmed_input = input.trim();
+    if lane.is_floating_point() {
+        return Ok(match lane {
+            LaneType::F32 => RegisterValue::F32(trimmed_input.parse()?),
+            _ => RegisterValue::F64(trimmed_input.parse()?),
+        });
+    }
+
+    let val = match trimmed_input.strip_prefix('-') {
+        Some(abs) => -(parse_u64(abs)? as i128),
+        None => parse_u64(trimmed_input)? as i128,
+    };
+    let bits = lane.size() as u32 * 8;
+    if val < -(1 << (bits - 1)) || val >= 1 << bits {
+        bail!("{trimmed_input} doesn't fit in a {lane} lane");
+    }
+
+    Ok(match lane {
+        LaneType::I8 => RegisterValue::I8(val as i8),
+        LaneType::I16 => RegisterValue::I16(val as i16),
+        LaneType::I32 => RegisterValue::I32(val as i32),
+        LaneType::I64 => RegisterValue::I64(val as i64),
+        LaneType::U8 => RegisterValue::U8(val as u8),
+        LaneType::U16 => RegisterValue::U16(val as u16),
+        LaneType::U32 => RegisterValue::U32(val as u32),
+        _ => RegisterValue::U64(val as u64),
+    })
+}
+
+// Tells `+ZF -CF` and `RC=up` apart from plain numbers (including negative ones)
+pub fn is_flag_change(input: &str) -> bool {
+    let trimmed_input = input.trim();
+    let flag_name = trimmed_input.trim_start_matches(['+', '-']);
+    trimmed_input.contains('=') || (flag_name.len() < trimmed_input.len() && flag_name.starts_with(char::is_alphabetic))
+}
+
+// Without a lane type, a lane write is an f32 if the value looks like a float, and an i32 otherwise
+pub fn guess_lane_type(input: &str) -> LaneType {
+    let trimmed_input = input.trim().to_lowercase();
+    let hex = trimmed_input.trim_start_matches('-').starts_with("0x");
+    if !hex && (trimmed_input.contains(['.', 'e']) || trimmed_input.contains("inf") || trimmed_input.contains("nan")) {
+        LaneType::F32
+    } else {
+        LaneType::I32
+    }
 }
 
 macro_rules! make_parse_uint {
@@ -67,8 +129,18 @@ pub(crate) fn parse_double_reg(input: &str) -> anyhow::Result<RegisterValue> {
     Ok(RegisterValue::F64(input.trim().parse::<f64>()?))
 }
 
-pub(crate) fn parse_long_double_reg(_input: &str) -> anyhow::Result<RegisterValue> {
-    bail!("long double unsupported");
+pub(crate) fn parse_long_double_reg(input: &str) -> anyhow::Result<RegisterValue> {
+    Ok(RegisterValue::F80(input.parse::<F80>()?))
+}
+
+pub(crate) fn parse_vector_lanes(input: &str, format: LaneFormat, size: usize) -> anyhow::Result<RegisterValue> {
+    let lanes = parse_list(input)?
+        .map(|lane| parse_lane(lane, format.lane))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    if format.count.is_some_and(|count| count != lanes.len()) || lanes.len() * format.lane.size() != size {
+        bail!("expected {} {} lanes, got {}", size / format.lane.size(), format.lane, lanes.len());
+    }
+    Ok(RegisterValue::from_lanes(format.lane, &lanes)?)
 }
 
 pub(crate) fn parse_vector_reg(input: &str, size: usize) -> anyhow::Result<RegisterValue> {
@@ -84,6 +156,16 @@ pub(crate) fn parse_vector_reg(input: &str, size: usize) -> anyhow::Result<Regis
                 .try_into()
                 .map_err(|v: Vec<u8>| anyhow!("incorrect size for vector register: {}", v.len()))?,
         ),
+        32 => RegisterValue::B256(
+            bytes
+                .try_into()
+                .map_err(|v: Vec<u8>| anyhow!("incorrect size for vector register: {}", v.len()))?,
+        ),
+        64 => RegisterValue::B512(
+            bytes
+                .try_into()
+                .map_err(|v: Vec<u8>| anyhow!("incorrect size for vector register: {}", v.len()))?,
+        ),
         _ => bail!("invalid register size: {size}"),
     })
 }
@@ -122,7 +204,96 @@ mod tests {
         "[0, 0b1, 2, 3, 0o4, 0x5, 6, 7, 8, 9, 0x0a, 11, 12, 13, 14, 15]", 16,
         RegisterValue::B128([0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15])
     )]
+    #[case(
+        "[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25, 26, 27, 28, 29, 30, 31]",
+        32, RegisterValue::B256(std::array::from_fn(|i| i as u8))
+    )]
     fn test_parse_vector(#[case] input: &str, #[case] size: usize, #[case] expected: RegisterValue) {
         assert_eq!(parse_vector_reg(input, size).unwrap(), expected);
     }
+
+    #[rstest]
+    #[case("[1.5, -2, 0, 1e3]", "f32x4", RegisterValue::from_lanes(LaneType::F32, &[
+        RegisterValue::F32(1.5), RegisterValue::F32(-2.0), RegisterValue::F32(0.0), RegisterValue::F32(1000.0),
+    ]).unwrap())]
+    #[case("[-1, 0x7fffffffffffffff]", "i64", RegisterValue::B128([
+        0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x7f,
+    ]))]
+    #[case("[1, 2, 3, 4, 5, 6, 7, 0xffff]", "u16x8", RegisterValue::B128([
+        1, 0, 2, 0, 3, 0, 4, 0, 5, 0, 6, 0, 7, 0, 0xff, 0xff,
+    ]))]
+    fn test_parse_vector_lanes(#[case] input: &str, #[case] format: &str, #[case] expected: RegisterValue) {
+        assert_eq!(parse_vector_lanes(input, format.parse().unwrap(), 16).unwrap(), expected);
+    }
+
+    #[rstest]
+    #[case("[1.5, 2.5]", "f32x4")]
+    #[case("[1, 2, 3, 4]", "f32x2")]
+    #[case("[1, 2, 3]", "i32")]
+    fn test_parse_vector_lanes_fails(#[case] input: &str, #[case] format: &str) {
+        assert_err!(parse_vector_lanes(input, format.parse().unwrap(), 16));
+    }
+
+    #[rstest]
+    #[case("-1", LaneType::I8, RegisterValue::I8(-1))]
+    #[case("0xff", LaneType::I8, RegisterValue::I8(-1))]
+    #[case("-128", LaneType::U8, RegisterValue::U8(0x80))]
+    #[case("1.5", LaneType::F32, RegisterValue::F32(1.5))]
+    #[case("65535", LaneType::U16, RegisterValue::U16(65535))]
+    fn test_parse_lane(#[case] input: &str, #[case] lane: LaneType, #[case] expected: RegisterValue) {
+        assert_eq!(parse_lane(input, lane).unwrap(), expected);
+    }
+
+    #[rstest]
+    #[case("256", LaneType::U8)]
+    #[case("-129", LaneType::I8)]
+    #[case("1.5", LaneType::I32)]
+    fn test_parse_lane_fails(#[case] input: &str, #[case] lane: LaneType) {
+        assert_err!(parse_lane(input, lane));
+    }
+
+    #[rstest]
+    #[case("xmm0", ("xmm0", None))]
+    #[case("xmm0[2]", ("xmm0", Some(2)))]
+    #[case("zmm31[0x3f]", ("zmm31", Some(63)))]
+    fn test_parse_register_lane(#[case] input: &str, #[case] expected: (&str, Option<usize>)) {
+        assert_eq!(parse_register_lane(input).unwrap(), expected);
+    }
+
+    #[rstest]
+    #[case("+ZF -CF", true)]
+    #[case("-cf", true)]
+    #[case("RC=up", true)]
+    #[case("0x246", false)]
+    #[case("-1", false)]
+    #[case("ZF", false)]
+    fn test_is_flag_change(#[case] input: &str, #[case] expected: bool) {
+        assert_eq!(is_flag_change(input), expected);
+    }
+
+    #[rstest]
+    #[case("1.5", LaneType::F32)]
+    #[case("-2e10", LaneType::F32)]
+    #[case("inf", LaneType::F32)]
+    #[case("42", LaneType::I32)]
+    #[case("0xe", LaneType::I32)]
+    fn test_guess_lane_type(#[case] input: &str, #[case] expected: LaneType) {
+        assert_eq!(guess_lane_type(input), expected);
+    }
+
+    #[rstest]
+    #[case("42.24", RegisterValue::F80(F80::from(42.24)))]
+    #[case(" -1.5 ", RegisterValue::F80(F80::from(-1.5)))]
+    #[case("inf", RegisterValue::F80(F80::from(f64::INFINITY)))]
+    #[case("0x3fff8000000000000001", RegisterValue::F80(F80::from_le_bytes([1, 0, 0, 0, 0, 0, 0, 0x80, 0xff, 0x3f])))]
+    fn test_parse_long_double(#[case] input: &str, #[case] expected: RegisterValue) {
+        assert_eq!(parse_long_double_reg(input).unwrap(), expected);
+    }
+
+    #[rstest]
+    #[case("")]
+    #[case("4x2")]
+    fn test_parse_long_double_fails(#[case] input: &str) {
+        assert_err!(parse_long_double_reg(input));
+    }
 }
@@ -1,25 +1,64 @@
+use std::collections::HashMap;
+use std::path::{
+    Path,
+    PathBuf,
+};
+
+use anyhow::{
+    anyhow,
+    bail,
+};
 use clap::{
     Args,
     Subcommand,
 };
 use libdrbug::prelude::*;
 
+use super::commands::{
+    ReplCommand,
+    parse_line,
+};
+use super::dprintf::Dprintf;
 use crate::Empty;
 
 #[derive(Subcommand)]
 pub(super) enum BreakpointCommand {
+    #[command(about = "set the commands to run when a breakpoint is hit", visible_aliases = &["cmds"])]
+    Commands(BpCommandsArgs),
+
+    #[command(about = "set or clear the condition on a breakpoint", visible_aliases = &["cond"])]
+    Condition(BpConditionArgs),
+
     #[command(about = "delete a breakpoint", visible_aliases = &["del", "rm"])]
     Delete(BpArgs),
 
     #[command(about = "disable a breakpoint", visible_aliases = &["dis"])]
     Disable(BpArgs),
 
+    #[command(about = "disable a breakpoint after it stops the process a number of times")]
+    DisableAfter(BpCountArgs),
+
+    #[command(about = "log a formatted message every time a location is reached, without stopping")]
+    Dprintf(BpDprintfArgs),
+
     #[command(about = "enable a breakpoint", visible_aliases = &["en"])]
     Enable(BpArgs),
 
+    #[command(about = "ignore the next hits of a breakpoint", visible_aliases = &["ign"])]
+    Ignore(BpCountArgs),
+
     #[command(about = "list all breakpoints", visible_aliases = &["l", "ls"])]
     List,
 
+    #[command(about = "load breakpoints from a file created by `breakpoint save`")]
+    Load(BpFileArgs),
+
+    #[command(about = "reset the hit count of a breakpoint")]
+    Reset(BpArgs),
+
+    #[command(about = "save all breakpoints to a file")]
+    Save(BpFileArgs),
+
     #[command(about = "set a breakpoint")]
     Set(BpSetArgs),
 }
@@ -30,68 +69,475 @@ pub(super) struct BpArgs {
     id: usize,
 }
 
+#[derive(Args)]
+pub(super) struct BpCommandsArgs {
+    #[arg(long_help = "id of breakpoint to operate on")]
+    id: usize,
+
+    #[arg(
+        long_help = "REPL commands to run, one per argument, e.g. \"register read rdi\"; omit to clear the command list"
+    )]
+    commands: Vec<String>,
+
+    #[arg(
+        short = 'c',
+        long = "continue",
+        long_help = "continue execution after running the commands"
+    )]
+    auto_continue: bool,
+}
+
+#[derive(Args)]
+pub(super) struct BpConditionArgs {
+    #[arg(long_help = "id of breakpoint to operate on")]
+    id: usize,
+
+    #[arg(long_help = "expression that must be true for the breakpoint to stop; omit to clear the condition")]
+    condition: Option<Expression>,
+}
+
+#[derive(Args)]
+pub(super) struct BpCountArgs {
+    #[arg(long_help = "id of breakpoint to operate on")]
+    id: usize,
+
+    #[arg(long_help = "number of hits")]
+    count: usize,
+}
+
+#[derive(Args)]
+pub(super) struct BpDprintfArgs {
+    #[arg(long_help = "where to log: a hex address, a function name, or a glob pattern like \"*parse*\"")]
+    location: BreakpointLocation,
+
+    #[arg(long_help = "printf-style format string; supports %d, %i, %u, %x, %X, %o, %p, %c, and %s")]
+    format: String,
+
+    #[arg(
+        allow_hyphen_values = true,
+        long_help = "expressions to format, e.g. rdi or \"*(u32*)(rsi+8)\""
+    )]
+    args: Vec<Expression>,
+
+    #[arg(
+        long = "if",
+        value_name = "EXPR",
+        long_help = "only log when this expression is true"
+    )]
+    condition: Option<Expression>,
+
+    #[arg(long, value_name = "N", long_help = "don't log the first N hits")]
+    ignore: Option<usize>,
+
+    #[arg(long, value_name = "N", long_help = "disable the dprintf after it logs N times")]
+    disable_after: Option<usize>,
+
+    #[arg(short, long, long_help = "delete the dprintf the first time it logs")]
+    temporary: bool,
+
+    #[arg(long, long_help = "create the dprintf without enabling it")]
+    disabled: bool,
+}
+
+#[derive(Args)]
+pub(super) struct BpFileArgs {
+    #[arg(long_help = "path of the breakpoint file")]
+    path: PathBuf,
+}
+
 #[derive(Args)]
 pub(super) struct BpSetArgs {
-    #[arg(long_help = "memory address to break on")]
-    location: VirtAddr,
+    #[arg(
+        long_help = "where to break: a hex address (e.g. 0x401000), a function name, or a glob pattern that matches \
+                     function names (e.g. \"*parse*\")"
+    )]
+    location: BreakpointLocation,
+
+    #[arg(
+        long = "if",
+        value_name = "EXPR",
+        long_help = "only stop when this expression is true, e.g. \"rdi == 0x10 && *(u32*)(rsi+8) > 5\""
+    )]
+    condition: Option<Expression>,
+
+    #[arg(long, value_name = "N", long_help = "don't stop for the first N hits")]
+    ignore: Option<usize>,
+
+    #[arg(long, value_name = "N", long_help = "disable the breakpoint after it stops N times")]
+    disable_after: Option<usize>,
+
+    #[arg(short, long, long_help = "delete the breakpoint the first time it's hit")]
+    temporary: bool,
+
+    #[arg(long, long_help = "create the breakpoint without enabling it")]
+    disabled: bool,
+}
+
+// `tbreak` is always temporary and always armed, so it doesn't get the flags for those
+#[derive(Args)]
+pub(super) struct TbreakArgs {
+    #[arg(long_help = "where to break: a hex address, a function name, or a glob pattern like \"*parse*\"")]
+    location: BreakpointLocation,
+
+    #[arg(
+        long = "if",
+        value_name = "EXPR",
+        long_help = "only stop when this expression is true"
+    )]
+    condition: Option<Expression>,
+
+    #[arg(long, value_name = "N", long_help = "don't stop for the first N hits")]
+    ignore: Option<usize>,
+}
+
+// Things to do automatically when a breakpoint is hit; these live on the REPL side, since the
+// library doesn't know anything about REPL commands
+#[derive(Clone, Default)]
+pub(super) struct BreakpointActions {
+    pub(super) auto_continue: bool,
+    pub(super) commands: Vec<String>,
+    pub(super) dprintf: Option<Dprintf>,
 }
 
+pub(super) type ActionMap = HashMap<usize, BreakpointActions>;
 
-pub(super) fn handle(command: &BreakpointCommand, proc: &mut Process) -> Empty {
+pub(super) fn handle(command: &BreakpointCommand, proc: &mut Process, actions: &mut ActionMap) -> Empty {
     match command {
-        BreakpointCommand::Delete(args) => handle_delete(proc, args.id),
+        BreakpointCommand::Commands(args) => handle_commands(proc, actions, args),
+        BreakpointCommand::Condition(args) => handle_condition(proc, args.id, &args.condition),
+        BreakpointCommand::Delete(args) => handle_delete(proc, actions, args.id),
         BreakpointCommand::Disable(args) => handle_disable(proc, args.id),
+        BreakpointCommand::DisableAfter(args) => handle_disable_after(proc, args.id, args.count),
+        BreakpointCommand::Dprintf(args) => handle_dprintf(proc, actions, args),
         BreakpointCommand::Enable(args) => handle_enable(proc, args.id),
+        BreakpointCommand::Ignore(args) => handle_ignore(proc, args.id, args.count),
         BreakpointCommand::List => {
-            handle_list(proc);
+            handle_list(proc, actions);
             Ok(())
         },
-        BreakpointCommand::Set(args) => handle_set(proc, args.location),
+        BreakpointCommand::Load(args) => handle_load(proc, actions, &args.path),
+        BreakpointCommand::Reset(args) => handle_reset(proc, args.id),
+        BreakpointCommand::Save(args) => handle_save(proc, actions, &args.path),
+        BreakpointCommand::Set(args) => handle_set(proc, args),
     }
 }
 
-fn handle_delete(proc: &mut Process, id: usize) -> Empty {
-    if proc.breakpoint_sites().get(&id).is_none() {
+pub(super) fn print_triggered(proc: &Process) {
+    if let Some(bp) = proc.triggered_breakpoint() {
+        println!("breakpoint {} hit {} time(s)", bp.id(), bp.hit_count());
+    }
+}
+
+// The actions for the breakpoint the process is currently stopped at, if any
+pub(super) fn triggered_actions(proc: &Process, actions: &ActionMap) -> Option<BreakpointActions> {
+    let bp = proc.triggered_breakpoint()?;
+    actions.get(&bp.id()).cloned()
+}
+
+// A temporary breakpoint that just stopped the process is done once its actions (if any) have
+// run; the library would delete it the next time the process stops anyway, but this way it doesn't
+// show up in the list in the meantime, and its actions go with it
+pub(super) fn forget_temporary(proc: &mut Process, actions: &mut ActionMap) -> Empty {
+    let Some(id) = proc.triggered_breakpoint().filter(|bp| bp.is_temporary()).map(|bp| bp.id()) else {
+        return Ok(());
+    };
+    actions.remove(&id);
+    proc.remove_breakpoint(&id)?;
+    Ok(())
+}
+
+fn handle_commands(proc: &mut Process, actions: &mut ActionMap, args: &BpCommandsArgs) -> Empty {
+    let id = args.id;
+    if proc.breakpoint(&id).is_none() {
+        println!("breakpoint {id} not found");
+        return Ok(());
+    }
+
+    // Check the commands now, rather than finding out about typos the first time the breakpoint
+    // is hit; commands that resume the process aren't allowed, since they'd re-enter the
+    // continue loop that's running the commands
+    for line in &args.commands {
+        let command = parse_line(line)?.command;
+        if command.resumes_process() || matches!(command, ReplCommand::Quit) {
+            bail!("`{line}` can't be used in a command list; use --continue to resume after the commands run");
+        }
+    }
+
+    let entry = actions.entry(id).or_default();
+    entry.commands = args.commands.clone();
+    entry.auto_continue = args.auto_continue;
+    if args.commands.is_empty() {
+        println!("breakpoint {id} commands cleared");
+    } else {
+        println!("breakpoint {id} will run {} command(s) when hit", args.commands.len());
+    }
+    Ok(())
+}
+
+fn handle_condition(proc: &mut Process, id: usize, condition: &Option<Expression>) -> Empty {
+    let Some(mut bp) = proc.breakpoint(&id) else {
+        println!("breakpoint {id} not found");
+        return Ok(());
+    };
+
+    bp.set_condition(condition.clone());
+    match condition {
+        Some(cond) => println!("breakpoint {id} will stop when: {cond}"),
+        None => println!("breakpoint {id} is now unconditional"),
+    }
+    Ok(())
+}
+
+fn handle_delete(proc: &mut Process, actions: &mut ActionMap, id: usize) -> Empty {
+    actions.remove(&id);
+    if proc.remove_breakpoint(&id)?.is_some() {
+        println!("breakpoint {id} deleted");
+    } else {
         println!("breakpoint {id} not found");
     }
-    proc.breakpoint_sites_mut().remove(&id)?;
-    println!("breakpoint {id} deleted");
     Ok(())
 }
 
 fn handle_disable(proc: &mut Process, id: usize) -> Empty {
-    if let Some(mut site) = proc.breakpoint_sites_mut().get(&id) {
-        site.disable()?;
-        println!("breakpoint {id} at {:#x} disabled", site.addr());
+    if let Some(mut bp) = proc.breakpoint(&id) {
+        bp.disable()?;
+        println!("breakpoint {id} at {} disabled", bp.location());
     } else {
         println!("breakpoint {id} not found");
     }
     Ok(())
 }
 
+fn handle_disable_after(proc: &mut Process, id: usize, count: usize) -> Empty {
+    let Some(mut bp) = proc.breakpoint(&id) else {
+        println!("breakpoint {id} not found");
+        return Ok(());
+    };
+
+    bp.set_disable_after(Some(count));
+    if count == 0 {
+        println!("breakpoint {id} will stay enabled");
+    } else {
+        println!("breakpoint {id} will be disabled after {count} more stop(s)");
+    }
+    Ok(())
+}
+
+fn handle_dprintf(proc: &mut Process, actions: &mut ActionMap, args: &BpDprintfArgs) -> Empty {
+    let dprintf = Dprintf::new(&args.format, args.args.clone())?;
+    let mut bp = if args.temporary {
+        proc.create_temporary_breakpoint(args.location.clone())?
+    } else {
+        proc.create_breakpoint(args.location.clone())?
+    };
+    bp.set_condition(args.condition.clone());
+    bp.set_ignore_count(args.ignore.unwrap_or(0));
+    bp.set_disable_after(args.disable_after);
+    if !args.disabled {
+        bp.enable()?;
+    }
+
+    actions.insert(
+        bp.id(),
+        BreakpointActions {
+            auto_continue: true,
+            commands: vec![],
+            dprintf: Some(dprintf),
+        },
+    );
+    println!("dprintf {} set at {}", bp.id(), bp.location());
+    print_pending(&bp);
+    Ok(())
+}
+
 fn handle_enable(proc: &mut Process, id: usize) -> Empty {
-    if let Some(mut site) = proc.breakpoint_sites_mut().get(&id) {
-        site.enable()?;
-        println!("breakpoint {id} at {:#x} enabled", site.addr());
+    if let Some(mut bp) = proc.breakpoint(&id) {
+        bp.enable()?;
+        println!("breakpoint {id} at {} enabled", bp.location());
     } else {
         println!("breakpoint {id} not found");
     }
     Ok(())
 }
 
-fn handle_list(proc: &Process) {
-    let sites = proc.breakpoint_sites();
-    if sites.is_empty() {
+fn handle_ignore(proc: &mut Process, id: usize, count: usize) -> Empty {
+    let Some(mut bp) = proc.breakpoint(&id) else {
+        println!("breakpoint {id} not found");
+        return Ok(());
+    };
+
+    bp.set_ignore_count(count);
+    println!("will ignore the next {count} hit(s) of breakpoint {id}");
+    Ok(())
+}
+
+fn handle_list(proc: &Process, actions: &ActionMap) {
+    if proc.breakpoints().next().is_none() {
         println!("no breakpoints set");
-    } else {
-        println!("current breakpoints:");
-        for (id, site) in sites.iter() {
-            println!("{id}: address = {:#x}, {}", site.addr(), if site.enabled() { "enabled" } else { "disabled" });
+        return;
+    }
+
+    println!("current breakpoints:");
+    for bp in proc.breakpoints() {
+        let id = bp.id();
+        print!(
+            "{id}: location = {}, {}, hits = {}",
+            bp.location(),
+            if bp.enabled() { "enabled" } else { "disabled" },
+            bp.hit_count()
+        );
+        if bp.is_pending() {
+            print!(", pending");
+        }
+        if bp.is_temporary() {
+            print!(", temporary");
+        }
+        if bp.ignore_count() > 0 {
+            print!(", ignore next {}", bp.ignore_count());
+        }
+        if let Some(n) = bp.disable_after() {
+            print!(", disable after {n} more stop(s)");
         }
+        if let Some(cond) = bp.condition() {
+            print!(", if {cond}");
+        }
+        println!();
+
+        // An address breakpoint only ever has the one site, so there's no point listing it
+        if !matches!(bp.location(), BreakpointLocation::Address(_) | BreakpointLocation::ModuleOffset { .. }) {
+            for (i, site) in bp.sites().iter().enumerate() {
+                println!("    {id}.{}: address = {:#x}, hits = {}", i + 1, site.addr(), site.hit_count());
+            }
+        }
+
+        if let Some(bp_actions) = actions.get(&id) {
+            if let Some(dprintf) = &bp_actions.dprintf {
+                println!("    dprintf {dprintf}");
+            }
+            for line in &bp_actions.commands {
+                println!("    {line}");
+            }
+            if bp_actions.auto_continue && bp_actions.dprintf.is_none() {
+                println!("    (continue)");
+            }
+        }
+    }
+}
+
+// A saved file is just the REPL commands that would recreate the breakpoints, so we only accept
+// the commands that `breakpoint save` writes; a line that doesn't work out (say, because there's
+// already a breakpoint at that address) gets reported without stopping the rest from loading
+fn handle_load(proc: &mut Process, actions: &mut ActionMap, path: &Path) -> Empty {
+    let contents = std::fs::read_to_string(path)?;
+    let mut loaded = 0;
+    for (i, line) in contents.lines().enumerate() {
+        if line.trim().is_empty() || line.trim_start().starts_with('#') {
+            continue;
+        }
+
+        let res = match parse_line(line).map(|root| root.command) {
+            Ok(ReplCommand::Breakpoint(BreakpointCommand::Set(args))) => handle_set(proc, &args),
+            Ok(ReplCommand::Breakpoint(BreakpointCommand::Dprintf(args))) => handle_dprintf(proc, actions, &args),
+            Ok(_) => Err(anyhow!("only `breakpoint set` and `breakpoint dprintf` are allowed")),
+            Err(err) => Err(err),
+        };
+        match res {
+            Ok(()) => loaded += 1,
+            Err(err) => println!("{}:{}: {err}", path.display(), i + 1),
+        }
+    }
+    println!("loaded {loaded} breakpoint(s) from {}", path.display());
+    Ok(())
+}
+
+fn handle_reset(proc: &mut Process, id: usize) -> Empty {
+    if let Some(mut bp) = proc.breakpoint(&id) {
+        bp.reset_hit_count();
+        println!("breakpoint {id} hit count reset");
+    } else {
+        println!("breakpoint {id} not found");
     }
+    Ok(())
+}
+
+// Addresses get turned into module offsets on the way out, so that the file still works in the
+// next session even if everything gets loaded somewhere else.  Command lists aren't saved, since
+// they're tied to the breakpoint ids from this session.
+fn handle_save(proc: &Process, actions: &ActionMap, path: &Path) -> Empty {
+    let mut contents = String::new();
+    let mut saved = 0;
+    for bp in proc.breakpoints() {
+        contents.push_str(&save_line(proc, bp, actions.get(&bp.id()))?);
+        contents.push('\n');
+        saved += 1;
+    }
+    std::fs::write(path, contents)?;
+    println!("saved {saved} breakpoint(s) to {}", path.display());
+    Ok(())
 }
 
-fn handle_set(proc: &mut Process, loc: VirtAddr) -> Empty {
-    proc.create_breakpoint_site(loc)?.enable()?;
+pub(super) fn handle_tbreak(proc: &mut Process, args: &TbreakArgs) -> Empty {
+    let mut bp = proc.create_temporary_breakpoint(args.location.clone())?;
+    bp.set_condition(args.condition.clone());
+    bp.set_ignore_count(args.ignore.unwrap_or(0));
+    bp.enable()?;
+    print_pending(&bp);
     Ok(())
 }
+
+fn handle_set(proc: &mut Process, args: &BpSetArgs) -> Empty {
+    let mut bp = if args.temporary {
+        proc.create_temporary_breakpoint(args.location.clone())?
+    } else {
+        proc.create_breakpoint(args.location.clone())?
+    };
+    bp.set_condition(args.condition.clone());
+    bp.set_ignore_count(args.ignore.unwrap_or(0));
+    bp.set_disable_after(args.disable_after);
+    if !args.disabled {
+        bp.enable()?;
+    }
+    print_pending(&bp);
+    Ok(())
+}
+
+fn print_pending(bp: &Breakpoint) {
+    if bp.is_pending() {
+        println!("breakpoint {} is pending; {} doesn't match any functions yet", bp.id(), bp.location());
+    }
+}
+
+fn save_line(proc: &Process, bp: &Breakpoint, bp_actions: Option<&BreakpointActions>) -> anyhow::Result<String> {
+    let location = match bp.location() {
+        BreakpointLocation::Address(addr) => proc.module_location(*addr)?.unwrap_or_else(|| bp.location().clone()),
+        location => location.clone(),
+    };
+
+    // Flags go before the positional arguments, since dprintf's expression list would swallow
+    // anything that comes after it
+    let mut args = vec!["breakpoint".to_string()];
+    let dprintf = bp_actions.and_then(|a| a.dprintf.as_ref());
+    args.push(if dprintf.is_some() { "dprintf" } else { "set" }.into());
+    if let Some(cond) = bp.condition() {
+        args.extend(["--if".into(), cond.to_string()]);
+    }
+    if bp.is_temporary() {
+        args.push("--temporary".into());
+    }
+    if !bp.enabled() {
+        args.push("--disabled".into());
+    }
+    if bp.ignore_count() > 0 {
+        args.extend(["--ignore".into(), bp.ignore_count().to_string()]);
+    }
+    if let Some(n) = bp.disable_after() {
+        args.extend(["--disable-after".into(), n.to_string()]);
+    }
+
+    args.push(location.to_string());
+    if let Some(dprintf) = dprintf {
+        args.push(dprintf.format().into());
+        args.extend(dprintf.args().iter().map(|expr| expr.to_string()));
+    }
+    Ok(shlex::try_join(args.iter().map(String::as_str))?)
+}
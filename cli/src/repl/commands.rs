@@ -1,3 +1,4 @@
+use anyhow::anyhow;
 use clap::{
     Parser,
     Subcommand,
@@ -5,8 +6,12 @@ use clap::{
 
 use super::breakpoint::*;
 use super::disassemble::*;
+use super::execution::*;
 use super::memory::*;
+use super::record::*;
 use super::register::*;
+use super::trace::*;
+use super::watchpoint::*;
 
 #[derive(Parser)]
 #[command(
@@ -22,6 +27,9 @@ pub(super) struct DrbRootCommand {
 
 #[derive(Subcommand)]
 pub(super) enum ReplCommand {
+    #[command(about = "run until the process reaches a location", visible_aliases = &["adv"])]
+    Advance(AdvanceArgs),
+
     #[command(subcommand, about = "manage breakpoints", visible_aliases = &["b", "br", "bp", "break"])]
     Breakpoint(BreakpointCommand),
 
@@ -31,15 +39,78 @@ pub(super) enum ReplCommand {
     #[command(about = "disassemble machine code to assembly", visible_aliases = &["dis"])]
     Disassemble(DisassembleArgs),
 
+    #[command(about = "run until the current function returns", visible_aliases = &["fin"])]
+    Finish,
+
     #[command(subcommand, about = "read and write to memory locations", visible_aliases = &["mem"])]
     Memory(MemoryCommand),
 
+    #[command(about = "step over a single instruction, running any function it calls to completion", visible_aliases = &["n", "nexti", "ni"])]
+    Next,
+
+    #[command(subcommand, about = "record execution history for reverse stepping", visible_aliases = &["rec"])]
+    Record(RecordCommand),
+
     #[command(subcommand, about = "interact with registers", visible_aliases = &["reg"])]
     Register(RegisterCommand),
 
+    #[command(about = "kill the process and start it over from the beginning", visible_aliases = &["run", "r"])]
+    Restart,
+
+    #[command(about = "run backwards through the recorded history until a breakpoint", visible_aliases = &["rc"])]
+    ReverseContinue,
+
+    #[command(about = "undo the most recently recorded instruction", visible_aliases = &["rsi"])]
+    ReverseStepi,
+
+    #[command(about = "restart the process and run until it reaches `main`")]
+    Start,
+
     #[command(about ="step over a single instruction", visible_aliases = &["s", "st"])]
     Step,
 
+    #[command(about = "run until the next taken branch", visible_aliases = &["stepb", "sb"])]
+    StepBlock,
+
     #[command(about = "stop debugging", visible_aliases = &["exit", "q"])]
     Quit,
+
+    #[command(about = "set a temporary breakpoint, which is deleted the first time it's hit", visible_aliases = &["tb"])]
+    Tbreak(TbreakArgs),
+
+    #[command(about = "single-step the process, writing each instruction and the registers it changes to a file")]
+    Trace(TraceArgs),
+
+    #[command(about = "run until the process gets past the current instruction, or reaches a location", visible_aliases = &["u"])]
+    Until(UntilArgs),
+
+    #[command(subcommand, about = "manage watchpoints", visible_aliases = &["w", "wp", "watch"])]
+    Watchpoint(WatchpointCommand),
+}
+
+impl ReplCommand {
+    // Commands that let the process run; these can't go in a breakpoint's command list, since the
+    // list runs in the middle of a continue, so any new command that resumes has to be added here
+    pub(super) fn resumes_process(&self) -> bool {
+        matches!(
+            self,
+            ReplCommand::Advance(_)
+                | ReplCommand::Continue
+                | ReplCommand::Finish
+                | ReplCommand::Next
+                | ReplCommand::Restart
+                | ReplCommand::ReverseContinue
+                | ReplCommand::ReverseStepi
+                | ReplCommand::Start
+                | ReplCommand::Step
+                | ReplCommand::StepBlock
+                | ReplCommand::Trace(_)
+                | ReplCommand::Until(_)
+        )
+    }
+}
+
+pub(super) fn parse_line(line: &str) -> anyhow::Result<DrbRootCommand> {
+    let tokens = shlex::split(line).ok_or(anyhow!("parse error"))?;
+    Ok(DrbRootCommand::try_parse_from(tokens)?)
 }
@@ -0,0 +1,224 @@
+use std::fmt;
+
+use anyhow::bail;
+use itertools::Itertools;
+use libdrbug::prelude::*;
+
+const MAX_STRING_LEN: usize = 256;
+const STRING_CHUNK_SIZE: usize = 16;
+
+// A dprintf breakpoint prints a printf-style log line every time it's hit, and then keeps going.
+// The supported conversions are %d, %i, %u, %x, %X, %o, %p, %c, and %s (which reads a
+// NUL-terminated string from the inferior's memory), with optional `#`, `0`, and `-` flags and a
+// field width.
+#[derive(Clone)]
+pub(super) struct Dprintf {
+    format: String,
+    pieces: Vec<FormatPiece>,
+    args: Vec<Expression>,
+}
+
+impl Dprintf {
+    pub(super) fn new(format: &str, args: Vec<Expression>) -> anyhow::Result<Self> {
+        let pieces = parse_format(format)?;
+        let spec_count = pieces.iter().filter(|p| matches!(p, FormatPiece::Spec(_))).count();
+        if spec_count != args.len() {
+            bail!("format string expects {spec_count} argument(s) but {} were given", args.len());
+        }
+        Ok(Dprintf { format: format.into(), pieces, args })
+    }
+
+    pub(super) fn args(&self) -> &[Expression] {
+        &self.args
+    }
+
+    pub(super) fn format(&self) -> &str {
+        &self.format
+    }
+
+    pub(super) fn render(&self, proc: &Process) -> anyhow::Result<String> {
+        let mut args = self.args.iter();
+        let mut line = String::new();
+        for piece in &self.pieces {
+            match piece {
+                FormatPiece::Literal(text) => line.push_str(text),
+                FormatPiece::Spec(spec) => {
+                    // We checked that the number of arguments matches in `new`
+                    let value = args.next().unwrap().evaluate(proc)?;
+                    if spec.conv == 's' {
+                        line.push_str(&spec.pad("", &read_c_string(proc, VirtAddr(value as u64))?));
+                    } else {
+                        line.push_str(&spec.format_int(value));
+                    }
+                },
+            }
+        }
+        Ok(line)
+    }
+}
+
+impl fmt::Display for Dprintf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.format)?;
+        if !self.args.is_empty() {
+            write!(f, ", {}", self.args.iter().join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum FormatPiece {
+    Literal(String),
+    Spec(FormatSpec),
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+struct FormatSpec {
+    alt: bool,
+    left: bool,
+    zero: bool,
+    width: usize,
+    conv: char,
+}
+
+impl FormatSpec {
+    fn format_int(&self, value: i128) -> String {
+        let (prefix, body) = match self.conv {
+            'd' | 'i' => {
+                let v = value as i64;
+                (if v < 0 { "-" } else { "" }, v.unsigned_abs().to_string())
+            },
+            'u' => ("", (value as u64).to_string()),
+            'x' => (if self.alt { "0x" } else { "" }, format!("{:x}", value as u64)),
+            'X' => (if self.alt { "0X" } else { "" }, format!("{:X}", value as u64)),
+            'o' => (if self.alt { "0" } else { "" }, format!("{:o}", value as u64)),
+            'p' => ("0x", format!("{:x}", value as u64)),
+            'c' => ("", (value as u8 as char).to_string()),
+            _ => unreachable!("invalid conversions are rejected when parsing"),
+        };
+        self.pad(prefix, &body)
+    }
+
+    // Zero-padding goes in between the sign or base prefix and the digits, like printf does it
+    fn pad(&self, prefix: &str, body: &str) -> String {
+        let fill = self.width.saturating_sub(prefix.len() + body.chars().count());
+        if self.left {
+            format!("{prefix}{body}{}", " ".repeat(fill))
+        } else if self.zero && !matches!(self.conv, 'c' | 's') {
+            format!("{prefix}{}{body}", "0".repeat(fill))
+        } else {
+            format!("{}{prefix}{body}", " ".repeat(fill))
+        }
+    }
+}
+
+fn parse_format(format: &str) -> anyhow::Result<Vec<FormatPiece>> {
+    let mut pieces = vec![];
+    let mut literal = String::new();
+    let mut chars = format.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            literal.push(c);
+            continue;
+        }
+        if chars.next_if_eq(&'%').is_some() {
+            literal.push('%');
+            continue;
+        }
+
+        let mut spec = FormatSpec::default();
+        while let Some(flag) = chars.next_if(|&c| matches!(c, '#' | '0' | '-')) {
+            match flag {
+                '#' => spec.alt = true,
+                '0' => spec.zero = true,
+                _ => spec.left = true,
+            }
+        }
+        while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+            spec.width = spec.width * 10 + digit.to_digit(10).unwrap() as usize;
+        }
+        spec.conv = match chars.next() {
+            Some(conv @ ('d' | 'i' | 'u' | 'x' | 'X' | 'o' | 'p' | 'c' | 's')) => conv,
+            Some(conv) => bail!("unsupported conversion: %{conv}"),
+            None => bail!("incomplete conversion at end of format string"),
+        };
+
+        if !literal.is_empty() {
+            pieces.push(FormatPiece::Literal(std::mem::take(&mut literal)));
+        }
+        pieces.push(FormatPiece::Spec(spec));
+    }
+
+    if !literal.is_empty() {
+        pieces.push(FormatPiece::Literal(literal));
+    }
+    Ok(pieces)
+}
+
+fn read_c_string(proc: &Process, addr: VirtAddr) -> anyhow::Result<String> {
+    let mut bytes = vec![];
+    while bytes.len() < MAX_STRING_LEN {
+        let chunk = proc.read_memory_without_traps(addr.add(bytes.len()), STRING_CHUNK_SIZE)?;
+        if let Some(end) = chunk.iter().position(|&b| b == 0) {
+            bytes.extend_from_slice(&chunk[..end]);
+            break;
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+    Ok(String::from_utf8_lossy(&bytes).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use assertables::*;
+    use rstest::*;
+
+    use super::*;
+
+    #[rstest]
+    #[case("%d", -42, "-42")]
+    #[case("%5d", -42, "  -42")]
+    #[case("%05d", -42, "-0042")]
+    #[case("%-5d|", 42, "42   |")]
+    #[case("%u", -1, "18446744073709551615")]
+    #[case("%x", 0xcafe, "cafe")]
+    #[case("%#X", 0xcafe, "0XCAFE")]
+    #[case("%#010x", 0xcafe, "0x0000cafe")]
+    #[case("%o", 8, "10")]
+    #[case("%p", 0x1000, "0x1000")]
+    #[case("%c", 0x41, "A")]
+    fn test_format_int(#[case] format: &str, #[case] value: i128, #[case] expected: &str) {
+        let pieces = parse_format(format).unwrap();
+        let output: String = pieces
+            .iter()
+            .map(|p| match p {
+                FormatPiece::Literal(text) => text.clone(),
+                FormatPiece::Spec(spec) => spec.format_int(value),
+            })
+            .collect();
+        assert_eq!(output, expected);
+    }
+
+    #[rstest]
+    fn test_parse_format() {
+        let pieces = parse_format("rdi = %#x, 100%%").unwrap();
+        assert_eq!(
+            pieces,
+            vec![
+                FormatPiece::Literal("rdi = ".into()),
+                FormatPiece::Spec(FormatSpec { alt: true, conv: 'x', ..Default::default() }),
+                FormatPiece::Literal(", 100%".into()),
+            ]
+        );
+    }
+
+    #[rstest]
+    #[case("%")]
+    #[case("%5")]
+    #[case("%f")]
+    #[case("%lld")]
+    fn test_parse_format_fails(#[case] format: &str) {
+        assert_err!(parse_format(format));
+    }
+}
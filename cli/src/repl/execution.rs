@@ -0,0 +1,40 @@
+use clap::Args;
+use libdrbug::prelude::*;
+
+use crate::Empty;
+
+#[derive(Args)]
+pub(super) struct AdvanceArgs {
+    #[arg(long_help = "memory address to run to")]
+    pub(super) location: VirtAddr,
+}
+
+#[derive(Args)]
+pub(super) struct UntilArgs {
+    #[arg(long_help = "memory address to run to; defaults to the instruction after the current one")]
+    pub(super) location: Option<VirtAddr>,
+}
+
+// Without a location, `until` runs to the instruction right after the current one; at the bottom
+// of a loop that's a quick way to skip the rest of the iterations, since the backwards jump never
+// lands there until the loop is finished
+pub(super) fn until_location(proc: &mut Process, location: Option<VirtAddr>) -> anyhow::Result<VirtAddr> {
+    if let Some(addr) = location {
+        return Ok(addr);
+    }
+
+    let instructions = Disassembler::new(proc).disassemble(None, 1)?;
+    Ok(VirtAddr(instructions[0].next_ip()))
+}
+
+// Integer and pointer return values come back in rax and floating-point ones in xmm0; we don't know
+// what the function was supposed to return, so we show both
+pub(super) fn print_return_values(proc: &Process) -> Empty {
+    let rax = proc.get_registers().read(register_info_by_name("rax")?)?;
+    let RegisterValue::B128(xmm0) = proc.get_registers().read(register_info_by_name("xmm0")?)? else {
+        panic!("should never happen");
+    };
+    let double = f64::from_le_bytes(xmm0[..8].try_into()?);
+    println!("returned rax = {rax}, xmm0 = {double}");
+    Ok(())
+}
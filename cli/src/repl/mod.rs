@@ -1,28 +1,49 @@
 mod breakpoint;
 mod commands;
 mod disassemble;
+mod dprintf;
+mod execution;
 mod memory;
+mod record;
 mod register;
+mod trace;
+mod watchpoint;
 
-use anyhow::anyhow;
-use clap::Parser;
 use libdrbug::prelude::*;
 use rustyline::DefaultEditor;
 use rustyline::error::ReadlineError;
 
+use self::breakpoint::ActionMap;
 use self::commands::*;
 use self::disassemble::print_disassembly;
+use self::execution::{
+    print_return_values,
+    until_location,
+};
 use crate::Empty;
 
 pub struct Repl {
+    breakpoint_actions: ActionMap,
     proc: Process,
     rl: DefaultEditor,
     running: bool,
+    show_register_changes: bool,
 }
 
 impl Repl {
-    pub fn new(proc: Process) -> anyhow::Result<Repl> {
-        Ok(Repl { proc, rl: DefaultEditor::new()?, running: true })
+    pub fn new(mut proc: Process) -> anyhow::Result<Repl> {
+        // The place we start out is the first stop, so the first continue has something to compare to
+        if proc.state().is_stopped() {
+            proc.get_registers_mut().record_stop()?;
+        }
+
+        Ok(Repl {
+            breakpoint_actions: ActionMap::new(),
+            proc,
+            rl: DefaultEditor::new()?,
+            running: true,
+            show_register_changes: false,
+        })
     }
 
     pub fn start(&mut self) -> Empty {
@@ -53,34 +74,155 @@ impl Repl {
 
     fn handle_line(&mut self, line: String) -> Empty {
         self.rl.add_history_entry(line.as_str())?;
-        let tokens = shlex::split(&line).ok_or(anyhow!("parse error"))?;
-        let root = DrbRootCommand::try_parse_from(tokens)?;
+        let res = self.run_command(&line);
+        breakpoint::forget_temporary(&mut self.proc, &mut self.breakpoint_actions)?;
+        res
+    }
 
+    fn run_command(&mut self, line: &str) -> Empty {
+        let root = parse_line(line)?;
         match &root.command {
-            ReplCommand::Breakpoint(cmd) => breakpoint::handle(cmd, &mut self.proc)?,
-            ReplCommand::Continue => {
-                self.proc.resume()?;
-                let status = self.proc.wait_on_signal()?;
+            ReplCommand::Advance(args) => {
+                let status = self.proc.run_to(args.location)?;
                 self.print_stop_reason(status)?;
             },
+            ReplCommand::Breakpoint(cmd) => breakpoint::handle(cmd, &mut self.proc, &mut self.breakpoint_actions)?,
+            ReplCommand::Continue => self.continue_execution()?,
             ReplCommand::Disassemble(args) => print_disassembly(&mut self.proc, args.addr, args.instr_count)?,
+            ReplCommand::Finish => {
+                let (return_addr, _) = self.proc.return_frame()?;
+                let status = self.proc.step_out()?;
+                self.print_stop_reason(status)?;
+                if status.is_stopped() && self.proc.get_pc()? == return_addr {
+                    print_return_values(&self.proc)?;
+                }
+            },
             ReplCommand::Memory(cmd) => memory::handle(cmd, &mut self.proc)?,
-            ReplCommand::Register(cmd) => register::handle(cmd, &mut self.proc)?,
+            ReplCommand::Next => {
+                let status = self.proc.step_over_instruction()?;
+                self.print_stop_reason(status)?;
+            },
+            ReplCommand::Record(cmd) => record::handle(cmd, &mut self.proc)?,
+            ReplCommand::Register(cmd) => register::handle(cmd, &mut self.proc, &mut self.show_register_changes)?,
+            ReplCommand::Restart => {
+                let status = self.proc.restart(None)?;
+                self.print_stop_reason(status)?;
+            },
+            ReplCommand::ReverseContinue => {
+                let status = self.proc.reverse_continue()?;
+                self.print_stop_reason(status)?;
+            },
+            ReplCommand::ReverseStepi => {
+                let status = self.proc.reverse_step_instruction()?;
+                self.print_stop_reason(status)?;
+            },
+            ReplCommand::Start => {
+                let status = self.proc.restart(Some(StopAt::Main))?;
+                self.print_stop_reason(status)?;
+            },
             ReplCommand::Step => {
                 let status = self.proc.step_instruction()?;
                 self.print_stop_reason(status)?;
             },
+            ReplCommand::StepBlock => {
+                let status = self.proc.step_block()?;
+                self.print_stop_reason(status)?;
+            },
             ReplCommand::Quit => {
                 self.running = false;
             },
+            ReplCommand::Tbreak(args) => breakpoint::handle_tbreak(&mut self.proc, args)?,
+            ReplCommand::Trace(args) => {
+                let status = trace::handle(args, &mut self.proc)?;
+                self.print_stop_reason(status)?;
+            },
+            ReplCommand::Until(args) => {
+                let addr = until_location(&mut self.proc, args.location)?;
+                let status = self.proc.run_to(addr)?;
+                self.print_stop_reason(status)?;
+            },
+            ReplCommand::Watchpoint(cmd) => watchpoint::handle(cmd, &mut self.proc)?,
         }
 
         Ok(())
     }
 
-    fn print_stop_reason(&self, status: ProcessState) -> Empty {
+    // Breakpoints with actions attached run them every time they're hit, and if they're set to
+    // auto-continue (which dprintf breakpoints always are), we keep going without giving control
+    // back to the user
+    fn continue_execution(&mut self) -> Empty {
+        loop {
+            self.proc.resume()?;
+            let status = self.proc.wait_on_signal()?;
+            let Some(actions) = breakpoint::triggered_actions(&self.proc, &self.breakpoint_actions) else {
+                return self.print_stop_reason(status);
+            };
+
+            if let Some(dprintf) = &actions.dprintf {
+                match dprintf.render(&self.proc) {
+                    Ok(line) => println!("{line}"),
+                    Err(err) => println!("dprintf failed: {err}"),
+                }
+            } else {
+                self.print_stop_reason(status)?;
+            }
+
+            for line in &actions.commands {
+                if let Err(err) = self.run_command(line) {
+                    println!("{err}");
+                }
+            }
+            breakpoint::forget_temporary(&mut self.proc, &mut self.breakpoint_actions)?;
+
+            if !actions.auto_continue {
+                return Ok(());
+            }
+        }
+    }
+
+    fn print_stop_reason(&mut self, status: ProcessState) -> Empty {
+        // Once the process is gone, there's no pc (or anything else) left to show
+        if !status.is_stopped() {
+            println!("process {}: {status}", self.proc.pid());
+            return Ok(());
+        }
+
         let pc = self.proc.get_pc()?;
         println!("process {}: {status} at {pc}", self.proc.pid());
+        breakpoint::print_triggered(&self.proc);
+        watchpoint::print_triggered(&self.proc);
+
+        self.proc.get_registers_mut().record_stop()?;
+        if self.show_register_changes {
+            register::print_changes(&self.proc)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assertables::*;
+    use rstest::*;
+
+    use super::*;
+
+    const COUNT_PATH: &str = "../target/debug/count";
+
+    #[rstest]
+    fn test_temporary_dprintf() -> Empty {
+        let mut repl = Repl::new(Process::launch(COUNT_PATH, Default::default())?)?;
+        repl.continue_execution()?; // `count` stops itself once it's printed the address of `tick`
+
+        repl.run_command(r#"breakpoint dprintf -t "*count4tick*" "tick(%d)" rdi"#)?;
+        let bp = repl.proc.breakpoints().next().cloned().unwrap();
+
+        // It logs the first call to `tick`, and then gets out of the way for the rest of them
+        repl.continue_execution()?;
+        assert_matches!(repl.proc.state(), ProcessState::Exited { exit_code: 0 });
+        assert_eq!(bp.hit_count(), 1);
+        assert_none!(repl.proc.breakpoint(&bp.id()));
+        assert_is_empty!(repl.breakpoint_actions);
         Ok(())
     }
 }
@@ -0,0 +1,48 @@
+use clap::{
+    Args,
+    Subcommand,
+};
+use libdrbug::prelude::*;
+
+use crate::Empty;
+
+#[derive(Subcommand)]
+pub(super) enum RecordCommand {
+    #[command(about = "start recording instructions, so that they can be stepped through backwards")]
+    Start(RecordStartArgs),
+
+    #[command(about = "show how many instructions have been recorded", visible_aliases = &["st"])]
+    Status,
+
+    #[command(about = "stop recording and throw away the recorded history")]
+    Stop,
+}
+
+#[derive(Args)]
+pub(super) struct RecordStartArgs {
+    #[arg(
+        short,
+        long,
+        default_value_t = DEFAULT_RECORD_LIMIT,
+        long_help = "maximum number of instructions to keep; older ones are forgotten"
+    )]
+    limit: usize,
+}
+
+pub(super) fn handle(command: &RecordCommand, proc: &mut Process) -> Empty {
+    match command {
+        RecordCommand::Start(args) => {
+            proc.start_recording(args.limit);
+            println!("recording (up to {} instructions); execution will be much slower", args.limit);
+        },
+        RecordCommand::Status => {
+            if proc.is_recording() {
+                println!("recording, {} instructions in history", proc.recorded_steps());
+            } else {
+                println!("not recording");
+            }
+        },
+        RecordCommand::Stop => proc.stop_recording(),
+    }
+    Ok(())
+}
@@ -1,3 +1,8 @@
+use std::io::{
+    IsTerminal,
+    stdout,
+};
+
 use clap::{
     Args,
     Subcommand,
@@ -5,10 +10,23 @@ use clap::{
 use libdrbug::prelude::*;
 
 use crate::Empty;
-use crate::parsing::parse_for_register;
+use crate::parsing::{
+    guess_lane_type,
+    is_flag_change,
+    parse_for_register,
+    parse_lane,
+    parse_register_lane,
+    parse_vector_lanes,
+};
 
 #[derive(Subcommand)]
 pub(super) enum RegisterCommand {
+    #[command(about = "show the registers that changed since the last stop", visible_aliases = &["c"])]
+    Changes(RegChangesArgs),
+
+    #[command(about = "show a register's value at each of the last few stops", visible_aliases = &["h"])]
+    History(RegHistoryArgs),
+
     #[command(about = "read from the program registers", visible_aliases = &["r"])]
     Read(RegReadArgs),
 
@@ -16,34 +34,110 @@ pub(super) enum RegisterCommand {
     Write(RegWriteArgs),
 }
 
+#[derive(Args)]
+pub(super) struct RegChangesArgs {
+    #[arg(
+        long,
+        long_help = "turn on (true) or off (false) showing the changed registers every time the process stops"
+    )]
+    auto: Option<bool>,
+}
+
+#[derive(Args)]
+pub(super) struct RegHistoryArgs {
+    #[arg(long_help = "register name to show the history of")]
+    reg: String,
+}
+
 #[derive(Args)]
 pub(super) struct RegReadArgs {
-    #[arg(long_help = "register name to read")]
+    #[arg(
+        long_help = "register name to read; `all` reads every register, `avx` and `avx512` read the AVX registers, and `x87` shows the x87 register stack"
+    )]
     regs: Option<String>,
+
+    #[arg(
+        long = "as",
+        long_help = "show vector registers as lanes of this type, e.g. f32x4, u16x8, or just i8"
+    )]
+    lanes: Option<LaneFormat>,
 }
 
 #[derive(Args, Clone)]
 pub(super) struct RegWriteArgs {
-    #[arg(long_help = "register name to write")]
+    #[arg(long_help = "register name to write; use e.g. xmm0[2] to write a single lane of a vector register")]
     reg: String,
 
-    #[arg(long_help = "value to write to the register")]
-    value: String,
+    #[arg(
+        allow_hyphen_values = true,
+        num_args = 1..,
+        required = true,
+        long_help = "value to write to the register; with --as, a vector register takes a list like [1.5, 2, 3, 4], \
+                     x87 registers (st0-st7) also take their exact 80 bits as 20 hex digits like 0x3fff8000000000000000, and \
+                     flags registers (eflags, mxcsr, fsw, fcw, dr6, dr7) take changes like +ZF -CF or RC=up"
+    )]
+    value: Vec<String>,
+
+    #[arg(
+        long = "as",
+        long_help = "lane type for vector registers, e.g. f32x4; a single-lane write without this is f32 if the value looks like a float and i32 otherwise \
+                     (this has to come before the register name, since everything after the register is part of the value)"
+    )]
+    lanes: Option<LaneFormat>,
 }
 
-pub(super) fn handle(command: &RegisterCommand, proc: &mut Process) -> Empty {
+pub(super) fn handle(command: &RegisterCommand, proc: &mut Process, show_changes: &mut bool) -> Empty {
     match command {
+        RegisterCommand::Changes(args) => handle_changes(args, proc, show_changes),
+        RegisterCommand::History(args) => handle_history(args, proc),
         RegisterCommand::Read(args) => handle_read(args, proc),
         RegisterCommand::Write(args) => handle_write(args, proc),
     }
 }
 
+// Called with every stop report when the changes are turned on, so it doesn't say anything if
+// nothing changed
+pub(super) fn print_changes(proc: &Process) -> Empty {
+    for (id, value) in proc.get_registers().changes_since_last_stop()? {
+        println!("{}:\t{value}", register_info_by_id(&id).name);
+    }
+    Ok(())
+}
+
+fn handle_changes(args: &RegChangesArgs, proc: &mut Process, show_changes: &mut bool) -> Empty {
+    match args.auto {
+        Some(auto) => *show_changes = auto,
+        None if proc.get_registers().changes_since_last_stop()?.is_empty() => println!("no registers changed"),
+        None => print_changes(proc)?,
+    }
+    Ok(())
+}
+
+// Oldest first, numbered backwards from the current stop (which is 0)
+fn handle_history(args: &RegHistoryArgs, proc: &mut Process) -> Empty {
+    let info = register_info_by_name(&args.reg)?;
+    let history = proc.get_registers().history(info)?;
+    if history.is_empty() {
+        println!("no stops recorded yet");
+    }
+    for (i, value) in history.iter().enumerate() {
+        println!("{}:\t{value}", i as isize + 1 - history.len() as isize);
+    }
+    Ok(())
+}
+
 fn handle_read(args: &RegReadArgs, proc: &mut Process) -> Empty {
     let reg_values = match args.regs.as_deref() {
         Some("all") => proc.get_registers().read_group(None)?,
+        Some("avx") => proc.get_registers().read_group(Some(RegisterType::Avx))?,
+        Some("avx512") => proc.get_registers().read_group(Some(RegisterType::Avx512))?,
+        Some("x87") => {
+            print_x87_stack(&proc.get_registers().x87_stack()?);
+            return Ok(());
+        },
         Some(name) => {
             let info = register_info_by_name(name)?;
-            vec![(name, Some(proc.get_registers().read(info)?))]
+            vec![(name, proc.get_registers().read(info)?)]
         },
         None => proc.get_registers().read_group(Some(RegisterType::General))?,
     };
@@ -52,17 +146,54 @@ fn handle_read(args: &RegReadArgs, proc: &mut Process) -> Empty {
         if reg == "orig_rax" {
             continue;
         }
-        match value {
-            Some(v) => println!("{reg}:\t{v}"),
-            None => println!("{reg}:\tnone (unsupported)"),
+        let info = register_info_by_name(reg)?;
+        let reg = highlight(reg, proc.get_registers().changed_since_last_stop(info));
+        match (args.lanes, Flags::decode(info, &value)) {
+            (Some(format), _) if value.is_vector() => println!("{reg}:\t{}", value.lanes(format)?),
+            (_, Some(flags)) => println!("{reg}:\t{value} {flags}"),
+            _ => println!("{reg}:\t{value}"),
         }
     }
     Ok(())
 }
 
+// Registers that changed since the last stop stand out in bold yellow, or get a `*` if the output
+// isn't going to a terminal that understands the escape codes
+fn highlight(reg: &str, changed: bool) -> String {
+    match changed {
+        true if stdout().is_terminal() => format!("\x1b[1;33m{reg}\x1b[0m"),
+        true => format!("{reg}*"),
+        false => reg.into(),
+    }
+}
+
+fn print_x87_stack(stack: &X87Stack) {
+    println!("TOP:\t{} ({} in use)", stack.top, stack.depth());
+    for reg in &stack.registers {
+        match reg.tag {
+            X87Tag::Empty => println!("st{} (r{}):\t{}", reg.st, reg.physical, reg.tag),
+            _ => println!("st{} (r{}):\t{}\t{}", reg.st, reg.physical, reg.tag, reg.value),
+        }
+    }
+}
+
 fn handle_write(args: &RegWriteArgs, proc: &mut Process) -> Empty {
-    let info = register_info_by_name(&args.reg)?;
-    let value = parse_for_register(info, &args.value)?;
+    let (name, lane) = parse_register_lane(&args.reg)?;
+    let info = register_info_by_name(name)?;
+    let input = args.value.join(" ");
+    let current = proc.get_registers().read(info)?;
+    let value = match (lane, args.lanes, Flags::decode(info, &current)) {
+        (None, None, Some(mut flags)) if is_flag_change(&input) => {
+            flags.apply(&input)?;
+            flags.to_register_value()
+        },
+        (None, None, _) => parse_for_register(info, &input)?,
+        (None, Some(format), _) => parse_vector_lanes(&input, format, info.size)?,
+        (Some(index), format, _) => {
+            let lane_type = format.map_or_else(|| guess_lane_type(&input), |f| f.lane);
+            current.with_lane(lane_type, index, &parse_lane(&input, lane_type)?)?
+        },
+    };
     proc.get_registers_mut().write(info, value)?;
     Ok(())
 }
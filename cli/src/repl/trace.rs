@@ -0,0 +1,49 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::PathBuf;
+
+use clap::Args;
+use libdrbug::prelude::*;
+
+#[derive(Args)]
+pub(super) struct TraceArgs {
+    #[arg(short, long, long_help = "file to write the trace to")]
+    output: PathBuf,
+
+    #[arg(short, long, default_value_t = TraceFormat::Text, long_help = "trace format: text or binary")]
+    format: TraceFormat,
+
+    #[arg(long, long_help = "run to this address before starting the trace")]
+    from: Option<VirtAddr>,
+
+    #[arg(
+        long,
+        conflicts_with = "count",
+        required_unless_present = "count",
+        long_help = "stop tracing when the process is about to run the instruction at this address"
+    )]
+    to: Option<VirtAddr>,
+
+    #[arg(short = 'n', long_help = "number of instructions to trace")]
+    count: Option<usize>,
+}
+
+pub(super) fn handle(args: &TraceArgs, proc: &mut Process) -> anyhow::Result<ProcessState> {
+    if let Some(addr) = args.from {
+        let status = proc.run_to(addr)?;
+        if !status.is_stopped() || proc.get_pc()? != addr {
+            return Ok(status); // something else stopped us before we got there
+        }
+    }
+
+    let end = match (args.to, args.count) {
+        (Some(addr), _) => TraceEnd::Address(addr),
+        (_, Some(count)) => TraceEnd::Count(count),
+        _ => unreachable!("clap requires one of them"),
+    };
+    let mut writer = TraceWriter::new(BufWriter::new(File::create(&args.output)?), args.format)?;
+    let status = proc.trace(end, &mut writer)?;
+    writer.finish()?;
+    println!("wrote {} trace to {}", args.format, args.output.display());
+    Ok(status)
+}
@@ -0,0 +1,245 @@
+use std::cmp::min;
+
+use anyhow::bail;
+use clap::{
+    Args,
+    Subcommand,
+};
+use itertools::Itertools;
+use libdrbug::prelude::*;
+
+use crate::Empty;
+
+#[derive(Subcommand)]
+pub(super) enum WatchpointCommand {
+    #[command(about = "delete a watchpoint", visible_aliases = &["del", "rm"])]
+    Delete(WpArgs),
+
+    #[command(about = "disable a watchpoint", visible_aliases = &["dis"])]
+    Disable(WpArgs),
+
+    #[command(about = "enable a watchpoint", visible_aliases = &["en"])]
+    Enable(WpArgs),
+
+    #[command(about = "list all watchpoints", visible_aliases = &["l", "ls"])]
+    List,
+
+    #[command(about = "watch a register for changes (software watchpoint)", visible_aliases = &["reg"])]
+    Register(WpRegisterArgs),
+
+    #[command(about = "set a watchpoint")]
+    Set(WpSetArgs),
+}
+
+#[derive(Args)]
+pub(super) struct WpArgs {
+    #[arg(long_help = "id of watchpoint to operate on")]
+    id: usize,
+}
+
+#[derive(Args)]
+pub(super) struct WpSetArgs {
+    #[arg(long_help = "memory address to watch")]
+    location: VirtAddr,
+
+    #[arg(long_help = "number of bytes to watch (1, 2, 4, or 8 for hardware watchpoints)")]
+    size: usize,
+
+    #[arg(
+        short,
+        long,
+        default_value = "write",
+        long_help = "type of access to watch for (write, rw, or exec)"
+    )]
+    mode: WatchMode,
+
+    #[arg(
+        short,
+        long,
+        long_help = "single-step and compare the memory after every instruction; this is slow, but can watch any \
+                     number of bytes"
+    )]
+    software: bool,
+
+    #[arg(
+        short,
+        long,
+        conflicts_with = "software",
+        long_help = "write-protect the pages containing the memory and catch the resulting page faults; this is \
+                     the best choice for watching large regions"
+    )]
+    page: bool,
+}
+
+#[derive(Args)]
+pub(super) struct WpRegisterArgs {
+    #[arg(long_help = "name of the register to watch")]
+    reg: String,
+}
+
+
+pub(super) fn handle(command: &WatchpointCommand, proc: &mut Process) -> Empty {
+    match command {
+        WatchpointCommand::Delete(args) => handle_delete(proc, args.id),
+        WatchpointCommand::Disable(args) => handle_disable(proc, args.id),
+        WatchpointCommand::Enable(args) => handle_enable(proc, args.id),
+        WatchpointCommand::List => {
+            handle_list(proc);
+            Ok(())
+        },
+        WatchpointCommand::Register(args) => handle_register(proc, &args.reg),
+        WatchpointCommand::Set(args) => handle_set(proc, args),
+    }
+}
+
+pub(super) fn print_triggered(proc: &Process) {
+    if let Some(wp) = proc.triggered_watchpoint() {
+        println!("watchpoint {} at {:#x} triggered", wp.id(), wp.addr());
+        if wp.mode() != WatchMode::Execute {
+            println!("old value: {:#x}", wp.previous_data());
+            println!("new value: {:#x}", wp.data());
+        }
+    }
+
+    if let Some(wp) = proc.triggered_page_watchpoint() {
+        let fault_addr = wp.fault_addr().unwrap_or(wp.addr());
+        println!("watchpoint {} at {:#x} triggered by a write to {:#x}", wp.id(), wp.addr(), fault_addr);
+        print_changed_bytes(wp.addr(), &wp.previous_data(), &wp.data());
+    }
+
+    if let Some(wp) = proc.triggered_software_watchpoint() {
+        println!("watchpoint {} on {} triggered", wp.id(), wp.target());
+        let (old, new) = (wp.previous_data(), wp.data());
+        match wp.target() {
+            WatchTarget::Memory { addr, size } if size > 8 => print_changed_bytes(addr, &old, &new),
+            _ => {
+                println!("old value: {:#x}", le_bytes_to_u128(&old));
+                println!("new value: {:#x}", le_bytes_to_u128(&new));
+            },
+        }
+    }
+}
+
+// For big memory ranges, just show the part that actually changed
+fn print_changed_bytes(addr: VirtAddr, old: &[u8], new: &[u8]) {
+    let Some(first) = old.iter().zip(new).position(|(o, n)| o != n) else {
+        println!("value unchanged");
+        return;
+    };
+    let last = old.iter().zip(new).rposition(|(o, n)| o != n).unwrap_or(first);
+    let end = min(last + 1, first + 16);
+    println!("first change at {:#x}", addr.add(first));
+    println!("old value: {}", format_bytes(&old[first..end]));
+    println!("new value: {}", format_bytes(&new[first..end]));
+}
+
+fn format_bytes(data: &[u8]) -> String {
+    data.iter().format_with(" ", |b, f| f(&format_args!("{b:02x}"))).to_string()
+}
+
+fn le_bytes_to_u128(data: &[u8]) -> u128 {
+    data.iter().rev().fold(0, |acc, b| (acc << 8) | *b as u128)
+}
+
+fn handle_delete(proc: &mut Process, id: usize) -> Empty {
+    if proc.watchpoints().get(&id).is_some() {
+        proc.watchpoints_mut().remove(&id)?;
+    } else if proc.remove_page_watchpoint(&id)?.is_none() && proc.remove_software_watchpoint(&id).is_none() {
+        println!("watchpoint {id} not found");
+        return Ok(());
+    }
+    println!("watchpoint {id} deleted");
+    Ok(())
+}
+
+fn handle_disable(proc: &mut Process, id: usize) -> Empty {
+    if let Some(mut wp) = proc.watchpoints_mut().get(&id) {
+        wp.disable()?;
+        println!("watchpoint {id} at {:#x} disabled", wp.addr());
+    } else if let Some(mut wp) = proc.page_watchpoint(&id) {
+        wp.disable();
+        println!("watchpoint {id} at {:#x} disabled", wp.addr());
+    } else if let Some(mut wp) = proc.software_watchpoint(&id) {
+        wp.disable();
+        println!("watchpoint {id} on {} disabled", wp.target());
+    } else {
+        println!("watchpoint {id} not found");
+    }
+    Ok(())
+}
+
+fn handle_enable(proc: &mut Process, id: usize) -> Empty {
+    if let Some(mut wp) = proc.watchpoints_mut().get(&id) {
+        wp.enable()?;
+        println!("watchpoint {id} at {:#x} enabled", wp.addr());
+    } else if let Some(mut wp) = proc.page_watchpoint(&id) {
+        wp.enable();
+        println!("watchpoint {id} at {:#x} enabled", wp.addr());
+    } else if let Some(mut wp) = proc.software_watchpoint(&id) {
+        wp.enable();
+        println!("watchpoint {id} on {} enabled", wp.target());
+    } else {
+        println!("watchpoint {id} not found");
+    }
+    Ok(())
+}
+
+fn handle_list(proc: &Process) {
+    let watchpoints = proc.watchpoints();
+    if watchpoints.is_empty()
+        && proc.page_watchpoints().next().is_none()
+        && proc.software_watchpoints().next().is_none()
+    {
+        println!("no watchpoints set");
+    } else {
+        println!("current watchpoints:");
+        for (id, wp) in watchpoints.iter() {
+            println!(
+                "{id}: address = {:#x}, mode = {}, size = {}, {}",
+                wp.addr(),
+                wp.mode(),
+                wp.size(),
+                if wp.enabled() { "enabled" } else { "disabled" }
+            );
+        }
+        for wp in proc.page_watchpoints() {
+            println!(
+                "{}: address = {:#x}, size = {}, page, {}",
+                wp.id(),
+                wp.addr(),
+                wp.size(),
+                if wp.enabled() { "enabled" } else { "disabled" }
+            );
+        }
+        for wp in proc.software_watchpoints() {
+            println!(
+                "{}: target = {}, software, {}",
+                wp.id(),
+                wp.target(),
+                if wp.enabled() { "enabled" } else { "disabled" }
+            );
+        }
+    }
+}
+
+fn handle_register(proc: &mut Process, name: &str) -> Empty {
+    let info = register_info_by_name(name)?;
+    proc.create_software_watchpoint(WatchTarget::Register(info.id))?.enable();
+    Ok(())
+}
+
+fn handle_set(proc: &mut Process, args: &WpSetArgs) -> Empty {
+    if (args.software || args.page) && args.mode != WatchMode::Write {
+        bail!("software and page watchpoints can only detect writes");
+    }
+
+    if args.software {
+        let target = WatchTarget::Memory { addr: args.location, size: args.size };
+        proc.create_software_watchpoint(target)?.enable();
+    } else if args.page {
+        proc.create_page_watchpoint(args.location, args.size)?.enable();
+    } else {
+        proc.create_watchpoint(args.location, args.mode, args.size)?.enable()?;
+    }
+    Ok(())
+}
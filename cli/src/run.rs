@@ -1,3 +1,5 @@
+use std::path::PathBuf;
+
 use libdrbug::prelude::*;
 
 use crate::Empty;
@@ -7,10 +9,58 @@ use crate::repl::Repl;
 pub struct Args {
     #[arg(help = "path to executable to debug")]
     path: String,
+
+    #[arg(
+        long,
+        conflicts_with = "main",
+        help = "stop at the program's entry point instead of in the dynamic loader"
+    )]
+    entry: bool,
+
+    #[arg(long, help = "stop at the start of `main` instead of in the dynamic loader")]
+    main: bool,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "write basic-block coverage to a file (in drcov format) when the process exits"
+    )]
+    coverage: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with = "replay_syscalls",
+        help = "save the results of nondeterministic syscalls (reads, clocks, randomness) to a file"
+    )]
+    record_syscalls: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "feed the process the syscall results saved by --record-syscalls"
+    )]
+    replay_syscalls: Option<PathBuf>,
 }
 
 pub fn cmd(args: &Args) -> Empty {
-    let proc = Process::launch(&args.path, Default::default())?;
+    let stop_at = match (args.entry, args.main) {
+        (true, _) => StopAt::Entry,
+        (_, true) => StopAt::Main,
+        _ => StopAt::Exec,
+    };
+    let syscalls = match (&args.record_syscalls, &args.replay_syscalls) {
+        (Some(path), _) => SyscallMode::Record(path.clone()),
+        (_, Some(path)) => SyscallMode::Replay(path.clone()),
+        _ => SyscallMode::Normal,
+    };
+    let opts = ProcessOptions {
+        coverage: args.coverage.clone(),
+        stop_at,
+        syscalls,
+        ..Default::default()
+    };
+    let proc = Process::launch(&args.path, opts)?;
     println!("launched process `{}` with PID {}", args.path, proc.pid());
 
     let mut repl = Repl::new(proc)?;
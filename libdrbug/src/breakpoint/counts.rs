@@ -0,0 +1,66 @@
+use std::cell::Cell;
+use std::rc::Rc;
+
+// Hit bookkeeping that's shared between breakpoint sites and the breakpoints that own them; the
+// counts live behind Rcs so that every clone of a breakpoint sees the same numbers
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub(crate) struct HitCounts {
+    hits: Rc<Cell<usize>>,
+    ignore: Rc<Cell<usize>>,
+    disable_after: Rc<Cell<Option<usize>>>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum HitResult {
+    Ignore,
+    Stop,
+    StopAndDisable,
+}
+
+impl HitCounts {
+    pub(crate) fn disable_after(&self) -> Option<usize> {
+        self.disable_after.get()
+    }
+
+    pub(crate) fn hits(&self) -> usize {
+        self.hits.get()
+    }
+
+    pub(crate) fn ignore(&self) -> usize {
+        self.ignore.get()
+    }
+
+    // Every hit gets counted, and then the ignore count gets a say; if the hit isn't ignored, we
+    // count down towards disabling the breakpoint
+    pub(crate) fn record(&self) -> HitResult {
+        self.hits.set(self.hits.get() + 1);
+        if self.ignore.get() > 0 {
+            self.ignore.set(self.ignore.get() - 1);
+            return HitResult::Ignore;
+        }
+
+        match self.disable_after.get() {
+            Some(1) => {
+                self.disable_after.set(None);
+                HitResult::StopAndDisable
+            },
+            Some(n) => {
+                self.disable_after.set(Some(n - 1));
+                HitResult::Stop
+            },
+            None => HitResult::Stop,
+        }
+    }
+
+    pub(crate) fn reset_hits(&self) {
+        self.hits.set(0);
+    }
+
+    pub(crate) fn set_disable_after(&self, stops: Option<usize>) {
+        self.disable_after.set(stops.filter(|&n| n > 0));
+    }
+
+    pub(crate) fn set_ignore(&self, count: usize) {
+        self.ignore.set(count);
+    }
+}
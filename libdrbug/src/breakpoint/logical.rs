@@ -0,0 +1,246 @@
+use std::cell::{
+    Cell,
+    RefCell,
+};
+use std::fmt;
+use std::rc::Rc;
+use std::str::FromStr;
+use std::sync::atomic::{
+    AtomicUsize,
+    Ordering,
+};
+
+use super::counts::{
+    HitCounts,
+    HitResult,
+};
+use super::{
+    Breakable,
+    BreakpointSite,
+};
+use crate::address::VirtAddr;
+use crate::elf::{
+    Symbol,
+    SymbolType,
+};
+use crate::expression::Expression;
+use crate::util::glob_match;
+use crate::{
+    DrbugError,
+    DrbugResult,
+    Empty,
+};
+
+static BREAKPOINT_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+// Where the user asked us to break; addresses are written in hex (a leading 0x or digit tells
+// them apart from function names, since identifiers can't start with a digit), and anything with
+// a `*` or `?` in it is a glob pattern that's matched against every function symbol.  A module
+// offset like `libfoo.so+0x1139` is relative to wherever the module gets loaded, so unlike an
+// address it still means the same thing after a restart (or with ASLR turned on).
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum BreakpointLocation {
+    Address(VirtAddr),
+    Function(String),
+    ModuleOffset { module: String, offset: u64 },
+    Pattern(String),
+}
+
+impl BreakpointLocation {
+    pub(crate) fn matches(&self, sym: &Symbol) -> bool {
+        if sym.type_ != SymbolType::Function {
+            return false;
+        }
+        match self {
+            BreakpointLocation::Address(_) | BreakpointLocation::ModuleOffset { .. } => false,
+            BreakpointLocation::Function(name) => sym.name == *name,
+            BreakpointLocation::Pattern(pattern) => glob_match(pattern, &sym.name),
+        }
+    }
+}
+
+impl FromStr for BreakpointLocation {
+    type Err = DrbugError;
+
+    fn from_str(s: &str) -> DrbugResult<Self> {
+        if s.is_empty() {
+            Err(DrbugError::InvalidBreakpointLocation(s.into()))
+        } else if s.starts_with("0x") || s.starts_with(|c: char| c.is_ascii_digit()) {
+            Ok(BreakpointLocation::Address(s.parse()?))
+        } else if s.contains(['*', '?']) {
+            // Checked before the module offset, since plenty of (C++) names have a `+` in them
+            Ok(BreakpointLocation::Pattern(s.into()))
+        } else if let Some((module, offset)) = s.rsplit_once('+').filter(|(module, _)| !module.is_empty())
+            && let Ok(offset) = offset.parse::<VirtAddr>()
+        {
+            Ok(BreakpointLocation::ModuleOffset { module: module.into(), offset: offset.0 })
+        } else {
+            // Including anything else with a `+` in it, like `operator+=`
+            Ok(BreakpointLocation::Function(s.into()))
+        }
+    }
+}
+
+impl fmt::Display for BreakpointLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BreakpointLocation::Address(addr) => write!(f, "{addr:#x}"),
+            BreakpointLocation::Function(name) => write!(f, "{name}"),
+            BreakpointLocation::ModuleOffset { module, offset } => write!(f, "{module}+{offset:#x}"),
+            BreakpointLocation::Pattern(pattern) => write!(f, "{pattern}"),
+        }
+    }
+}
+
+// A breakpoint is what the user actually asks for; it owns however many breakpoint sites its
+// location resolves to (zero if it's pending, e.g., because the function doesn't exist yet, or
+// lots if it's a pattern).  Enabling, disabling, and the condition and hit counts all apply to the
+// breakpoint as a whole; the sites keep their own hit counts, so you can see which one got hit.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Breakpoint {
+    id: usize,
+    location: BreakpointLocation,
+    module_location: Option<BreakpointLocation>,
+    is_temporary: bool,
+    is_enabled: Rc<Cell<bool>>,
+    sites: Rc<RefCell<Vec<BreakpointSite>>>,
+    condition: Rc<RefCell<Option<Expression>>>,
+    counts: HitCounts,
+}
+
+impl Breakpoint {
+    pub(crate) fn new(location: BreakpointLocation, is_temporary: bool) -> Self {
+        let next_id = BREAKPOINT_COUNT.fetch_add(1, Ordering::Relaxed) + 1;
+        Breakpoint {
+            id: next_id,
+            location,
+            module_location: None,
+            is_temporary,
+            is_enabled: Rc::new(Cell::new(false)),
+            sites: Rc::new(RefCell::new(vec![])),
+            condition: Rc::new(RefCell::new(None)),
+            counts: HitCounts::default(),
+        }
+    }
+
+    pub fn condition(&self) -> Option<Expression> {
+        self.condition.borrow().clone()
+    }
+
+    pub fn disable(&mut self) -> Empty {
+        for site in self.sites.borrow_mut().iter_mut() {
+            site.disable()?;
+        }
+        self.is_enabled.set(false);
+        Ok(())
+    }
+
+    // The number of stops remaining before the breakpoint disables itself, if any
+    pub fn disable_after(&self) -> Option<usize> {
+        self.counts.disable_after()
+    }
+
+    pub fn enable(&mut self) -> Empty {
+        for site in self.sites.borrow_mut().iter_mut() {
+            site.enable()?;
+        }
+        self.is_enabled.set(true);
+        Ok(())
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.is_enabled.get()
+    }
+
+    pub fn hit_count(&self) -> usize {
+        self.counts.hits()
+    }
+
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    // The number of upcoming hits that will be counted but won't stop the process
+    pub fn ignore_count(&self) -> usize {
+        self.counts.ignore()
+    }
+
+    // A pending breakpoint hasn't resolved to any addresses (yet)
+    pub fn is_pending(&self) -> bool {
+        self.sites.borrow().is_empty()
+    }
+
+    pub fn is_temporary(&self) -> bool {
+        self.is_temporary
+    }
+
+    pub fn location(&self) -> &BreakpointLocation {
+        &self.location
+    }
+
+    pub fn reset_hit_count(&mut self) {
+        self.counts.reset_hits();
+        for site in self.sites.borrow_mut().iter_mut() {
+            site.reset_hit_count();
+        }
+    }
+
+    pub fn set_condition(&mut self, condition: Option<Expression>) {
+        *self.condition.borrow_mut() = condition;
+    }
+
+    pub fn set_disable_after(&mut self, stops: Option<usize>) {
+        self.counts.set_disable_after(stops);
+    }
+
+    pub fn set_ignore_count(&mut self, count: usize) {
+        self.counts.set_ignore(count);
+    }
+
+    pub fn sites(&self) -> Vec<BreakpointSite> {
+        self.sites.borrow().clone()
+    }
+
+    pub(crate) fn clear_sites(&mut self) {
+        self.sites.borrow_mut().clear();
+    }
+
+    // New sites start out matching the breakpoint, so a site that resolves after the breakpoint
+    // was enabled gets enabled too
+    pub(crate) fn add_site(&mut self, mut site: BreakpointSite) -> Empty {
+        if self.enabled() {
+            site.enable()?;
+        }
+        self.sites.borrow_mut().push(site);
+        Ok(())
+    }
+
+    // When the process restarts, an address breakpoint moves over to the module offset that it had
+    // in the old process (if it was in a module at all), since the module has probably been loaded
+    // somewhere else this time around
+    pub(crate) fn relocate(&mut self) {
+        if let Some(location) = self.module_location.take() {
+            self.location = location;
+        }
+    }
+
+    pub(crate) fn remove_site(&mut self, site_id: usize) {
+        self.sites.borrow_mut().retain(|site| site.id() != site_id);
+    }
+
+    // Same as `BreakpointSite::record_hit`, except that running out of stops disables every site
+    pub(crate) fn record_hit(&mut self) -> DrbugResult<bool> {
+        match self.counts.record() {
+            HitResult::Ignore => Ok(false),
+            HitResult::Stop => Ok(true),
+            HitResult::StopAndDisable => {
+                self.disable()?;
+                Ok(true)
+            },
+        }
+    }
+
+    pub(crate) fn set_module_location(&mut self, location: Option<BreakpointLocation>) {
+        self.module_location = location;
+    }
+}
@@ -1,7 +1,13 @@
+mod counts;
 mod list;
+mod logical;
 mod site;
 
 pub use self::list::BreakList;
+pub use self::logical::{
+    Breakpoint,
+    BreakpointLocation,
+};
 pub use self::site::BreakpointSite;
 use crate::Empty;
 use crate::address::VirtAddr;
@@ -12,5 +18,4 @@ pub trait Breakable {
     fn enable(&mut self) -> Empty;
     fn enabled(&self) -> bool;
     fn id(&self) -> usize;
-    fn orig_data(&self) -> u8;
 }
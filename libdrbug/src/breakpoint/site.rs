@@ -1,4 +1,7 @@
-use std::cell::Cell;
+use std::cell::{
+    Cell,
+    RefCell,
+};
 use std::rc::Rc;
 use std::sync::atomic::{
     AtomicUsize,
@@ -9,9 +12,15 @@ use nix::sys::ptrace;
 use nix::unistd::Pid;
 
 use super::Breakable;
+use super::counts::{
+    HitCounts,
+    HitResult,
+};
 use crate::address::VirtAddr;
+use crate::expression::Expression;
 use crate::{
     DrbugError,
+    DrbugResult,
     Empty,
     syscall_error,
 };
@@ -24,19 +33,99 @@ pub struct BreakpointSite {
     id: usize,
     pid: Pid,
     addr: VirtAddr,
+    breakpoint_id: Option<usize>,
+    is_temporary: bool,
     is_enabled: Rc<Cell<bool>>,
     saved_data: Rc<Cell<u8>>,
+    condition: Rc<RefCell<Option<Expression>>>,
+    counts: HitCounts,
 }
 
 impl BreakpointSite {
-    pub(crate) fn new(pid: Pid, addr: VirtAddr) -> Self {
+    pub(crate) fn new(pid: Pid, addr: VirtAddr, breakpoint_id: Option<usize>, is_temporary: bool) -> Self {
         let next_id = BP_COUNT.fetch_add(1, Ordering::Relaxed) + 1;
         BreakpointSite {
             id: next_id,
             pid,
             addr,
+            breakpoint_id,
+            is_temporary,
             is_enabled: Rc::new(Cell::new(false)),
             saved_data: Rc::new(Cell::new(0)),
+            condition: Rc::new(RefCell::new(None)),
+            counts: HitCounts::default(),
+        }
+    }
+
+    // The id of the breakpoint that this site belongs to, if it was created by one
+    pub fn breakpoint_id(&self) -> Option<usize> {
+        self.breakpoint_id
+    }
+
+    pub fn condition(&self) -> Option<Expression> {
+        self.condition.borrow().clone()
+    }
+
+    // The number of stops remaining before the breakpoint disables itself, if any
+    pub fn disable_after(&self) -> Option<usize> {
+        self.counts.disable_after()
+    }
+
+    pub fn hit_count(&self) -> usize {
+        self.counts.hits()
+    }
+
+    // The number of upcoming hits that will be counted but won't stop the process
+    pub fn ignore_count(&self) -> usize {
+        self.counts.ignore()
+    }
+
+    pub fn is_temporary(&self) -> bool {
+        self.is_temporary
+    }
+
+    pub fn orig_data(&self) -> u8 {
+        self.saved_data.get()
+    }
+
+    // For when something else had already put an int3 here before the site was enabled, so the
+    // data it saved isn't really the original
+    pub(crate) fn set_orig_data(&self, data: u8) {
+        self.saved_data.set(data);
+    }
+
+    pub fn reset_hit_count(&mut self) {
+        self.counts.reset_hits();
+    }
+
+    pub fn set_condition(&mut self, condition: Option<Expression>) {
+        *self.condition.borrow_mut() = condition;
+    }
+
+    pub fn set_disable_after(&mut self, stops: Option<usize>) {
+        self.counts.set_disable_after(stops);
+    }
+
+    pub fn set_ignore_count(&mut self, count: usize) {
+        self.counts.set_ignore(count);
+    }
+
+    // The code under the site got unmapped (e.g., its library was dlclose'd), so there's nothing
+    // left to restore; this just makes sure that nobody tries
+    pub(crate) fn unload(&mut self) {
+        self.is_enabled.set(false);
+    }
+
+    // Called every time the process reaches this breakpoint (and its condition, if any, holds);
+    // returns true if the process should actually stop here
+    pub(crate) fn record_hit(&mut self) -> DrbugResult<bool> {
+        match self.counts.record() {
+            HitResult::Ignore => Ok(false),
+            HitResult::Stop => Ok(true),
+            HitResult::StopAndDisable => {
+                self.disable()?;
+                Ok(true)
+            },
         }
     }
 }
@@ -88,8 +177,4 @@ impl Breakable for BreakpointSite {
     fn id(&self) -> usize {
         self.id
     }
-
-    fn orig_data(&self) -> u8 {
-        self.saved_data.get()
-    }
 }
@@ -8,7 +8,7 @@ use crate::DrbugResult;
 use crate::address::VirtAddr;
 use crate::process::Process;
 
-const BITNESS: u32 = 64;
+pub(crate) const BITNESS: u32 = 64;
 
 pub struct Disassembler<'a> {
     proc: &'a mut Process,
@@ -0,0 +1,198 @@
+use std::path::{
+    Path,
+    PathBuf,
+};
+
+use crate::address::VirtAddr;
+use crate::{
+    DrbugError,
+    DrbugResult,
+};
+
+// Just enough of the ELF64 format to find symbols: we read the file header, the program headers (so
+// we know where the file wants to be loaded), and the symbol tables out of the section headers.
+// Everything is little-endian, since we only support x86_64 anyways.  Field offsets come from the
+// System V ABI spec (or `man 5 elf`).
+const ELF_MAGIC: &[u8] = b"\x7fELF";
+const ELFCLASS64: u8 = 2;
+const ELFDATA2LSB: u8 = 1;
+const EHDR_SIZE: usize = 64;
+
+const PT_LOAD: u32 = 1;
+const SHT_SYMTAB: u32 = 2;
+const SHT_DYNSYM: u32 = 11;
+const SHN_UNDEF: u16 = 0;
+const STT_OBJECT: u8 = 1;
+const STT_FUNC: u8 = 2;
+const SYM_SIZE: usize = 24;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SymbolType {
+    Function,
+    Object,
+    Other,
+}
+
+// A symbol's address is a _file_ address, i.e., where the symbol would be if the ELF file were
+// loaded at the address it asks for; for position-independent code, you need to add the module's
+// load bias to get the address in the running process.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Symbol {
+    pub name: String,
+    pub addr: VirtAddr,
+    pub size: u64,
+    pub type_: SymbolType,
+}
+
+impl Symbol {
+    pub fn contains(&self, addr: VirtAddr) -> bool {
+        self.addr <= addr && addr.0 < self.addr.0 + self.size.max(1)
+    }
+}
+
+#[derive(Debug)]
+pub struct Elf {
+    path: PathBuf,
+    entry: VirtAddr,
+    base_addr: VirtAddr,
+    symbols: Vec<Symbol>,
+}
+
+impl Elf {
+    pub fn open(path: &Path) -> DrbugResult<Self> {
+        let data = std::fs::read(path)?;
+        Self::parse(path, &data)
+    }
+
+    // The lowest address of any loadable segment; the load bias is the difference between this
+    // and wherever the first segment actually got mapped
+    pub fn base_addr(&self) -> VirtAddr {
+        self.base_addr
+    }
+
+    pub fn entry(&self) -> VirtAddr {
+        self.entry
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn symbol_containing(&self, addr: VirtAddr) -> Option<&Symbol> {
+        self.symbols
+            .iter()
+            .filter(|sym| sym.type_ == SymbolType::Function && sym.contains(addr))
+            .min_by_key(|sym| sym.size)
+    }
+
+    pub fn symbols(&self) -> &[Symbol] {
+        &self.symbols
+    }
+
+    pub fn symbols_by_name<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Symbol> {
+        self.symbols.iter().filter(move |sym| sym.name == name)
+    }
+
+    fn parse(path: &Path, data: &[u8]) -> DrbugResult<Self> {
+        let invalid = || DrbugError::InvalidElf(path.to_string_lossy().into());
+        if data.len() < EHDR_SIZE || &data[..4] != ELF_MAGIC || data[4] != ELFCLASS64 || data[5] != ELFDATA2LSB {
+            return Err(invalid());
+        }
+        let reader = Reader { data, invalid: &invalid };
+
+        let entry = reader.u64(24)?;
+        let (phoff, phentsize, phnum) = (reader.u64(32)? as usize, reader.u16(54)? as usize, reader.u16(56)? as usize);
+        let (shoff, shentsize, shnum) = (reader.u64(40)? as usize, reader.u16(58)? as usize, reader.u16(60)? as usize);
+
+        let mut base_addr = None;
+        for i in 0..phnum {
+            let ph = phoff + i * phentsize;
+            if reader.u32(ph)? == PT_LOAD {
+                let vaddr = reader.u64(ph + 16)?;
+                base_addr = Some(base_addr.map_or(vaddr, |b: u64| b.min(vaddr)));
+            }
+        }
+
+        // Prefer the full symbol table, but stripped binaries only have the dynamic one
+        let mut symtab = None;
+        for i in 0..shnum {
+            let sh = shoff + i * shentsize;
+            match reader.u32(sh + 4)? {
+                SHT_SYMTAB => symtab = Some(sh),
+                SHT_DYNSYM if symtab.is_none() => symtab = Some(sh),
+                _ => (),
+            }
+        }
+
+        let mut symbols = vec![];
+        if let Some(sh) = symtab {
+            let (offset, size) = (reader.u64(sh + 24)? as usize, reader.u64(sh + 32)? as usize);
+            let strtab = shoff + reader.u32(sh + 40)? as usize * shentsize;
+            let strtab_offset = reader.u64(strtab + 24)? as usize;
+
+            for sym in (offset..offset + size).step_by(SYM_SIZE) {
+                let info = reader.u8(sym + 4)?;
+                let value = reader.u64(sym + 8)?;
+                if reader.u16(sym + 6)? == SHN_UNDEF || value == 0 {
+                    continue;
+                }
+                let name = reader.cstr(strtab_offset + reader.u32(sym)? as usize)?;
+                if name.is_empty() {
+                    continue;
+                }
+                symbols.push(Symbol {
+                    name,
+                    addr: VirtAddr(value),
+                    size: reader.u64(sym + 16)?,
+                    type_: match info & 0xf {
+                        STT_FUNC => SymbolType::Function,
+                        STT_OBJECT => SymbolType::Object,
+                        _ => SymbolType::Other,
+                    },
+                });
+            }
+        }
+
+        Ok(Elf {
+            path: path.into(),
+            entry: VirtAddr(entry),
+            base_addr: VirtAddr(base_addr.ok_or_else(invalid)? & !0xfff),
+            symbols,
+        })
+    }
+}
+
+// Bounds-checked little-endian reads out of the raw file data
+struct Reader<'a, F: Fn() -> DrbugError> {
+    data: &'a [u8],
+    invalid: &'a F,
+}
+
+impl<F: Fn() -> DrbugError> Reader<'_, F> {
+    fn bytes<const N: usize>(&self, offset: usize) -> DrbugResult<[u8; N]> {
+        let slice = self.data.get(offset..offset + N).ok_or_else(self.invalid)?;
+        Ok(slice.try_into()?)
+    }
+
+    fn u8(&self, offset: usize) -> DrbugResult<u8> {
+        Ok(self.bytes::<1>(offset)?[0])
+    }
+
+    fn u16(&self, offset: usize) -> DrbugResult<u16> {
+        Ok(u16::from_le_bytes(self.bytes(offset)?))
+    }
+
+    fn u32(&self, offset: usize) -> DrbugResult<u32> {
+        Ok(u32::from_le_bytes(self.bytes(offset)?))
+    }
+
+    fn u64(&self, offset: usize) -> DrbugResult<u64> {
+        Ok(u64::from_le_bytes(self.bytes(offset)?))
+    }
+
+    fn cstr(&self, offset: usize) -> DrbugResult<String> {
+        let rest = self.data.get(offset..).ok_or_else(self.invalid)?;
+        let end = rest.iter().position(|&b| b == 0).ok_or_else(self.invalid)?;
+        Ok(String::from_utf8_lossy(&rest[..end]).into())
+    }
+}
@@ -1,12 +1,18 @@
+use nix::sys::wait::WaitStatus;
+use nix::unistd::Pid;
 use thiserror::Error;
 
 use crate::address::VirtAddr;
+use crate::register::lanes::LaneFormat;
 use crate::register::value::RegisterValue;
 
 pub type DrbugResult<T> = Result<T, DrbugError>;
 
 #[derive(Debug, Error)]
 pub enum DrbugError {
+    #[error("auxiliary vector has no {0} entry")]
+    AuxvEntryNotFound(&'static str),
+
     #[error("breakpoint site {0} exists at address: {1}")]
     BreakpointSiteExists(usize, VirtAddr),
 
@@ -19,6 +25,33 @@ pub enum DrbugError {
     #[error("child process failed: {0}")]
     ChildProcessFailed(String),
 
+    #[error("division by zero")]
+    DivisionByZero,
+
+    #[error("couldn't find the executable in the process's memory")]
+    ExecutableNotMapped,
+
+    #[error("invalid breakpoint location: {0:?}")]
+    InvalidBreakpointLocation(String),
+
+    #[error("invalid or unsupported ELF file: {0}")]
+    InvalidElf(String),
+
+    #[error("invalid expression: {0}")]
+    InvalidExpression(String),
+
+    #[error("invalid flag change: {0}")]
+    InvalidFlagChange(String),
+
+    #[error("invalid floating point value: {0}")]
+    InvalidFloat(String),
+
+    #[error("invalid lane format: {0}")]
+    InvalidLaneFormat(String),
+
+    #[error("invalid memory map entry: {0}")]
+    InvalidMemoryMap(String),
+
     #[error("invalid register name: {0}")]
     InvalidRegisterName(String),
 
@@ -28,23 +61,80 @@ pub enum DrbugError {
     #[error("invalid register value: {0}")]
     InvalidRegisterValue(RegisterValue),
 
+    #[error("invalid syscall log entry: {0}")]
+    InvalidSyscallLog(String),
+
+    #[error("invalid trace file: {0}")]
+    InvalidTrace(String),
+
+    #[error("invalid trace format: {0}")]
+    InvalidTraceFormat(String),
+
+    #[error("invalid watch mode: {0}")]
+    InvalidWatchMode(String),
+
+    #[error("invalid watchpoint size: {0}")]
+    InvalidWatchpointSize(usize),
+
     #[error("i/o error: {0}")]
     IOError(#[from] std::io::Error),
 
-    #[error("long double (f80) type not currently supported")]
-    LongDoubleUnsupported,
+    #[error("lane format {0} doesn't fit a {1}-byte register")]
+    LaneFormatMismatch(LaneFormat, usize),
+
+    #[error("lane {0} is out of range for a vector with {1} lanes")]
+    LaneOutOfRange(usize, usize),
+
+    #[error("no free debug registers")]
+    NoFreeDebugRegisters,
+
+    #[error("no history is kept for register {0}")]
+    NoRegisterHistory(&'static str),
 
     #[error("parse error: {0}")]
     ParseError(#[from] std::num::ParseIntError),
 
+    #[error("no recorded history to go back through")]
+    NoRecordedHistory,
+
     #[error("pipe closed")]
     PipeClosed,
 
     #[error("{0} failed ({1})")]
     SyscallFailed(&'static str, nix::Error),
 
+    #[error("register {0} isn't available on this processor")]
+    RegisterUnavailable(&'static str),
+
     #[error("conversion from {0} to {1} failed")]
     RegisterValueConversionFailed(&'static str, RegisterValue),
+
+    #[error("register values aren't available until the process stops")]
+    RegistersStale,
+
+    #[error("process {0} wasn't launched by the debugger, so it can't be restarted")]
+    RestartUnsupported(Pid),
+
+    #[error("symbol not found: {0}")]
+    SymbolNotFound(String),
+
+    #[error("syscall injection failed: {0:?}")]
+    SyscallInjectionFailed(WaitStatus),
+
+    #[error("syscall replay diverged: expected syscall {0}, got {1}")]
+    SyscallReplayMismatch(i64, i64),
+
+    #[error("too many arguments for syscall: {0}")]
+    TooManySyscallArgs(usize),
+
+    #[error("watchpoint address {0} is not aligned to its size ({1})")]
+    UnalignedWatchpoint(VirtAddr, usize),
+
+    #[error("address {0} is not mapped")]
+    UnmappedAddress(VirtAddr),
+
+    #[error("watchpoint {0} exists at address: {1}")]
+    WatchpointExists(usize, VirtAddr),
 }
 
 #[macro_export]
@@ -0,0 +1,404 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::address::VirtAddr;
+use crate::process::Process;
+use crate::register::info::{
+    RegisterFormat,
+    RegisterId,
+    register_info_by_id,
+    register_info_by_name,
+};
+use crate::register::value::RegisterValue;
+use crate::{
+    DrbugError,
+    DrbugResult,
+};
+
+// Binary operators from loosest to tightest binding; the precedence levels are the same as in C
+const BINARY_OPS: &[(&str, BinaryOp, u8)] = &[
+    ("||", BinaryOp::Or, 1),
+    ("&&", BinaryOp::And, 2),
+    ("|", BinaryOp::BitOr, 3),
+    ("^", BinaryOp::BitXor, 4),
+    ("&", BinaryOp::BitAnd, 5),
+    ("==", BinaryOp::Eq, 6),
+    ("!=", BinaryOp::Ne, 6),
+    ("<", BinaryOp::Lt, 7),
+    ("<=", BinaryOp::Le, 7),
+    (">", BinaryOp::Gt, 7),
+    (">=", BinaryOp::Ge, 7),
+    ("<<", BinaryOp::Shl, 8),
+    (">>", BinaryOp::Shr, 8),
+    ("+", BinaryOp::Add, 9),
+    ("-", BinaryOp::Sub, 9),
+    ("*", BinaryOp::Mul, 10),
+    ("/", BinaryOp::Div, 10),
+    ("%", BinaryOp::Rem, 10),
+];
+
+// Longer operators have to come first so that, e.g., `<=` doesn't get tokenized as `<` `=`
+const OPERATORS: &[&str] = &[
+    "&&", "||", "==", "!=", "<=", ">=", "<<", ">>", "(", ")", "*", "/", "%", "+", "-", "&", "|", "^", "~", "!", "<",
+    ">",
+];
+
+// A small C-like expression over registers and memory, used for things like breakpoint conditions
+// and dprintf arguments, for example:
+//
+//   rdi == 0x10 && *(u32*)(rsi+8) > 5
+//
+// Registers can be written with or without a leading `$`, and memory is read with `*`, which
+// reads 8 bytes unless it's followed by a pointer cast like `(u32*)`.  All arithmetic is done
+// with 128-bit signed integers, so comparisons between 64-bit values (signed or unsigned) behave
+// the way you'd expect.  Like in C, anything non-zero counts as true.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Expression {
+    source: String,
+    expr: Expr,
+}
+
+impl Expression {
+    pub fn evaluate(&self, proc: &Process) -> DrbugResult<i128> {
+        self.expr.eval(proc)
+    }
+
+    pub fn is_true(&self, proc: &Process) -> DrbugResult<bool> {
+        Ok(self.evaluate(proc)? != 0)
+    }
+}
+
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+impl FromStr for Expression {
+    type Err = DrbugError;
+
+    fn from_str(s: &str) -> DrbugResult<Self> {
+        let mut parser = Parser { tokens: tokenize(s)?, pos: 0 };
+        let expr = parser.parse_binary(0)?;
+        if let Some(tok) = parser.peek() {
+            return Err(DrbugError::InvalidExpression(format!("unexpected token: {tok}")));
+        }
+        Ok(Expression { source: s.trim().into(), expr })
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum IntType {
+    U8,
+    U16,
+    U32,
+    U64,
+    I8,
+    I16,
+    I32,
+    I64,
+}
+
+impl IntType {
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "u8" => IntType::U8,
+            "u16" => IntType::U16,
+            "u32" => IntType::U32,
+            "u64" => IntType::U64,
+            "i8" => IntType::I8,
+            "i16" => IntType::I16,
+            "i32" => IntType::I32,
+            "i64" => IntType::I64,
+            _ => return None,
+        })
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            IntType::U8 | IntType::I8 => 1,
+            IntType::U16 | IntType::I16 => 2,
+            IntType::U32 | IntType::I32 => 4,
+            IntType::U64 | IntType::I64 => 8,
+        }
+    }
+
+    fn truncate(&self, val: i128) -> i128 {
+        match self {
+            IntType::U8 => val as u8 as i128,
+            IntType::U16 => val as u16 as i128,
+            IntType::U32 => val as u32 as i128,
+            IntType::U64 => val as u64 as i128,
+            IntType::I8 => val as i8 as i128,
+            IntType::I16 => val as i16 as i128,
+            IntType::I32 => val as i32 as i128,
+            IntType::I64 => val as i64 as i128,
+        }
+    }
+
+    fn read_le_bytes(&self, data: &[u8]) -> i128 {
+        let mut buf = [0u8; 8];
+        buf[..data.len()].copy_from_slice(data);
+        self.truncate(u64::from_le_bytes(buf) as i128)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum UnaryOp {
+    BitNot,
+    Neg,
+    Not,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum BinaryOp {
+    Add,
+    And,
+    BitAnd,
+    BitOr,
+    BitXor,
+    Div,
+    Eq,
+    Ge,
+    Gt,
+    Le,
+    Lt,
+    Mul,
+    Ne,
+    Or,
+    Rem,
+    Shl,
+    Shr,
+    Sub,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Expr {
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    Cast(IntType, Box<Expr>),
+    Const(i128),
+    Deref(IntType, Box<Expr>),
+    Register(RegisterId),
+    Unary(UnaryOp, Box<Expr>),
+}
+
+impl Expr {
+    fn eval(&self, proc: &Process) -> DrbugResult<i128> {
+        Ok(match self {
+            Expr::Binary(BinaryOp::And, lhs, rhs) => (lhs.eval(proc)? != 0 && rhs.eval(proc)? != 0) as i128,
+            Expr::Binary(BinaryOp::Or, lhs, rhs) => (lhs.eval(proc)? != 0 || rhs.eval(proc)? != 0) as i128,
+            Expr::Binary(op, lhs, rhs) => eval_binary(*op, lhs.eval(proc)?, rhs.eval(proc)?)?,
+            Expr::Cast(ty, e) => ty.truncate(e.eval(proc)?),
+            Expr::Const(val) => *val,
+            Expr::Deref(ty, e) => {
+                let addr = VirtAddr(e.eval(proc)? as u64);
+                ty.read_le_bytes(&proc.read_memory_without_traps(addr, ty.size())?)
+            },
+            Expr::Register(id) => match proc.get_registers().read(register_info_by_id(id))? {
+                RegisterValue::U8(v) => v as i128,
+                RegisterValue::U16(v) => v as i128,
+                RegisterValue::U32(v) => v as i128,
+                RegisterValue::U64(v) => v as i128,
+                RegisterValue::I8(v) => v as i128,
+                RegisterValue::I16(v) => v as i128,
+                RegisterValue::I32(v) => v as i128,
+                RegisterValue::I64(v) => v as i128,
+                v => return Err(DrbugError::InvalidRegisterValue(v)),
+            },
+            Expr::Unary(UnaryOp::BitNot, e) => !e.eval(proc)?,
+            Expr::Unary(UnaryOp::Neg, e) => e.eval(proc)?.wrapping_neg(),
+            Expr::Unary(UnaryOp::Not, e) => (e.eval(proc)? == 0) as i128,
+        })
+    }
+}
+
+fn eval_binary(op: BinaryOp, lhs: i128, rhs: i128) -> DrbugResult<i128> {
+    let shift = u32::try_from(rhs).unwrap_or(u32::MAX);
+    Ok(match op {
+        BinaryOp::Add => lhs.wrapping_add(rhs),
+        BinaryOp::BitAnd => lhs & rhs,
+        BinaryOp::BitOr => lhs | rhs,
+        BinaryOp::BitXor => lhs ^ rhs,
+        BinaryOp::Div => lhs.checked_div(rhs).ok_or(DrbugError::DivisionByZero)?,
+        BinaryOp::Eq => (lhs == rhs) as i128,
+        BinaryOp::Ge => (lhs >= rhs) as i128,
+        BinaryOp::Gt => (lhs > rhs) as i128,
+        BinaryOp::Le => (lhs <= rhs) as i128,
+        BinaryOp::Lt => (lhs < rhs) as i128,
+        BinaryOp::Mul => lhs.wrapping_mul(rhs),
+        BinaryOp::Ne => (lhs != rhs) as i128,
+        BinaryOp::Rem => lhs.checked_rem(rhs).ok_or(DrbugError::DivisionByZero)?,
+        BinaryOp::Shl => lhs.checked_shl(shift).unwrap_or(0),
+        BinaryOp::Shr => lhs.checked_shr(shift).unwrap_or(0),
+        BinaryOp::Sub => lhs.wrapping_sub(rhs),
+        BinaryOp::And | BinaryOp::Or => unreachable!("logical operators short-circuit"),
+    })
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Token {
+    Ident(String),
+    Num(i128),
+    Op(&'static str),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Ident(name) => write!(f, "{name}"),
+            Token::Num(val) => write!(f, "{val}"),
+            Token::Op(op) => write!(f, "{op}"),
+        }
+    }
+}
+
+fn tokenize(input: &str) -> DrbugResult<Vec<Token>> {
+    let mut tokens = vec![];
+    let mut rest = input.trim_start();
+    while let Some(c) = rest.chars().next() {
+        let len = if c.is_ascii_alphanumeric() || c == '_' || c == '$' {
+            let len = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '$'))
+                .unwrap_or(rest.len());
+            let word = &rest[..len];
+            if c.is_ascii_digit() {
+                tokens.push(Token::Num(parse_number(word)?));
+            } else {
+                tokens.push(Token::Ident(word.into()));
+            }
+            len
+        } else if let Some(&op) = OPERATORS.iter().find(|op| rest.starts_with(**op)) {
+            tokens.push(Token::Op(op));
+            op.len()
+        } else {
+            return Err(DrbugError::InvalidExpression(format!("unexpected character: {c}")));
+        };
+        rest = rest[len..].trim_start();
+    }
+    Ok(tokens)
+}
+
+fn parse_number(word: &str) -> DrbugResult<i128> {
+    let (radix, digits) = match word.get(..2) {
+        Some("0x") | Some("0X") => (16, &word[2..]),
+        Some("0o") | Some("0O") => (8, &word[2..]),
+        Some("0b") | Some("0B") => (2, &word[2..]),
+        _ => (10, word),
+    };
+    u64::from_str_radix(digits, radix)
+        .map(|v| v as i128)
+        .map_err(|_| DrbugError::InvalidExpression(format!("invalid number: {word}")))
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn peek_op(&self, offset: usize) -> Option<&'static str> {
+        match self.tokens.get(self.pos + offset) {
+            Some(Token::Op(op)) => Some(*op),
+            _ => None,
+        }
+    }
+
+    fn peek_type(&self, offset: usize) -> Option<IntType> {
+        match self.tokens.get(self.pos + offset) {
+            Some(Token::Ident(name)) => IntType::from_name(name),
+            _ => None,
+        }
+    }
+
+    fn next(&mut self) -> DrbugResult<Token> {
+        let tok = self
+            .tokens
+            .get(self.pos)
+            .cloned()
+            .ok_or_else(|| DrbugError::InvalidExpression("unexpected end of expression".into()))?;
+        self.pos += 1;
+        Ok(tok)
+    }
+
+    fn expect_op(&mut self, expected: &str) -> DrbugResult<()> {
+        match self.next()? {
+            Token::Op(op) if op == expected => Ok(()),
+            tok => Err(DrbugError::InvalidExpression(format!("expected `{expected}`, found: {tok}"))),
+        }
+    }
+
+    // Standard precedence climbing: parse everything that binds at least as tightly as
+    // `min_prec`, and let the recursive calls handle the tighter-binding operators
+    fn parse_binary(&mut self, min_prec: u8) -> DrbugResult<Expr> {
+        let mut lhs = self.parse_unary()?;
+        while let Some(op) = self.peek_op(0)
+            && let Some(&(_, bin_op, prec)) = BINARY_OPS.iter().find(|(s, ..)| *s == op)
+            && prec >= min_prec
+        {
+            self.pos += 1;
+            let rhs = self.parse_binary(prec + 1)?;
+            lhs = Expr::Binary(bin_op, Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_unary(&mut self) -> DrbugResult<Expr> {
+        let unary_op = match self.peek_op(0) {
+            Some("-") => UnaryOp::Neg,
+            Some("~") => UnaryOp::BitNot,
+            Some("!") => UnaryOp::Not,
+            Some("*") => {
+                self.pos += 1;
+                return Ok(Expr::Deref(self.parse_pointer_cast()?, Box::new(self.parse_unary()?)));
+            },
+            Some("(") if self.peek_type(1).is_some() => {
+                let ty = self.peek_type(1).unwrap();
+                if self.peek_op(2) == Some("*") {
+                    return Err(DrbugError::InvalidExpression("pointer casts must be dereferenced".into()));
+                }
+                self.pos += 2;
+                self.expect_op(")")?;
+                return Ok(Expr::Cast(ty, Box::new(self.parse_unary()?)));
+            },
+            _ => return self.parse_primary(),
+        };
+        self.pos += 1;
+        Ok(Expr::Unary(unary_op, Box::new(self.parse_unary()?)))
+    }
+
+    // A dereference reads 8 bytes by default, unless it's immediately followed by `(<type>*)`
+    fn parse_pointer_cast(&mut self) -> DrbugResult<IntType> {
+        let Some(ty) = self.peek_type(1) else {
+            return Ok(IntType::U64);
+        };
+        if self.peek_op(0) != Some("(") || self.peek_op(2) != Some("*") {
+            return Ok(IntType::U64);
+        }
+        self.pos += 3;
+        self.expect_op(")")?;
+        Ok(ty)
+    }
+
+    fn parse_primary(&mut self) -> DrbugResult<Expr> {
+        match self.next()? {
+            Token::Num(val) => Ok(Expr::Const(val)),
+            Token::Ident(name) => {
+                let info = register_info_by_name(name.strip_prefix('$').unwrap_or(&name))?;
+                if info.format != RegisterFormat::Uint {
+                    return Err(DrbugError::InvalidExpression(format!("{} is not an integer register", info.name)));
+                }
+                Ok(Expr::Register(info.id))
+            },
+            Token::Op("(") => {
+                let expr = self.parse_binary(0)?;
+                self.expect_op(")")?;
+                Ok(expr)
+            },
+            tok => Err(DrbugError::InvalidExpression(format!("unexpected token: {tok}"))),
+        }
+    }
+}
@@ -2,34 +2,84 @@
 mod address;
 mod breakpoint;
 mod disassembly;
+mod elf;
 mod error;
+mod expression;
+mod maps;
 mod pipe;
 mod process;
 mod register;
 mod util;
+mod watchpoint;
 
 pub use crate::error::*;
 
 type Empty = DrbugResult<()>;
 type Byte64 = [u8; 8];
 type Byte128 = [u8; 16];
+type Byte256 = [u8; 32];
+type Byte512 = [u8; 64];
 
 pub mod prelude {
     pub use crate::address::VirtAddr;
-    pub use crate::breakpoint::Breakable;
+    pub use crate::breakpoint::{
+        Breakable,
+        Breakpoint,
+        BreakpointLocation,
+    };
     pub use crate::disassembly::Disassembler;
+    pub use crate::elf::{
+        Elf,
+        Symbol,
+        SymbolType,
+    };
+    pub use crate::expression::Expression;
+    pub use crate::maps::MemoryMap;
     pub use crate::process::{
+        DEFAULT_RECORD_LIMIT,
+        Module,
         Process,
         ProcessOptions,
         ProcessState,
+        StopAt,
+        SyscallMode,
+        TraceEnd,
+        TraceEntry,
+        TraceFormat,
+        TraceWriter,
+        TrapType,
+        read_trace,
+    };
+    pub use crate::register::flags::{
+        FlagField,
+        Flags,
     };
     pub use crate::register::info::{
         RegisterFormat,
         RegisterInfo,
         RegisterType,
+        register_info_by_id,
         register_info_by_name,
     };
+    pub use crate::register::lanes::{
+        LaneFormat,
+        LaneType,
+        Lanes,
+    };
     pub use crate::register::value::RegisterValue;
+    pub use crate::register::x87::{
+        F80,
+        X87Register,
+        X87Stack,
+        X87Tag,
+    };
+    pub use crate::watchpoint::{
+        PageWatchpoint,
+        SoftwareWatchpoint,
+        WatchMode,
+        WatchTarget,
+        Watchpoint,
+    };
 }
 
 #[cfg(test)]
@@ -0,0 +1,79 @@
+use std::str::FromStr;
+
+use libc::{
+    PROT_EXEC,
+    PROT_READ,
+    PROT_WRITE,
+};
+
+use crate::address::VirtAddr;
+use crate::{
+    DrbugError,
+    DrbugResult,
+};
+
+// One line of /proc/pid/maps, which looks like this:
+//
+//   55d4c1a00000-55d4c1a02000 r-xp 00001000 08:01 1234    /usr/bin/foo
+//
+// i.e., address range, permissions, file offset, device, inode, and (optionally) a path
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MemoryMap {
+    pub start: VirtAddr,
+    pub end: VirtAddr,
+    pub readable: bool,
+    pub writable: bool,
+    pub executable: bool,
+    pub offset: u64,
+    pub path: Option<String>,
+}
+
+impl MemoryMap {
+    pub fn contains(&self, addr: VirtAddr) -> bool {
+        self.start <= addr && addr < self.end
+    }
+
+    pub(crate) fn prot_flags(&self) -> i32 {
+        let mut prot = 0;
+        if self.readable {
+            prot |= PROT_READ;
+        }
+        if self.writable {
+            prot |= PROT_WRITE;
+        }
+        if self.executable {
+            prot |= PROT_EXEC;
+        }
+        prot
+    }
+}
+
+impl FromStr for MemoryMap {
+    type Err = DrbugError;
+
+    fn from_str(line: &str) -> DrbugResult<Self> {
+        let err = || DrbugError::InvalidMemoryMap(line.into());
+        let mut fields = line.split_whitespace();
+
+        let (start, end) = fields.next().and_then(|r| r.split_once('-')).ok_or_else(err)?;
+        let perms = fields.next().ok_or_else(err)?.as_bytes();
+        if perms.len() < 3 {
+            return Err(err());
+        }
+        let offset = u64::from_str_radix(fields.next().ok_or_else(err)?, 16)?;
+
+        // Skip the device and inode fields; anything left over is the path (which can, in
+        // theory, have spaces in it)
+        let path = fields.skip(2).collect::<Vec<_>>().join(" ");
+
+        Ok(MemoryMap {
+            start: start.parse()?,
+            end: end.parse()?,
+            readable: perms[0] == b'r',
+            writable: perms[1] == b'w',
+            executable: perms[2] == b'x',
+            offset,
+            path: if path.is_empty() { None } else { Some(path) },
+        })
+    }
+}
@@ -1,16 +1,42 @@
-use super::Process;
+use iced_x86::Mnemonic;
+
+use super::{
+    Process,
+    ProcessState,
+};
 use crate::address::VirtAddr;
 use crate::breakpoint::{
     BreakList,
     Breakable,
+    Breakpoint,
+    BreakpointLocation,
     BreakpointSite,
 };
+use crate::disassembly::Disassembler;
 use crate::{
     DrbugError,
     DrbugResult,
+    Empty,
 };
 
+// Where `run_to_frame` is trying to get to, and whether the breakpoint site there is one that it
+// made for the purpose
+#[derive(Debug)]
+pub(super) struct RunToTarget {
+    addr: VirtAddr,
+    sp: VirtAddr,
+    is_internal: bool,
+}
+
 impl Process {
+    pub fn breakpoint(&self, id: &usize) -> Option<Breakpoint> {
+        self.breakpoints.get(id).cloned()
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = &Breakpoint> {
+        self.breakpoints.values()
+    }
+
     pub fn breakpoint_sites(&self) -> &BreakList<BreakpointSite> {
         &self.breakpoint_sites
     }
@@ -19,13 +45,248 @@ impl Process {
         &mut self.breakpoint_sites
     }
 
+    // Creates a breakpoint with a site at every address that the location currently resolves to;
+    // if it doesn't resolve to anything, the breakpoint is pending until a library that defines it
+    // gets loaded.  Like the sites themselves, new breakpoints start out disabled.
+    pub fn create_breakpoint(&mut self, location: BreakpointLocation) -> DrbugResult<Breakpoint> {
+        self.add_breakpoint(location, false)
+    }
+
     pub fn create_breakpoint_site(&mut self, addr: VirtAddr) -> DrbugResult<BreakpointSite> {
+        self.add_breakpoint_site(addr, None, false)
+    }
+
+    // A temporary breakpoint (and all of its sites) is deleted the first time it stops the process,
+    // once the process moves on from that stop; until then, it's disabled, but still around for
+    // `triggered_breakpoint` and anything else that wants to look it up
+    pub fn create_temporary_breakpoint(&mut self, location: BreakpointLocation) -> DrbugResult<Breakpoint> {
+        self.add_breakpoint(location, true)
+    }
+
+    // A temporary breakpoint site deletes itself the first time it stops the process
+    pub fn create_temporary_breakpoint_site(&mut self, addr: VirtAddr) -> DrbugResult<BreakpointSite> {
+        self.add_breakpoint_site(addr, None, true)
+    }
+
+    // The breakpoint that caused the current stop, if any; bare breakpoint sites (like the ones
+    // that `run_to` uses) don't count
+    pub fn triggered_breakpoint(&self) -> Option<&Breakpoint> {
+        self.triggered_breakpoint.as_ref()
+    }
+
+    pub fn remove_breakpoint(&mut self, id: &usize) -> DrbugResult<Option<Breakpoint>> {
+        let Some(bp) = self.breakpoints.remove(id) else {
+            return Ok(None);
+        };
+        for site in bp.sites() {
+            self.breakpoint_sites.remove(&site.id())?;
+        }
+        Ok(Some(bp))
+    }
+
+    // Runs until the process reaches `addr` or stops for some other reason; if there isn't a
+    // breakpoint at `addr` already, we use an internal one, which gets cleaned up either way
+    pub fn run_to(&mut self, addr: VirtAddr) -> DrbugResult<ProcessState> {
+        self.run_to_frame(addr, VirtAddr(0))
+    }
+
+    // Like `step_instruction`, except that a `call` runs the whole function and stops at the return
+    // address (once the stack pointer is back to where it was before the call)
+    pub fn step_over_instruction(&mut self) -> DrbugResult<ProcessState> {
+        let instructions = Disassembler::new(self).disassemble(None, 1)?;
+        let Some(instr) = instructions.first().filter(|instr| instr.mnemonic() == Mnemonic::Call) else {
+            return self.step_instruction();
+        };
+
+        let sp = self.get_sp()?;
+        self.run_to_frame(VirtAddr(instr.next_ip()), sp)
+    }
+
+    // Runs until the current function returns to its caller
+    pub fn step_out(&mut self) -> DrbugResult<ProcessState> {
+        let (return_addr, caller_sp) = self.return_frame()?;
+        self.run_to_frame(return_addr, caller_sp)
+    }
+
+    // Whether hitting `site` should stop the process.  Getting to where `run_to_frame` is going
+    // always does, without checking or counting anything on a user breakpoint that happens to be
+    // there too, since it isn't the user's breakpoint that we stopped for; in a deeper frame, the
+    // internal site never stops it, but a user breakpoint gets its usual say.
+    pub(super) fn should_stop_at_site(&mut self, site: &BreakpointSite) -> DrbugResult<bool> {
+        if let Some(target) = self.run_to_target.as_ref().filter(|target| target.addr == site.addr()) {
+            let is_internal = target.is_internal;
+            if self.get_sp()? >= target.sp {
+                return Ok(true);
+            } else if is_internal {
+                return Ok(false);
+            }
+        }
+        self.handle_breakpoint_hit(site)
+    }
+
+    // A breakpoint with a condition only counts as a hit if the condition holds; if the condition
+    // can't be evaluated (e.g., it reads from unmapped memory), we stop so the user can take a look.
+    // Hits that pass the condition are counted, and then the ignore count gets a say.  Sites that
+    // belong to a breakpoint have to get past both their own checks and the breakpoint's.  Returns
+    // true if the process should stop.
+    fn handle_breakpoint_hit(&mut self, site: &BreakpointSite) -> DrbugResult<bool> {
+        let bp = site.breakpoint_id().and_then(|id| self.breakpoint(&id));
+        let conditions = [site.condition(), bp.as_ref().and_then(|bp| bp.condition())];
+        if conditions.iter().flatten().any(|cond| !cond.is_true(self).unwrap_or(true)) {
+            return Ok(false);
+        }
+
+        if !site.clone().record_hit()? {
+            return Ok(false);
+        }
+
+        let Some(mut bp) = bp else {
+            if site.is_temporary() {
+                self.breakpoint_sites.remove(&site.id())?;
+            }
+            return Ok(true);
+        };
+
+        let should_stop = bp.record_hit()?;
+        if should_stop {
+            if bp.is_temporary() {
+                bp.disable()?;
+            }
+            self.triggered_breakpoint = Some(bp);
+        }
+        Ok(should_stop)
+    }
+
+    // Called whenever the process moves on from a stop; this is where temporary breakpoints
+    // actually get deleted
+    pub(super) fn clear_triggered_breakpoint(&mut self) -> Empty {
+        if let Some(bp) = self.triggered_breakpoint.take()
+            && bp.is_temporary()
+        {
+            self.remove_breakpoint(&bp.id())?;
+        }
+        Ok(())
+    }
+
+    // Any int3 that we've put at `addr`, including the one we use to track library loads
+    pub(super) fn enabled_site_at(&self, addr: &VirtAddr) -> Option<BreakpointSite> {
+        let site = self.breakpoint_sites.get_by_addr(addr).filter(|site| site.enabled());
+        site.or_else(|| self.rendezvous_site().filter(|site| site.addr() == *addr))
+    }
+
+    // The sites all belonged to the old process, so every breakpoint starts over as pending
+    pub(super) fn carry_over_breakpoints(&mut self) -> Empty {
+        for bp in self.breakpoints.values_mut() {
+            bp.clear_sites();
+            bp.relocate();
+        }
+        if self
+            .breakpoints
+            .values()
+            .any(|bp| !matches!(bp.location(), BreakpointLocation::Address(_)))
+        {
+            self.track_library_loads()?;
+        }
+        self.refresh_breakpoints()
+    }
+
+    // Called whenever the set of loaded libraries changes: breakpoints that refer to symbols drop
+    // the sites in libraries that went away (which puts them back to pending if that was all of
+    // them), and pick up sites in libraries that just showed up.  Pending address breakpoints (which
+    // only happen after a restart) get their site as soon as the address is mapped.
+    pub(super) fn refresh_breakpoints(&mut self) -> Empty {
+        let maps = self.memory_maps()?;
+        let breakpoints: Vec<_> = self.breakpoints.values().cloned().collect();
+        for mut bp in breakpoints {
+            if matches!(bp.location(), BreakpointLocation::Address(_)) && !bp.is_pending() {
+                continue;
+            }
+
+            for mut site in bp.sites() {
+                if !maps.iter().any(|map| map.contains(site.addr())) {
+                    site.unload();
+                    bp.remove_site(site.id());
+                    self.breakpoint_sites.remove(&site.id())?;
+                }
+            }
+
+            // Some other breakpoint might have gotten to an address first; sites aren't shared
+            for addr in self.resolve_location(bp.location())? {
+                if self.breakpoint_sites.get_by_addr(&addr).is_none() && maps.iter().any(|map| map.contains(addr)) {
+                    let site = self.add_breakpoint_site(addr, Some(bp.id()), false)?;
+                    bp.add_site(site)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn add_breakpoint(&mut self, location: BreakpointLocation, is_temporary: bool) -> DrbugResult<Breakpoint> {
+        if !matches!(location, BreakpointLocation::Address(_)) {
+            self.track_library_loads()?;
+        }
+
+        // Check everything up front, so we don't leave a half-built breakpoint lying around
+        let addrs = self.resolve_location(&location)?;
+        for addr in &addrs {
+            if let Some(site) = self.breakpoint_sites.get_by_addr(addr) {
+                return Err(DrbugError::BreakpointSiteExists(site.id(), *addr));
+            }
+        }
+
+        // Address breakpoints also remember where they are relative to the module, for restarts
+        let module_location = match location {
+            BreakpointLocation::Address(addr) => self.module_location(addr)?,
+            _ => None,
+        };
+        let mut bp = Breakpoint::new(location, is_temporary);
+        bp.set_module_location(module_location);
+        for addr in addrs {
+            let site = self.add_breakpoint_site(addr, Some(bp.id()), false)?;
+            bp.add_site(site)?;
+        }
+        self.breakpoints.insert(bp.id(), bp.clone());
+        Ok(bp)
+    }
+
+    fn add_breakpoint_site(
+        &mut self,
+        addr: VirtAddr,
+        breakpoint_id: Option<usize>,
+        is_temporary: bool,
+    ) -> DrbugResult<BreakpointSite> {
         if let Some(site) = self.breakpoint_sites.get_by_addr(&addr) {
             return Err(DrbugError::BreakpointSiteExists(site.id(), addr));
         }
 
-        let site = BreakpointSite::new(self.pid, addr);
+        let site = BreakpointSite::new(self.pid, addr, breakpoint_id, is_temporary);
         self.breakpoint_sites.add(site.clone());
         Ok(site)
     }
+
+    // Runs until the process reaches `addr` with the stack pointer at or above `sp`.  A recursive
+    // call can get to the same address in a deeper stack frame (with a lower stack pointer), and we
+    // don't want to stop there; this is also why the internal breakpoint isn't temporary, since it
+    // might get hit more than once.  `should_stop_at_site` is what actually decides.
+    fn run_to_frame(&mut self, addr: VirtAddr, sp: VirtAddr) -> DrbugResult<ProcessState> {
+        let (mut site, is_internal) = match self.breakpoint_sites.get_by_addr(&addr) {
+            Some(site) => (site, false),
+            None => (self.add_breakpoint_site(addr, None, false)?, true),
+        };
+        let was_enabled = site.enabled();
+        site.enable()?;
+
+        self.run_to_target = Some(RunToTarget { addr, sp, is_internal });
+        let state = self.resume().and_then(|_| self.wait_on_signal());
+        self.run_to_target = None;
+        let state = state?;
+        if state.is_stopped() {
+            if is_internal {
+                self.breakpoint_sites.remove(&site.id())?;
+            } else if !was_enabled {
+                site.disable()?;
+            }
+        }
+        Ok(state)
+    }
 }
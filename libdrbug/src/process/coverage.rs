@@ -0,0 +1,271 @@
+use std::collections::{
+    BTreeMap,
+    BTreeSet,
+};
+use std::fs::File;
+use std::io::{
+    BufWriter,
+    Write,
+};
+use std::path::PathBuf;
+
+use iced_x86::{
+    Decoder,
+    DecoderOptions,
+    FlowControl,
+};
+use nix::sys::ptrace;
+
+use super::Process;
+use crate::address::VirtAddr;
+use crate::breakpoint::Breakable;
+use crate::disassembly::BITNESS;
+use crate::{
+    DrbugError,
+    DrbugResult,
+    Empty,
+    syscall_error,
+};
+
+const INT3: u8 = 0xcc;
+
+#[derive(Debug)]
+struct Block {
+    size: u16,
+    orig_data: u8,
+    hit: bool,
+}
+
+// Coverage works by putting an `int3` at the start of every basic block in the executable and
+// taking each one out again the first time it's hit, so every block costs us at most one stop.
+// These aren't breakpoint sites: there are thousands of them, and they don't need conditions or
+// hit counts or any of that, they just need to get in and out of memory quickly.
+#[derive(Debug)]
+pub(super) struct Coverage {
+    output: PathBuf,
+    path: String,
+    base: VirtAddr,
+    end: VirtAddr,
+    entry: VirtAddr,
+    code_maps: Vec<(VirtAddr, VirtAddr)>,
+    blocks: BTreeMap<VirtAddr, Block>,
+}
+
+impl Coverage {
+    // The drcov format is what DynamoRIO's coverage tool writes, and what Lighthouse, bncov, and
+    // friends know how to read: a text header with a table of modules, and then a binary table of
+    // the blocks that got hit, as (offset from module start: u32, size: u16, module id: u16)
+    fn write_drcov(&self, mut out: impl Write) -> Empty {
+        let hits: Vec<_> = self.blocks.iter().filter(|(_, block)| block.hit).collect();
+        writeln!(out, "DRCOV VERSION: 2")?;
+        writeln!(out, "DRCOV FLAVOR: drcov")?;
+        writeln!(out, "Module Table: version 2, count 1")?;
+        writeln!(out, "Columns: id, base, end, entry, checksum, timestamp, path")?;
+        writeln!(
+            out,
+            " 0, {:#018x}, {:#018x}, {:#018x}, 0x00000000, 0x00000000, {}",
+            self.base.0, self.end.0, self.entry.0, self.path
+        )?;
+        writeln!(out, "BB Table: {} bbs", hits.len())?;
+        for (addr, block) in hits {
+            out.write_all(&((addr.0 - self.base.0) as u32).to_le_bytes())?;
+            out.write_all(&block.size.to_le_bytes())?;
+            out.write_all(&0u16.to_le_bytes())?;
+        }
+        out.flush()?;
+        Ok(())
+    }
+}
+
+impl Process {
+    // (blocks hit, total blocks), if we're collecting coverage
+    pub fn coverage(&self) -> Option<(usize, usize)> {
+        let coverage = self.coverage.as_ref()?;
+        Some((coverage.blocks.values().filter(|block| block.hit).count(), coverage.blocks.len()))
+    }
+
+    // Finds the basic blocks in the executable's code and sets a trap on each of them; the results
+    // are written to `output` when the process exits (or when we're done with it, if that's first)
+    pub fn start_coverage(&mut self, output: PathBuf) -> Empty {
+        let exe = self.executable_module()?.ok_or(DrbugError::ExecutableNotMapped)?;
+        let maps: Vec<_> = self
+            .memory_maps()?
+            .into_iter()
+            .filter(|map| map.path.as_ref() == Some(&exe.path))
+            .collect();
+        let (Some(base), Some(end)) = (maps.iter().map(|map| map.start).min(), maps.iter().map(|map| map.end).max())
+        else {
+            return Err(DrbugError::ExecutableNotMapped);
+        };
+
+        let mut blocks = BTreeMap::new();
+        let mut code_maps = vec![];
+        for map in maps.iter().filter(|map| map.executable) {
+            code_maps.push((map.start, map.end));
+            let code = self.read_memory_without_traps(map.start, (map.end.0 - map.start.0) as usize)?;
+            let mut traps = BTreeMap::new();
+            for (addr, size) in find_blocks(&code, map.start) {
+                // Anything that already has an int3 on it can look after itself
+                if self.enabled_site_at(&addr).is_some() {
+                    continue;
+                }
+                let orig_data = code[(addr.0 - map.start.0) as usize];
+                blocks.insert(addr, Block { size, orig_data, hit: false });
+                traps.insert(addr, INT3);
+            }
+            self.write_bytes(&traps)?;
+        }
+
+        let entry = self.entry_point()?;
+        self.coverage = Some(Coverage {
+            output,
+            path: exe.path,
+            base,
+            end,
+            entry,
+            code_maps,
+            blocks,
+        });
+        Ok(())
+    }
+
+    // Writes out the coverage file, and takes out any traps that haven't been hit yet (as long as
+    // there's still a process to take them out of)
+    pub(super) fn finish_coverage(&mut self) -> Empty {
+        let Some(coverage) = self.coverage.take() else {
+            return Ok(());
+        };
+        coverage.write_drcov(BufWriter::new(File::create(&coverage.output)?))?;
+
+        if !self.state.is_stopped() {
+            return Ok(());
+        }
+        for (start, end) in &coverage.code_maps {
+            let mut orig = BTreeMap::new();
+            for (addr, block) in coverage.blocks.range(start..end).filter(|(_, block)| !block.hit) {
+                match self.breakpoint_sites.get_by_addr(addr).filter(|site| site.enabled()) {
+                    Some(site) => site.set_orig_data(block.orig_data),
+                    None => {
+                        orig.insert(*addr, block.orig_data);
+                    },
+                }
+            }
+            self.write_bytes(&orig)?;
+        }
+        Ok(())
+    }
+
+    // Marks the block at `addr` as hit and puts back the original code, returning false if there
+    // wasn't a trap there.  If a breakpoint got set on the block after we put the trap in, the
+    // breakpoint thinks the int3 is the original code, so we set it straight instead.
+    pub(super) fn take_coverage_block(&mut self, addr: VirtAddr) -> DrbugResult<bool> {
+        let Some(block) = self
+            .coverage
+            .as_mut()
+            .and_then(|c| c.blocks.get_mut(&addr))
+            .filter(|block| !block.hit)
+        else {
+            return Ok(false);
+        };
+        block.hit = true;
+        let orig_data = block.orig_data;
+
+        match self.breakpoint_sites.get_by_addr(&addr).filter(|site| site.enabled()) {
+            Some(site) => site.set_orig_data(orig_data),
+            None => self.write_memory(addr, &[orig_data])?,
+        }
+        Ok(true)
+    }
+
+    // Puts the original code back over any traps in a chunk of memory we just read
+    pub(super) fn hide_coverage_traps(&self, addr: VirtAddr, data: &mut [u8]) {
+        let Some(coverage) = &self.coverage else {
+            return;
+        };
+        let unhit = coverage
+            .blocks
+            .range(addr..addr.add(data.len()))
+            .filter(|(_, block)| !block.hit);
+        for (block_addr, block) in unhit {
+            data[(block_addr.0 - addr.0) as usize] = block.orig_data;
+        }
+    }
+
+    // Changes a bunch of bytes in one go: everything gets read at once, and then there's one ptrace
+    // write for each word that has something in it to change (rather than a read and a write for
+    // every byte, which adds up fast with thousands of blocks).  The addresses all have to be in
+    // the same mapping.
+    fn write_bytes(&mut self, bytes: &BTreeMap<VirtAddr, u8>) -> Empty {
+        let (Some(first), Some(last)) = (bytes.keys().next(), bytes.keys().next_back()) else {
+            return Ok(());
+        };
+        let start = first.0 & !7;
+        let mut data = self.read_memory(VirtAddr(start), ((last.0 & !7) + 8 - start) as usize)?;
+        for (addr, byte) in bytes {
+            data[(addr.0 - start) as usize] = *byte;
+        }
+
+        let words: BTreeSet<_> = bytes.keys().map(|addr| addr.0 & !7).collect();
+        for word in words {
+            let offset = (word - start) as usize;
+            let val = i64::from_le_bytes(data[offset..offset + 8].try_into()?);
+            syscall_error!(ptrace::write(self.pid, unsafe { VirtAddr(word).into_void_ptr() }, val))?;
+        }
+        Ok(())
+    }
+}
+
+// A linear sweep over the code: a block starts at the beginning, after every instruction that can
+// change the flow of control, and at every direct branch target, and runs until the next one
+// starts (or it hits one of those instructions).  Anything that doesn't decode cleanly is probably
+// data, and the sweep picks back up after it.  We only put blocks at addresses that the sweep
+// actually decoded an instruction at, since an int3 in the middle of an instruction would be bad.
+fn find_blocks(code: &[u8], start: VirtAddr) -> Vec<(VirtAddr, u16)> {
+    let decoder = Decoder::with_ip(BITNESS, code, start.0, DecoderOptions::NONE);
+    let instructions: Vec<_> = decoder.into_iter().collect();
+    let end = start.0 + code.len() as u64;
+
+    let mut starts = BTreeSet::from([start.0]);
+    for instr in &instructions {
+        if instr.is_invalid() {
+            starts.insert(instr.next_ip());
+        } else if instr.flow_control() != FlowControl::Next {
+            starts.insert(instr.next_ip());
+            let target = instr.near_branch_target();
+            if (start.0..end).contains(&target) {
+                starts.insert(target);
+            }
+        }
+    }
+
+    let mut blocks = vec![];
+    let mut current: Option<u64> = None;
+    let mut end_of_block = start.0;
+    for instr in &instructions {
+        if instr.is_invalid() {
+            current = None;
+            continue;
+        }
+        if starts.contains(&instr.ip()) {
+            current = Some(instr.ip());
+        }
+        let Some(block_start) = current else {
+            continue;
+        };
+        end_of_block = instr.next_ip();
+        if instr.flow_control() != FlowControl::Next || starts.contains(&end_of_block) {
+            blocks.push((VirtAddr(block_start), block_size(block_start, end_of_block)));
+            current = None;
+        }
+    }
+
+    // The code ran out partway through a block
+    if let Some(block_start) = current {
+        blocks.push((VirtAddr(block_start), block_size(block_start, end_of_block)));
+    }
+    blocks
+}
+
+fn block_size(start: u64, end: u64) -> u16 {
+    (end - start).min(u16::MAX as u64) as u16
+}
@@ -0,0 +1,71 @@
+use super::Process;
+use crate::address::VirtAddr;
+use crate::breakpoint::{
+    Breakable,
+    BreakpointSite,
+};
+use crate::{
+    DrbugResult,
+    Empty,
+};
+
+const RT_CONSISTENT: u32 = 0;
+const R_STATE_OFFSET: usize = 24; // r_version, then (padded) r_map and r_brk, see <link.h>
+
+// The dynamic loader calls `_dl_debug_state` (an empty function that only exists so that debuggers
+// have something to put a breakpoint on) right before it changes the list of loaded libraries, and
+// again once it's done; `_r_debug.r_state` tells us which of the two calls we're looking at.  This
+// is the same "rendezvous" protocol that gdb uses.
+#[derive(Debug)]
+pub(super) struct Rendezvous {
+    site: BreakpointSite,
+    r_state_addr: Option<VirtAddr>,
+}
+
+impl Process {
+    // We only bother tracking libraries once somebody sets a breakpoint by name; statically-linked
+    // programs don't have a dynamic loader, so for them this doesn't do anything.  The rendezvous
+    // breakpoint is kept out of `breakpoint_sites`, since the user didn't ask for it.
+    pub(super) fn track_library_loads(&mut self) -> Empty {
+        if self.rendezvous.is_some() {
+            return Ok(());
+        }
+
+        for module in self.modules()? {
+            let Some(brk) = module.elf.symbols_by_name("_dl_debug_state").next() else {
+                continue;
+            };
+            let r_debug = module.elf.symbols_by_name("_r_debug").next();
+
+            let mut site = BreakpointSite::new(self.pid, module.symbol_addr(brk), None, false);
+            site.enable()?;
+            self.rendezvous = Some(Rendezvous {
+                site,
+                r_state_addr: r_debug.map(|sym| module.symbol_addr(sym).add(R_STATE_OFFSET)),
+            });
+            break;
+        }
+        Ok(())
+    }
+
+    pub(super) fn rendezvous_site(&self) -> Option<BreakpointSite> {
+        self.rendezvous.as_ref().map(|r| r.site.clone()).filter(|site| site.enabled())
+    }
+
+    // Once the loader says the library list is consistent again, any libraries that were going to
+    // be (un)loaded have been, so that's when we go looking for new breakpoint locations
+    pub(super) fn handle_library_event(&mut self) -> Empty {
+        if self.library_list_changing()? {
+            return Ok(());
+        }
+        self.refresh_breakpoints()
+    }
+
+    fn library_list_changing(&self) -> DrbugResult<bool> {
+        let Some(addr) = self.rendezvous.as_ref().and_then(|r| r.r_state_addr) else {
+            return Ok(false);
+        };
+        let data = self.read_memory(addr, 4)?;
+        Ok(u32::from_le_bytes(data[..4].try_into()?) != RT_CONSISTENT)
+    }
+}
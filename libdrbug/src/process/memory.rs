@@ -10,6 +10,7 @@ use nix::sys::uio::{
 use super::Process;
 use crate::address::VirtAddr;
 use crate::breakpoint::Breakable;
+use crate::maps::MemoryMap;
 use crate::{
     DrbugError,
     DrbugResult,
@@ -18,6 +19,11 @@ use crate::{
 };
 
 impl Process {
+    pub fn memory_maps(&self) -> DrbugResult<Vec<MemoryMap>> {
+        let maps = std::fs::read_to_string(format!("/proc/{}/maps", self.pid))?;
+        maps.lines().map(|line| line.parse()).collect()
+    }
+
     pub fn read_memory(&self, mut addr: VirtAddr, mut size: usize) -> DrbugResult<Vec<u8>> {
         let mut buf = vec![0u8; size];
         let local_iov = IoSliceMut::new(&mut buf);
@@ -43,16 +49,20 @@ impl Process {
 
     pub fn read_memory_without_traps(&self, addr: VirtAddr, size: usize) -> DrbugResult<Vec<u8>> {
         let mut data = self.read_memory(addr, size)?;
-        let sites = self.breakpoint_sites.get_in_region(&addr, &addr.add(size));
+        let mut sites = self.breakpoint_sites.get_in_region(&addr, &addr.add(size));
+        sites.extend(self.rendezvous_site());
 
         // Fix all the `int3` instructions we stuck in
         for site in sites {
-            if !site.enabled() {
+            let Some(offset) = site.addr().delta(addr) else {
+                continue;
+            };
+            if !site.enabled() || offset >= data.len() {
                 continue;
             }
-            let offset = site.addr().delta(addr).unwrap();
             data[offset] = site.orig_data();
         }
+        self.hide_coverage_traps(addr, &mut data);
         Ok(data)
     }
 
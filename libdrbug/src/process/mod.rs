@@ -1,18 +1,38 @@
 mod breakpoint;
+mod coverage;
+mod library;
 mod memory;
+mod record;
+mod replay;
 mod state;
-
+mod symbols;
+mod syscall;
+mod trace;
+mod unwind;
+mod watchpoint;
+
+use std::cell::RefCell;
+use std::collections::{
+    BTreeMap,
+    HashMap,
+};
 use std::ffi::CString;
 use std::io::Write;
 use std::ops::Drop;
 use std::os::fd::OwnedFd;
+use std::path::PathBuf;
+use std::rc::Rc;
 
+use nix::errno::Errno;
 use nix::sys::personality::Persona;
 use nix::sys::signal::{
     Signal,
     kill,
 };
-use nix::sys::wait::waitpid;
+use nix::sys::wait::{
+    WaitStatus,
+    waitpid,
+};
 use nix::sys::{
     personality,
     ptrace,
@@ -25,13 +45,38 @@ use nix::unistd::{
     fork,
 };
 
-pub use self::state::ProcessState;
+use self::breakpoint::RunToTarget;
+use self::coverage::Coverage;
+use self::library::Rendezvous;
+pub use self::record::DEFAULT_RECORD_LIMIT;
+use self::record::{
+    History,
+    PendingStep,
+};
+use self::replay::SyscallLog;
+pub use self::replay::SyscallMode;
+pub use self::state::{
+    ProcessState,
+    TrapType,
+};
+pub use self::symbols::Module;
+use self::syscall::SYSCALL_INSTR;
+pub use self::trace::{
+    TraceEnd,
+    TraceEntry,
+    TraceFormat,
+    TraceWriter,
+    read_trace,
+};
 use crate::address::VirtAddr;
 use crate::breakpoint::{
     BreakList,
     Breakable,
+    Breakpoint,
     BreakpointSite,
 };
+use crate::disassembly::Disassembler;
+use crate::elf::Elf;
 use crate::pipe::Pipe;
 use crate::register::Registers;
 use crate::register::info::{
@@ -39,6 +84,11 @@ use crate::register::info::{
     register_info_by_id,
 };
 use crate::register::value::RegisterValue;
+use crate::watchpoint::{
+    PageWatchpoint,
+    SoftwareWatchpoint,
+    Watchpoint,
+};
 use crate::{
     DrbugError,
     DrbugResult,
@@ -46,35 +96,108 @@ use crate::{
     syscall_error,
 };
 
-#[derive(Default)]
+// nix doesn't have a wrapper for this one; it's from <asm/ptrace-abi.h>
+const PTRACE_SINGLEBLOCK: libc::c_uint = 33;
+
+#[derive(Debug, Default)]
 pub struct ProcessOptions {
-    pub disable_aslr: bool,     // should only use for testing
-    pub start_unattached: bool, // use the negative here so the default does the right thing
+    pub coverage: Option<PathBuf>, // where to write a drcov file
+    pub disable_aslr: bool,        // should only use for testing
+    pub start_unattached: bool,    // use the negative here so the default does the right thing
     pub stdout: Option<OwnedFd>,
+    pub stop_at: StopAt,
+    pub syscalls: SyscallMode,
+}
+
+// Where a newly-launched process first stops.  Right after the `execve` is what the kernel gives
+// us, but that's before the dynamic loader has done anything, which usually isn't very interesting;
+// the other options run the process up to the executable's entry point or to `main`.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum StopAt {
+    #[default]
+    Exec,
+    Entry,
+    Main,
 }
 
 #[derive(Debug)]
 pub struct Process {
     attached: bool,
+    block_stepping: bool,
     breakpoint_sites: BreakList<BreakpointSite>,
+    breakpoints: BTreeMap<usize, Breakpoint>,
+    continuing: bool,
+    coverage: Option<Coverage>,
+    elf_cache: RefCell<HashMap<String, Option<Rc<Elf>>>>,
+    history: Option<History>,
+    in_syscall: bool,
+    launched_with: Option<(String, ProcessOptions)>,
+    page_watchpoints: BTreeMap<usize, PageWatchpoint>,
     pid: Pid,
+    recorded_step: Option<PendingStep>,
     registers: Registers,
+    rendezvous: Option<Rendezvous>,
+    run_to_target: Option<RunToTarget>,
+    software_watchpoints: BTreeMap<usize, SoftwareWatchpoint>,
     state: ProcessState,
+    stepped_over_site: Option<BreakpointSite>,
+    stepping_over_syscall: bool,
+    syscall_log: Option<SyscallLog>,
     terminate_on_end: bool,
+    trap_type: Option<TrapType>,
+    triggered_breakpoint: Option<Breakpoint>,
+    triggered_page_watchpoint: Option<PageWatchpoint>,
+    triggered_software_watchpoint: Option<SoftwareWatchpoint>,
+    triggered_watchpoint: Option<Watchpoint>,
+    watch_stepping: bool,
+    watchpoints: BreakList<Watchpoint>,
 }
 
 impl Process {
-    fn new_then_wait(pid: Pid, opts: ProcessOptions, terminate_on_end: bool) -> DrbugResult<Self> {
+    // We hang on to the path and options for processes that we launched so that they can be
+    // restarted; that includes the stdout fd, so the new process writes to the same place
+    fn new_then_wait(pid: Pid, opts: ProcessOptions, launch_path: Option<&str>) -> DrbugResult<Self> {
+        let stop_at = opts.stop_at;
+        let syscalls = opts.syscalls.clone();
+        let coverage = opts.coverage.clone();
         let mut proc = Process {
             attached: !opts.start_unattached,
+            block_stepping: false,
             breakpoint_sites: BreakList::new(),
+            breakpoints: BTreeMap::new(),
+            continuing: false,
+            coverage: None,
+            elf_cache: RefCell::new(HashMap::new()),
+            history: None,
+            in_syscall: false,
+            launched_with: launch_path.map(|path| (path.into(), opts)),
+            page_watchpoints: BTreeMap::new(),
             pid,
+            recorded_step: None,
             registers: Registers::new(pid),
+            rendezvous: None,
+            run_to_target: None,
+            software_watchpoints: BTreeMap::new(),
             state: ProcessState::Stopped { signal: None },
-            terminate_on_end,
+            stepped_over_site: None,
+            stepping_over_syscall: false,
+            syscall_log: None,
+            terminate_on_end: launch_path.is_some(),
+            trap_type: None,
+            triggered_breakpoint: None,
+            triggered_page_watchpoint: None,
+            triggered_software_watchpoint: None,
+            triggered_watchpoint: None,
+            watch_stepping: false,
+            watchpoints: BreakList::new(),
         };
         if proc.attached {
             proc.wait_on_signal()?;
+            proc.start_syscall_log(&syscalls)?;
+            if let Some(output) = coverage {
+                proc.start_coverage(output)?;
+            }
+            proc.run_to_start(stop_at)?;
         }
         Ok(proc)
     }
@@ -82,10 +205,69 @@ impl Process {
     pub fn attach(pid_int: i32) -> DrbugResult<Self> {
         let pid = Pid::from_raw(pid_int);
         syscall_error!(ptrace::attach(pid))?;
-        Self::new_then_wait(pid, Default::default(), false)
+        Self::new_then_wait(pid, Default::default(), None)
     }
 
     pub fn launch(path: &str, opts: ProcessOptions) -> DrbugResult<Self> {
+        let child = Self::spawn(path, &opts)?;
+        Self::new_then_wait(child, opts, Some(path))
+    }
+
+    // Kills the process and launches it again with the same options.  Breakpoints and watchpoints
+    // carry over (keeping their ids, so anything that refers to them still works), but everything
+    // else about the old process is forgotten.  It stops in the same place it did the first time
+    // around, unless `stop_at` says otherwise.
+    pub fn restart(&mut self, stop_at: Option<StopAt>) -> DrbugResult<ProcessState> {
+        let Some((path, opts)) = self.launched_with.take() else {
+            return Err(DrbugError::RestartUnsupported(self.pid));
+        };
+
+        self.clear_triggered_breakpoint()?;
+        self.finish_coverage()?;
+        if !self.state.is_exited() && !self.state.is_terminated() {
+            let _ = kill(self.pid, Signal::SIGKILL);
+            let _ = waitpid(self.pid, None);
+        }
+
+        let spawned = Self::spawn(&path, &opts);
+        let start_unattached = opts.start_unattached;
+        let stop_at = stop_at.unwrap_or(opts.stop_at);
+        let syscalls = opts.syscalls.clone();
+        let coverage = opts.coverage.clone();
+        self.launched_with = Some((path, opts));
+        self.pid = spawned?;
+
+        self.attached = !start_unattached;
+        self.block_stepping = false;
+        self.breakpoint_sites = BreakList::new();
+        self.continuing = false;
+        self.elf_cache.borrow_mut().clear(); // in case the program got rebuilt in the meantime
+        self.history = None;
+        self.recorded_step = None;
+        self.registers = Registers::new(self.pid);
+        self.rendezvous = None;
+        self.state = ProcessState::Stopped { signal: None };
+        self.stepped_over_site = None;
+        self.stepping_over_syscall = false;
+        self.trap_type = None;
+        self.triggered_page_watchpoint = None;
+        self.triggered_software_watchpoint = None;
+        self.triggered_watchpoint = None;
+        self.watch_stepping = false;
+        if self.attached {
+            self.wait_on_signal()?;
+            self.start_syscall_log(&syscalls)?;
+            if let Some(output) = coverage {
+                self.start_coverage(output)?;
+            }
+            self.carry_over_watchpoints()?;
+            self.carry_over_breakpoints()?;
+            self.run_to_start(stop_at)?;
+        }
+        Ok(self.state)
+    }
+
+    fn spawn(path: &str, opts: &ProcessOptions) -> DrbugResult<Pid> {
         let mut channel = Pipe::new_exec_safe()?;
 
         let path_cstring = CString::new(path)?;
@@ -106,7 +288,7 @@ impl Process {
             channel.close_reader();
 
             // Replace stdout of the child process so our debugger and/or test harness can read it
-            if let Some(fd) = opts.stdout {
+            if let Some(fd) = &opts.stdout {
                 if let Err(e) = dup2_stdout(fd) {
                     let _ = write!(&mut channel, "dup2_stdout failed: {e:?}");
                     return Err(DrbugError::SyscallFailed("dup2", e));
@@ -170,8 +352,7 @@ impl Process {
             syscall_error!(waitpid(child, None))?;
             return Err(DrbugError::ChildProcessFailed(String::from_utf8_lossy(&data).into()));
         }
-
-        Self::new_then_wait(child, opts, true)
+        Ok(child)
     }
 
     pub fn get_pc(&self) -> DrbugResult<VirtAddr> {
@@ -190,14 +371,41 @@ impl Process {
         &mut self.registers
     }
 
+    pub fn get_sp(&self) -> DrbugResult<VirtAddr> {
+        let rsp_info = register_info_by_id(&RegisterId::rsp);
+        self.registers.read(rsp_info).map(|v| match v {
+            RegisterValue::U64(rsp) => VirtAddr(rsp),
+            _ => panic!("should never happen"),
+        })
+    }
+
     pub fn pid(&self) -> Pid {
         self.pid
     }
 
     pub fn resume(&mut self) -> Empty {
+        self.continuing = true;
+        self.sync_page_watchpoints()?;
+        self.refresh_software_watchpoints()?;
+        if self.history.is_some() || self.software_watchpoints.values().any(|wp| wp.enabled()) {
+            // Software watchpoints can only be checked in between instructions, and recording has
+            // to see every instruction, so instead of continuing we single-step, and
+            // `wait_on_signal` keeps stepping until something interesting happens
+            self.watch_stepping = true;
+            self.recorded_step = self.begin_recorded_step()?;
+            return self.start_single_step();
+        }
+
+        // If the instruction we're about to run has a coverage trap on it, it's hit either way, and
+        // taking it out first means we don't have to worry about stepping over it
         let pc = self.get_pc()?;
-        if let Some(mut bp) = self.breakpoint_sites.get_by_addr(&pc)
-            && bp.enabled()
+        self.take_coverage_block(pc)?;
+        self.flush_registers()?;
+
+        // In the middle of a syscall, the pc is already past the `syscall` instruction, but the
+        // instruction at the pc hasn't run yet, so there's nothing to step over until it returns
+        if !self.in_syscall
+            && let Some(mut bp) = self.enabled_site_at(&pc)
         {
             bp.disable()?;
             syscall_error!(ptrace::step(self.pid, None))?;
@@ -205,7 +413,11 @@ impl Process {
             bp.enable()?;
         }
 
-        syscall_error!(ptrace::cont(self.pid, None))?;
+        if self.syscall_log.is_some() {
+            syscall_error!(ptrace::syscall(self.pid, None))?;
+        } else {
+            syscall_error!(ptrace::cont(self.pid, None))?;
+        }
         self.state = ProcessState::Running;
         Ok(())
     }
@@ -219,38 +431,262 @@ impl Process {
         self.state
     }
 
-    pub fn step_instruction(&mut self) -> DrbugResult<ProcessState> {
-        let pc = self.get_pc()?;
-        let mut bp_to_reenable = self.breakpoint_sites.get_by_addr(&pc);
-        if let Some(ref mut bp) = bp_to_reenable
-            && bp.enabled()
+    // Runs until the next taken branch, using the processor's branch-trap flag, so we only stop
+    // once per basic block instead of once per instruction.  Recording, software watchpoints, and
+    // syscall logging all need to see every instruction, so when any of them are on we fall back
+    // to stepping one instruction at a time until we end up somewhere other than the next one.
+    pub fn step_block(&mut self) -> DrbugResult<ProcessState> {
+        self.refresh_software_watchpoints()?;
+        if self.history.is_some()
+            || self.syscall_log.is_some()
+            || self.software_watchpoints.values().any(|wp| wp.enabled())
         {
-            bp.disable()?;
+            return self.step_block_by_instructions();
         }
 
-        syscall_error!(ptrace::step(self.pid, None))?;
-        let state = self.wait_on_signal()?;
+        self.continuing = false;
+        self.sync_page_watchpoints()?;
+        self.block_stepping = true;
+        self.start_block_step()?;
+        self.wait_on_signal()
+    }
 
-        if let Some(mut bp) = bp_to_reenable {
-            bp.enable()?;
+    pub fn step_instruction(&mut self) -> DrbugResult<ProcessState> {
+        let recorded = self.begin_recorded_step()?;
+        self.continuing = false;
+        self.sync_page_watchpoints()?;
+        self.refresh_software_watchpoints()?;
+        self.start_single_step()?;
+        let state = self.wait_on_signal()?;
+        if state.is_stopped() {
+            self.end_recorded_step(recorded);
         }
         Ok(state)
     }
 
+    pub fn trap_type(&self) -> Option<TrapType> {
+        self.trap_type
+    }
+
     pub fn wait_on_signal(&mut self) -> DrbugResult<ProcessState> {
+        loop {
+            let internal_stop = self.wait_for_stop()?;
+            if self.stepping_over_syscall && self.trap_type == Some(TrapType::Syscall) {
+                if self.in_syscall {
+                    self.flush_registers()?;
+                    syscall_error!(ptrace::syscall(self.pid, None))?;
+                    continue;
+                }
+                // The syscall has returned, which is as far as a single step would have gotten
+                self.stepping_over_syscall = false;
+                self.trap_type = Some(TrapType::SingleStep);
+            }
+
+            if self.watch_stepping {
+                let recorded = self.recorded_step.take();
+                if self.state.is_stopped() {
+                    self.end_recorded_step(recorded);
+                }
+                if self.watch_step_should_stop()? {
+                    break;
+                }
+                self.recorded_step = self.begin_recorded_step()?;
+                self.start_single_step()?;
+            } else if internal_stop && self.continuing {
+                self.resume()?;
+            } else if internal_stop && self.block_stepping {
+                self.start_block_step()?;
+            } else {
+                break;
+            }
+        }
+
+        self.block_stepping = false;
+        self.watch_stepping = false;
+        Ok(self.state)
+    }
+
+    fn run_to_start(&mut self, stop_at: StopAt) -> Empty {
+        let addr = match stop_at {
+            StopAt::Exec => return Ok(()),
+            StopAt::Entry => self.entry_point()?,
+            StopAt::Main => self.main_addr()?,
+        };
+        self.run_to(addr)?;
+        Ok(())
+    }
+
+    fn start_block_step(&mut self) -> Empty {
+        let pc = self.get_pc()?;
+        self.take_coverage_block(pc)?;
+        if let Some(mut bp) = self.enabled_site_at(&pc) {
+            bp.disable()?;
+            self.stepped_over_site = Some(bp);
+        }
+
+        self.flush_registers()?;
+
+        // SAFETY: the address and data arguments are ignored for this request, but since ptrace is
+        // variadic, they still have to be pointer-sized
+        let (addr, data) = (std::ptr::null_mut::<libc::c_void>(), std::ptr::null_mut::<libc::c_void>());
+        let res = unsafe { libc::ptrace(PTRACE_SINGLEBLOCK, self.pid.as_raw(), addr, data) };
+        Errno::result(res).map_err(|e| DrbugError::SyscallFailed("ptrace::singleblock", e))?;
+        self.state = ProcessState::Running;
+        Ok(())
+    }
+
+    fn start_single_step(&mut self) -> Empty {
+        // If we're sitting on a breakpoint we have to get the int3 out of the way first; it gets
+        // put back once the step finishes in `wait_for_stop`.  Coverage traps don't get put back.
+        let pc = self.get_pc()?;
+        self.take_coverage_block(pc)?;
+        if let Some(mut bp) = self.enabled_site_at(&pc) {
+            bp.disable()?;
+            self.stepped_over_site = Some(bp);
+        }
+
+        // A single step goes straight through a syscall without stopping at either end of it, so
+        // if we need to see it, we have to step with PTRACE_SYSCALL instead (`wait_on_signal`
+        // takes care of getting from the entry stop to the exit stop)
+        self.stepping_over_syscall =
+            self.syscall_log.is_some() && self.read_memory_without_traps(pc, SYSCALL_INSTR.len())? == SYSCALL_INSTR;
+        self.flush_registers()?;
+        if self.stepping_over_syscall {
+            syscall_error!(ptrace::syscall(self.pid, None))?;
+        } else {
+            syscall_error!(ptrace::step(self.pid, None))?;
+        }
+        self.state = ProcessState::Running;
+        Ok(())
+    }
+
+    // Anything we've written to the registers has to get out to the process before it runs again, and
+    // whatever's in the cache stops being accurate as soon as it does
+    fn flush_registers(&mut self) -> Empty {
+        self.registers.flush()?;
+        self.registers.invalidate();
+        Ok(())
+    }
+
+    fn step_block_by_instructions(&mut self) -> DrbugResult<ProcessState> {
+        loop {
+            let next_ip = Disassembler::new(self)
+                .disassemble(None, 1)?
+                .first()
+                .map(|instr| instr.next_ip());
+            let state = self.step_instruction()?;
+            if !state.is_stopped() || self.watch_step_should_stop()? || Some(self.get_pc()?.0) != next_ip {
+                return Ok(state);
+            }
+        }
+    }
+
+    // Returns true if the stop was only for the debugger's benefit, and shouldn't be reported to
+    // the user (e.g., a page fault outside the range of a page watchpoint, a breakpoint whose
+    // condition doesn't hold, or the dynamic loader telling us about a library)
+    fn wait_for_stop(&mut self) -> DrbugResult<bool> {
         let res = syscall_error!(waitpid(self.pid, None))?;
+        let syscall_stop = matches!(res, WaitStatus::PtraceSyscall(_));
         self.state = res.into();
+        if self.state.is_exited() || self.state.is_terminated() {
+            self.finish_coverage()?;
+        }
+        self.trap_type = None;
+        self.clear_triggered_breakpoint()?;
+        self.triggered_watchpoint = None;
+        self.triggered_page_watchpoint = None;
+        self.triggered_software_watchpoint = None;
+
+        // No point in re-enabling the breakpoint if the process went away
+        if let Some(mut bp) = self.stepped_over_site.take()
+            && self.state.is_stopped()
+        {
+            bp.enable()?;
+        }
+
+        let mut handled_fault = false;
+        if self.attached && self.state == (ProcessState::Stopped { signal: Some(Signal::SIGSEGV) }) {
+            handled_fault = self.handle_page_fault()?;
+        }
+
+        let mut skipped_breakpoint = false;
+        let mut library_event = false;
+        let mut syscall_event = false;
+        let mut coverage_event = false;
 
         if self.attached && self.state.is_stopped() {
             self.registers.load_all()?;
+            if syscall_stop {
+                self.trap_type = Some(TrapType::Syscall);
+            } else if self.state.is_trapped() {
+                let info = syscall_error!(ptrace::getsiginfo(self.pid))?;
+                self.trap_type = Some(TrapType::from_si_code(info.si_code));
+            }
+
             let mut pc = self.get_pc()?;
             pc.decrement();
 
-            if self.state.is_trapped() && self.breakpoint_sites.breakable_enabled_at(&pc) {
+            // If there's a breakpoint here as well, we still need to handle it below
+            if self.trap_type == Some(TrapType::SoftwareBreak) && self.take_coverage_block(pc)? {
                 self.set_pc(pc)?;
+                coverage_event = !self.breakpoint_sites.breakable_enabled_at(&pc);
+            }
+
+            match self.trap_type {
+                Some(TrapType::Syscall) => {
+                    self.handle_syscall_stop()?;
+                    syscall_event = true;
+                },
+                Some(TrapType::SoftwareBreak) if self.rendezvous_site().is_some_and(|site| site.addr() == pc) => {
+                    self.set_pc(pc)?;
+                    self.handle_library_event()?;
+                    library_event = true;
+                },
+                Some(TrapType::SoftwareBreak) if self.breakpoint_sites.breakable_enabled_at(&pc) => {
+                    self.set_pc(pc)?;
+                    let site = self.breakpoint_sites.get_by_addr(&pc).unwrap();
+                    skipped_breakpoint = !self.should_stop_at_site(&site)?;
+                },
+                Some(TrapType::HardwareBreak | TrapType::SingleStep) => {
+                    self.triggered_watchpoint = self.find_triggered_watchpoint()?;
+                },
+                _ => (),
             }
+            self.triggered_software_watchpoint = self.find_triggered_software_watchpoint()?;
         }
-        Ok(self.state)
+        Ok(skipped_breakpoint
+            || library_event
+            || syscall_event
+            || coverage_event
+            || (handled_fault && self.triggered_page_watchpoint.is_none()))
+    }
+
+    fn watch_step_should_stop(&mut self) -> DrbugResult<bool> {
+        if self.trap_type != Some(TrapType::SingleStep)
+            || self.triggered_watchpoint.is_some()
+            || self.triggered_page_watchpoint.is_some()
+            || self.triggered_software_watchpoint.is_some()
+        {
+            return Ok(true);
+        }
+
+        // Single-stepping never executes the int3 on the library-tracking breakpoint either, so
+        // we have to check for it by hand
+        let pc = self.get_pc()?;
+        if self.rendezvous_site().is_some_and(|site| site.addr() == pc) {
+            self.handle_library_event()?;
+        }
+
+        // We're about to execute an instruction with a breakpoint on it; the int3 never fired
+        // because we stepped onto it, but from the user's point of view this is a breakpoint hit
+        if let Some(site) = self.breakpoint_sites.get_by_addr(&pc)
+            && site.enabled()
+            && self.should_stop_at_site(&site)?
+        {
+            self.trap_type = Some(TrapType::SoftwareBreak);
+            return Ok(true);
+        }
+        Ok(false)
     }
 }
 
@@ -262,7 +698,16 @@ impl Drop for Process {
             if self.state.is_running() {
                 let _ = kill(self.pid, Signal::SIGSTOP);
                 let _ = waitpid(self.pid, None);
+                self.state = ProcessState::Stopped { signal: Some(Signal::SIGSTOP) };
+            }
+            let _ = self.finish_coverage();
+
+            // If the process is going to keep running after we're gone, don't leave it with pages
+            // it can't write to or debug registers that will kill it with a SIGTRAP
+            if !self.terminate_on_end {
+                let _ = self.disarm_all_watchpoints();
             }
+            let _ = self.registers.flush();
         }
 
         let _ = ptrace::detach(self.pid, None);
@@ -0,0 +1,192 @@
+use std::collections::VecDeque;
+
+use iced_x86::{
+    InstructionInfoFactory,
+    OpAccess,
+    Register,
+};
+use libc::{
+    user,
+    user_regs_struct,
+};
+
+use super::{
+    Process,
+    ProcessState,
+};
+use crate::address::VirtAddr;
+use crate::disassembly::Disassembler;
+use crate::{
+    DrbugError,
+    DrbugResult,
+};
+
+pub const DEFAULT_RECORD_LIMIT: usize = 200_000;
+
+// Everything we need to undo one instruction: the old value of every register word it changed
+// (in the user area and in the XSAVE area, where the upper halves of the vector registers live),
+// and the old contents of every piece of memory it wrote to
+#[derive(Debug)]
+pub(super) struct RecordedStep {
+    registers: Vec<(usize, u64)>,
+    xstate: Vec<(usize, u64)>,
+    memory: Vec<(VirtAddr, Vec<u8>)>,
+}
+
+// A step that's in progress, along with the register values from before it started
+#[derive(Debug)]
+pub(super) struct PendingStep {
+    before: user,
+    xstate_before: Vec<u8>,
+    step: RecordedStep,
+}
+
+// While we're recording, the process runs one instruction at a time (`resume` watch-steps instead
+// of continuing, so everything that runs the process gets recorded), and we keep the most recent
+// `limit` steps around so that we can go backwards through them.  This only knows about what the
+// instructions themselves do to the registers (all of them, vector registers included) and to
+// memory: memory written by the kernel during a syscall isn't recorded, and neither is anything
+// the user changes by hand while the process is stopped.
+#[derive(Debug)]
+pub(super) struct History {
+    steps: VecDeque<RecordedStep>,
+    limit: usize,
+}
+
+impl Process {
+    pub fn is_recording(&self) -> bool {
+        self.history.is_some()
+    }
+
+    pub fn recorded_steps(&self) -> usize {
+        self.history.as_ref().map_or(0, |h| h.steps.len())
+    }
+
+    // Undoes recorded instructions until we get back to a breakpoint, or run out of history
+    pub fn reverse_continue(&mut self) -> DrbugResult<ProcessState> {
+        while self.recorded_steps() > 0 {
+            self.reverse_step_instruction()?;
+            let pc = self.get_pc()?;
+            if self.breakpoint_sites.breakable_enabled_at(&pc) {
+                break;
+            }
+        }
+        Ok(self.state)
+    }
+
+    pub fn reverse_step_instruction(&mut self) -> DrbugResult<ProcessState> {
+        let Some(step) = self.history.as_mut().and_then(|h| h.steps.pop_back()) else {
+            return Err(DrbugError::NoRecordedHistory);
+        };
+
+        // Memory first, so the register values we put back don't get clobbered by anything
+        for (addr, data) in step.memory.iter().rev() {
+            self.write_memory(*addr, data)?;
+        }
+        self.registers.restore_words(&step.registers);
+        self.registers.restore_xstate_words(&step.xstate);
+
+        // Whatever stopped the process last time doesn't apply anymore
+        self.trap_type = None;
+        self.clear_triggered_breakpoint()?;
+        self.triggered_page_watchpoint = None;
+        self.triggered_software_watchpoint = None;
+        self.triggered_watchpoint = None;
+        Ok(self.state)
+    }
+
+    pub fn start_recording(&mut self, limit: usize) {
+        self.history = Some(History { steps: VecDeque::new(), limit });
+    }
+
+    pub fn stop_recording(&mut self) {
+        self.history = None;
+    }
+
+    // Called right before an instruction runs, by `step_instruction` or while watch-stepping
+    pub(super) fn begin_recorded_step(&mut self) -> DrbugResult<Option<PendingStep>> {
+        if self.history.is_none() {
+            return Ok(None);
+        }
+
+        let before = self.registers.snapshot();
+        let mut memory = vec![];
+        for (addr, size) in self.memory_writes(&before.regs)? {
+            // If we can't read it, the instruction is going to fault instead of writing to it
+            if let Ok(data) = self.read_memory(addr, size) {
+                memory.push((addr, data));
+            }
+        }
+        Ok(Some(PendingStep {
+            before,
+            xstate_before: self.registers.xstate_snapshot(),
+            step: RecordedStep { registers: vec![], xstate: vec![], memory },
+        }))
+    }
+
+    // ...and right after it's done
+    pub(super) fn end_recorded_step(&mut self, pending: Option<PendingStep>) {
+        let (Some(pending), Some(history)) = (pending, self.history.as_mut()) else {
+            return;
+        };
+        let mut step = pending.step;
+        step.registers = self.registers.changed_words(&pending.before);
+        step.xstate = self.registers.changed_xstate_words(&pending.xstate_before);
+        if history.steps.len() == history.limit {
+            history.steps.pop_front();
+        }
+        history.steps.push_back(step);
+    }
+
+    // The memory that the instruction at the pc is going to write to, worked out by decoding its
+    // memory operands (including implicit ones, like the stack slot that a `push` or `call` writes)
+    fn memory_writes(&mut self, regs: &user_regs_struct) -> DrbugResult<Vec<(VirtAddr, usize)>> {
+        let instructions = Disassembler::new(self).disassemble(None, 1)?;
+        let Some(instr) = instructions.first() else {
+            return Ok(vec![]);
+        };
+
+        let mut factory = InstructionInfoFactory::new();
+        let info = factory.info(instr);
+        let writes = info.used_memory().iter().filter(|mem| {
+            matches!(
+                mem.access(),
+                OpAccess::Write | OpAccess::CondWrite | OpAccess::ReadWrite | OpAccess::ReadCondWrite
+            )
+        });
+        Ok(writes
+            .filter_map(|mem| {
+                let addr = mem.virtual_address(0, |reg, _, _| register_value(regs, reg))?;
+                Some((VirtAddr(addr), mem.memory_size().size())).filter(|(_, size)| *size > 0)
+            })
+            .collect())
+    }
+}
+
+fn register_value(regs: &user_regs_struct, reg: Register) -> Option<u64> {
+    let val = match reg.full_register() {
+        Register::RAX => regs.rax,
+        Register::RBX => regs.rbx,
+        Register::RCX => regs.rcx,
+        Register::RDX => regs.rdx,
+        Register::RSI => regs.rsi,
+        Register::RDI => regs.rdi,
+        Register::RBP => regs.rbp,
+        Register::RSP => regs.rsp,
+        Register::R8 => regs.r8,
+        Register::R9 => regs.r9,
+        Register::R10 => regs.r10,
+        Register::R11 => regs.r11,
+        Register::R12 => regs.r12,
+        Register::R13 => regs.r13,
+        Register::R14 => regs.r14,
+        Register::R15 => regs.r15,
+        Register::FS => regs.fs_base,
+        Register::GS => regs.gs_base,
+        // The other segments are flat in 64-bit mode, and the displacement of a RIP-relative
+        // operand is already the absolute address
+        Register::ES | Register::CS | Register::SS | Register::DS | Register::RIP => 0,
+        _ => return None, // e.g., the vector index register of a gather
+    };
+    Some(if reg.is_gpr32() { val as u32 as u64 } else { val })
+}
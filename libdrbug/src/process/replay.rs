@@ -0,0 +1,224 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::File;
+use std::io::Write;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use libc::{
+    SYS_clock_gettime,
+    SYS_getrandom,
+    SYS_gettimeofday,
+    SYS_read,
+    SYS_recvfrom,
+    SYS_time,
+    user_regs_struct,
+};
+use nix::sys::ptrace;
+
+use super::Process;
+use crate::address::VirtAddr;
+use crate::register::info::{
+    RegisterId,
+    register_info_by_id,
+};
+use crate::register::value::RegisterValue;
+use crate::{
+    DrbugError,
+    DrbugResult,
+    Empty,
+    syscall_error,
+};
+
+const NONDETERMINISTIC_SYSCALLS: [i64; 6] =
+    [SYS_clock_gettime, SYS_getrandom, SYS_gettimeofday, SYS_read, SYS_recvfrom, SYS_time];
+
+// What to do about syscalls whose results can change from one run to the next.  When recording,
+// we write the result of each one (and whatever it wrote into the process's memory) to a file; when
+// replaying, the syscalls don't actually run, and the process gets the recorded results instead,
+// in the same order.  Calls that go through the vDSO (which is most calls to `clock_gettime` and
+// `gettimeofday`) never make it to the kernel, so there's nothing for us to catch.
+#[derive(Clone, Debug, Default)]
+pub enum SyscallMode {
+    #[default]
+    Normal,
+    Record(PathBuf),
+    Replay(PathBuf),
+}
+
+#[derive(Debug)]
+pub(super) enum SyscallLog {
+    Record(File),
+    Replay {
+        records: VecDeque<SyscallRecord>,
+        current: Option<(SyscallRecord, Vec<VirtAddr>)>,
+    },
+}
+
+// One line of the log file: the syscall number, its result, and then the contents (in hex) of
+// every buffer it filled in, in the order that `output_buffers` lists them.  The addresses aren't
+// saved, since with ASLR the buffers are somewhere else every run; when replaying, we work them
+// out again from the arguments of the call we're standing in for.
+#[derive(Debug, Eq, PartialEq)]
+pub(super) struct SyscallRecord {
+    number: i64,
+    result: i64,
+    buffers: Vec<Vec<u8>>,
+}
+
+impl FromStr for SyscallRecord {
+    type Err = DrbugError;
+
+    fn from_str(s: &str) -> DrbugResult<Self> {
+        let mut fields = s.split_whitespace();
+        let (Some(number), Some(result)) = (fields.next(), fields.next()) else {
+            return Err(DrbugError::InvalidSyscallLog(s.into()));
+        };
+
+        let mut buffers = vec![];
+        for data in fields {
+            if data.len() % 2 != 0 {
+                return Err(DrbugError::InvalidSyscallLog(s.into()));
+            }
+            let bytes: Result<Vec<_>, _> = (0..data.len())
+                .step_by(2)
+                .map(|i| u8::from_str_radix(&data[i..i + 2], 16))
+                .collect();
+            buffers.push(bytes.map_err(|_| DrbugError::InvalidSyscallLog(s.into()))?);
+        }
+        Ok(SyscallRecord {
+            number: number.parse()?,
+            result: result.parse()?,
+            buffers,
+        })
+    }
+}
+
+impl fmt::Display for SyscallRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.number, self.result)?;
+        for data in &self.buffers {
+            let hex: String = data.iter().map(|b| format!("{b:02x}")).collect();
+            write!(f, " {hex}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Process {
+    // Syscall stops come in pairs, one when the syscall starts and one when it returns, and
+    // there's nothing in the stop itself to tell them apart, so we just keep track
+    pub(super) fn handle_syscall_stop(&mut self) -> Empty {
+        self.in_syscall = !self.in_syscall;
+        let regs = self.registers.snapshot().regs;
+        let tracked = NONDETERMINISTIC_SYSCALLS.contains(&(regs.orig_rax as i64));
+        match (&self.syscall_log, self.in_syscall) {
+            (Some(SyscallLog::Record(_)), false) if tracked => self.record_syscall(&regs),
+            (Some(SyscallLog::Replay { .. }), true) if tracked => self.begin_replayed_syscall(&regs),
+            (Some(SyscallLog::Replay { .. }), false) => self.end_replayed_syscall(),
+            _ => Ok(()),
+        }
+    }
+
+    pub(super) fn start_syscall_log(&mut self, mode: &SyscallMode) -> Empty {
+        self.in_syscall = false;
+        self.syscall_log = match mode {
+            SyscallMode::Normal => None,
+            SyscallMode::Record(path) => Some(SyscallLog::Record(File::create(path)?)),
+            SyscallMode::Replay(path) => {
+                let log = std::fs::read_to_string(path)?;
+                let records = log.lines().map(|line| line.parse()).collect::<DrbugResult<_>>()?;
+                Some(SyscallLog::Replay { records, current: None })
+            },
+        };
+
+        // With this option set, syscall stops show up as SIGTRAP | 0x80, so that we can tell them
+        // apart from breakpoints and single steps
+        if self.syscall_log.is_some() {
+            syscall_error!(ptrace::setoptions(self.pid, ptrace::Options::PTRACE_O_TRACESYSGOOD))?;
+        }
+        Ok(())
+    }
+
+    // The kernel skips syscall number -1 (it just returns -ENOSYS), so when we're replaying, the
+    // real syscall never happens; we fill in the recorded results when it "returns".  Where they
+    // go depends on the arguments, which we can only trust at this point, before the kernel has
+    // had a chance to touch any registers.
+    fn begin_replayed_syscall(&mut self, regs: &user_regs_struct) -> Empty {
+        let Some(SyscallLog::Replay { records, .. }) = &mut self.syscall_log else {
+            return Ok(());
+        };
+        let Some(record) = records.pop_front() else {
+            return Ok(()); // we've run out of recording, so it's back to real syscalls
+        };
+        if record.number != regs.orig_rax as i64 {
+            return Err(DrbugError::SyscallReplayMismatch(record.number, regs.orig_rax as i64));
+        }
+
+        let addrs = self.output_buffers(record.number, regs, record.result)?;
+        let addrs = addrs.into_iter().map(|(addr, _)| addr).collect();
+        if let Some(SyscallLog::Replay { current, .. }) = &mut self.syscall_log {
+            *current = Some((record, addrs));
+        }
+
+        let orig_rax_info = register_info_by_id(&RegisterId::orig_rax);
+        self.registers.write(orig_rax_info, RegisterValue::U64(u64::MAX))
+    }
+
+    fn end_replayed_syscall(&mut self) -> Empty {
+        let Some(SyscallLog::Replay { current, .. }) = &mut self.syscall_log else {
+            return Ok(());
+        };
+        let Some((record, addrs)) = current.take() else {
+            return Ok(());
+        };
+
+        // The sizes are the ones from the recording, since (e.g.) the length of the address that
+        // `recvfrom` filled in isn't known until it returns
+        for (addr, data) in addrs.iter().zip(&record.buffers) {
+            self.write_memory(*addr, data)?;
+        }
+        let rax_info = register_info_by_id(&RegisterId::rax);
+        self.registers.write(rax_info, RegisterValue::U64(record.result as u64))
+    }
+
+    fn record_syscall(&mut self, regs: &user_regs_struct) -> Empty {
+        let number = regs.orig_rax as i64;
+        let result = regs.rax as i64;
+        let mut record = SyscallRecord { number, result, buffers: vec![] };
+        for (addr, size) in self.output_buffers(number, regs, result)? {
+            record.buffers.push(self.read_memory(addr, size)?);
+        }
+
+        if let Some(SyscallLog::Record(file)) = &mut self.syscall_log {
+            writeln!(file, "{record}")?;
+        }
+        Ok(())
+    }
+
+    // The memory that a syscall filled in, given its arguments (in rdi, rsi, rdx, r10, r8, r9) and
+    // its result
+    fn output_buffers(&self, number: i64, regs: &user_regs_struct, result: i64) -> DrbugResult<Vec<(VirtAddr, usize)>> {
+        let written = if result > 0 { result as usize } else { 0 };
+        let buffers = match number {
+            SYS_read => vec![(regs.rsi, written)],
+            SYS_recvfrom if regs.r8 != 0 && regs.r9 != 0 && result >= 0 => {
+                // The sender's address only gets filled in if the caller asked for it, and the
+                // kernel tells us how much of it there is by updating the length
+                let addrlen = u32::from_le_bytes(self.read_memory(VirtAddr(regs.r9), 4)?[..4].try_into()?);
+                vec![(regs.rsi, written), (regs.r9, size_of::<u32>()), (regs.r8, addrlen as usize)]
+            },
+            SYS_recvfrom => vec![(regs.rsi, written)],
+            SYS_clock_gettime if result == 0 => vec![(regs.rsi, size_of::<libc::timespec>())],
+            SYS_gettimeofday if result == 0 && regs.rdi != 0 => vec![(regs.rdi, size_of::<libc::timeval>())],
+            SYS_time if regs.rdi != 0 => vec![(regs.rdi, size_of::<libc::time_t>())],
+            SYS_getrandom => vec![(regs.rdi, written)],
+            _ => vec![],
+        };
+        Ok(buffers
+            .into_iter()
+            .filter(|(_, size)| *size > 0)
+            .map(|(addr, size)| (VirtAddr(addr), size))
+            .collect())
+    }
+}
@@ -3,6 +3,12 @@ use std::fmt;
 use nix::sys::signal::Signal;
 use nix::sys::wait::WaitStatus;
 
+// si_code values for SIGTRAP, from <asm-generic/siginfo.h>; int3 doesn't get a TRAP_* code of its
+// own, the kernel just reports it as SI_KERNEL
+const TRAP_TRACE: i32 = 2;
+const TRAP_HWBKPT: i32 = 4;
+const SI_KERNEL: i32 = 0x80;
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum ProcessState {
     Exited { exit_code: i32 },
@@ -34,12 +40,33 @@ impl ProcessState {
     }
 }
 
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TrapType {
+    HardwareBreak,
+    SingleStep,
+    SoftwareBreak,
+    Syscall,
+    Unknown,
+}
+
+impl TrapType {
+    pub(crate) fn from_si_code(si_code: i32) -> Self {
+        match si_code {
+            TRAP_TRACE => TrapType::SingleStep,
+            TRAP_HWBKPT => TrapType::HardwareBreak,
+            SI_KERNEL => TrapType::SoftwareBreak,
+            _ => TrapType::Unknown,
+        }
+    }
+}
+
 impl From<WaitStatus> for ProcessState {
     fn from(ws: WaitStatus) -> Self {
         match ws {
             WaitStatus::Exited(_, code) => ProcessState::Exited { exit_code: code },
             WaitStatus::Signaled(_, signal, _) => ProcessState::Terminated { signal },
             WaitStatus::Stopped(_, signal) => ProcessState::Stopped { signal: Some(signal) },
+            WaitStatus::PtraceSyscall(_) => ProcessState::Stopped { signal: Some(Signal::SIGTRAP) },
             _ => ProcessState::Unknown(ws),
         }
     }
@@ -0,0 +1,151 @@
+use std::collections::BTreeSet;
+use std::path::Path;
+use std::rc::Rc;
+
+use libc::AT_ENTRY;
+
+use super::Process;
+use crate::address::VirtAddr;
+use crate::breakpoint::BreakpointLocation;
+use crate::elf::{
+    Elf,
+    Symbol,
+    SymbolType,
+};
+use crate::{
+    DrbugError,
+    DrbugResult,
+};
+
+// A module is an ELF file (the executable itself, or a shared library) that's mapped into the
+// process; the load bias is how far the loader moved it from the addresses in the file
+#[derive(Clone, Debug)]
+pub struct Module {
+    pub path: String,
+    pub load_bias: u64,
+    pub elf: Rc<Elf>,
+}
+
+impl Module {
+    // Modules can be referred to by their full path or just the file name
+    pub fn matches_path(&self, name: &str) -> bool {
+        self.path == name || Path::new(&self.path).file_name().is_some_and(|f| *f == *name)
+    }
+
+    pub fn symbol_addr(&self, sym: &Symbol) -> VirtAddr {
+        VirtAddr(sym.addr.0.wrapping_add(self.load_bias))
+    }
+}
+
+impl Process {
+    // The kernel passes the entry point to the process in its auxiliary vector (a list of
+    // (key, value) pairs that it puts on the stack); unlike the entry point in the ELF header, this
+    // one already has the load bias added in
+    pub fn entry_point(&self) -> DrbugResult<VirtAddr> {
+        let auxv = std::fs::read(format!("/proc/{}/auxv", self.pid))?;
+        for entry in auxv.chunks_exact(16) {
+            if u64::from_le_bytes(entry[..8].try_into()?) == AT_ENTRY {
+                return Ok(VirtAddr(u64::from_le_bytes(entry[8..].try_into()?)));
+            }
+        }
+        Err(DrbugError::AuxvEntryNotFound("AT_ENTRY"))
+    }
+
+    // Libraries can have a `main` symbol too, so we don't go looking anywhere but the executable
+    pub fn main_addr(&self) -> DrbugResult<VirtAddr> {
+        let exe = self.executable_module()?;
+        exe.and_then(|m| {
+            let mut symbols = m.elf.symbols_by_name("main");
+            symbols
+                .find(|sym| sym.type_ == SymbolType::Function)
+                .map(|sym| m.symbol_addr(sym))
+        })
+        .ok_or(DrbugError::SymbolNotFound("main".into()))
+    }
+
+    // The executable is whichever module the entry point belongs to
+    pub fn executable_module(&self) -> DrbugResult<Option<Module>> {
+        let entry = self.entry_point()?;
+        let modules = self.modules()?;
+        Ok(modules
+            .into_iter()
+            .find(|m| m.elf.entry().0.wrapping_add(m.load_bias) == entry.0))
+    }
+
+    // The first mapping of each file (the one at file offset 0) tells us where it got loaded
+    pub fn modules(&self) -> DrbugResult<Vec<Module>> {
+        let mut modules: Vec<Module> = vec![];
+        for map in self.memory_maps()? {
+            let Some(path) = map.path.filter(|p| p.starts_with('/')) else {
+                continue;
+            };
+            if map.offset != 0 || modules.iter().any(|m| m.path == path) {
+                continue;
+            }
+            if let Some(elf) = self.load_elf(&path) {
+                let load_bias = map.start.0.wrapping_sub(elf.base_addr().0);
+                modules.push(Module { path, load_bias, elf });
+            }
+        }
+        Ok(modules)
+    }
+
+    // Turns an address into a module offset, if the address is inside a module; this is what you
+    // want to save if you need the location to work in a different run of the program
+    pub fn module_location(&self, addr: VirtAddr) -> DrbugResult<Option<BreakpointLocation>> {
+        let maps = self.memory_maps()?;
+        let Some(path) = maps.iter().find(|map| map.contains(addr)).and_then(|map| map.path.as_ref()) else {
+            return Ok(None);
+        };
+        let module = self.modules()?.into_iter().find(|m| m.path == *path);
+        Ok(module.map(|m| BreakpointLocation::ModuleOffset {
+            module: m.path,
+            offset: addr.0.wrapping_sub(m.load_bias),
+        }))
+    }
+
+    // The start of the function that `addr` is in, according to the symbol table of its module
+    pub(super) fn function_start(&self, addr: VirtAddr) -> DrbugResult<Option<VirtAddr>> {
+        let maps = self.memory_maps()?;
+        let Some(path) = maps.iter().find(|map| map.contains(addr)).and_then(|map| map.path.as_ref()) else {
+            return Ok(None);
+        };
+        let Some(module) = self.modules()?.into_iter().find(|m| m.path == *path) else {
+            return Ok(None);
+        };
+        let sym = module.elf.symbol_containing(VirtAddr(addr.0.wrapping_sub(module.load_bias)));
+        Ok(sym
+            .filter(|sym| sym.type_ == SymbolType::Function)
+            .map(|sym| module.symbol_addr(sym)))
+    }
+
+    pub(super) fn resolve_location(&self, location: &BreakpointLocation) -> DrbugResult<Vec<VirtAddr>> {
+        match location {
+            BreakpointLocation::Address(addr) => return Ok(vec![*addr]),
+            BreakpointLocation::ModuleOffset { module, offset } => {
+                let modules = self.modules()?.into_iter().filter(|m| m.matches_path(module));
+                return Ok(modules.map(|m| VirtAddr(offset.wrapping_add(m.load_bias))).collect());
+            },
+            BreakpointLocation::Function(_) | BreakpointLocation::Pattern(_) => (),
+        }
+
+        // Symbol tables often have more than one name for the same function, so dedup by address
+        let mut addrs = BTreeSet::new();
+        for module in self.modules()? {
+            let symbols = module.elf.symbols().iter().filter(|sym| location.matches(sym));
+            addrs.extend(symbols.map(|sym| module.symbol_addr(sym)));
+        }
+        Ok(addrs.into_iter().collect())
+    }
+
+    // Parsing is expensive, so we hang on to every ELF file we've looked at; files that aren't ELF
+    // files at all (lots of things get mmapped) are remembered too, so we don't keep re-reading them
+    fn load_elf(&self, path: &str) -> Option<Rc<Elf>> {
+        if let Some(elf) = self.elf_cache.borrow().get(path) {
+            return elf.clone();
+        }
+        let elf = Elf::open(Path::new(path)).ok().map(Rc::new);
+        self.elf_cache.borrow_mut().insert(path.into(), elf.clone());
+        elf
+    }
+}
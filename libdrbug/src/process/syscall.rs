@@ -0,0 +1,70 @@
+use libc::user_regs_struct;
+use nix::sys::ptrace;
+use nix::sys::signal::Signal;
+use nix::sys::wait::{
+    WaitStatus,
+    waitpid,
+};
+
+use super::Process;
+use crate::address::VirtAddr;
+use crate::{
+    DrbugError,
+    DrbugResult,
+    syscall_error,
+};
+
+pub(super) const SYSCALL_INSTR: [u8; 2] = [0x0f, 0x05];
+const MAX_SYSCALL_ARGS: usize = 6;
+
+impl Process {
+    // Make the inferior run a syscall on our behalf: we temporarily replace the instruction at the
+    // current pc with `syscall`, load up the registers according to the x86_64 syscall calling
+    // convention, step over it, and then put everything back the way we found it.  The return
+    // value is the raw value of rax, so errors come back as -errno.
+    pub fn inject_syscall(&mut self, number: i64, args: &[u64]) -> DrbugResult<i64> {
+        if args.len() > MAX_SYSCALL_ARGS {
+            return Err(DrbugError::TooManySyscallArgs(args.len()));
+        }
+
+        // We don't go through self.registers here, because we want the cached values to be
+        // exactly the same when we're done as they were when we started; anything that's been
+        // written to the cache has to go out first, though, or we'd save (and restore) the old values
+        self.registers.flush()?;
+        let saved_regs = syscall_error!(ptrace::getregs(self.pid))?;
+        let pc = VirtAddr(saved_regs.rip);
+        let saved_code = self.read_memory(pc, SYSCALL_INSTR.len())?;
+        self.write_memory(pc, &SYSCALL_INSTR)?;
+
+        let mut regs = saved_regs;
+        regs.rax = number as u64;
+        regs.orig_rax = u64::MAX; // -1, so the kernel doesn't try to "restart" an interrupted syscall
+        let arg_regs = [&mut regs.rdi, &mut regs.rsi, &mut regs.rdx, &mut regs.r10, &mut regs.r8, &mut regs.r9];
+        for (reg, arg) in arg_regs.into_iter().zip(args) {
+            *reg = *arg;
+        }
+
+        let result = self.step_injected_syscall(regs);
+
+        // Put the code and registers back however the syscall went.  If it failed because the
+        // process is gone, this will fail too, but it's the first error that says what happened.
+        let restored = self
+            .write_memory(pc, &saved_code)
+            .and_then(|_| syscall_error!(ptrace::setregs(self.pid, saved_regs)));
+        let result = result?;
+        restored?;
+        Ok(result)
+    }
+
+    // Run the `syscall` instruction we've put at the pc, with the registers set up for it, and
+    // return whatever it left in rax
+    fn step_injected_syscall(&mut self, regs: user_regs_struct) -> DrbugResult<i64> {
+        syscall_error!(ptrace::setregs(self.pid, regs))?;
+        syscall_error!(ptrace::step(self.pid, None))?;
+        let status = syscall_error!(waitpid(self.pid, None))?;
+        if !matches!(status, WaitStatus::Stopped(_, Signal::SIGTRAP)) {
+            return Err(DrbugError::SyscallInjectionFailed(status));
+        }
+        Ok(syscall_error!(ptrace::getregs(self.pid))?.rax as i64)
+    }
+}
@@ -0,0 +1,234 @@
+use std::fmt;
+use std::io::{
+    Read,
+    Write,
+};
+use std::str::FromStr;
+
+use iced_x86::{
+    Decoder,
+    DecoderOptions,
+    Formatter,
+    GasFormatter,
+    Instruction,
+};
+
+use super::{
+    Process,
+    ProcessState,
+};
+use crate::address::VirtAddr;
+use crate::disassembly::{
+    BITNESS,
+    Disassembler,
+};
+use crate::register::decode_value;
+use crate::register::info::{
+    REGISTER_INFOS,
+    RegisterId,
+    register_info_by_id,
+};
+use crate::register::value::RegisterValue;
+use crate::{
+    Byte512,
+    DrbugError,
+    DrbugResult,
+    Empty,
+};
+
+// Binary traces start with this, followed by a version byte
+const TRACE_MAGIC: &[u8; 8] = b"DRBTRACE";
+const TRACE_VERSION: u8 = 1;
+
+// Traces are for diffing one run against another, so the text format is one line per instruction,
+// with nothing on it that changes between otherwise-identical runs except for what the program
+// itself did.  The binary format has the same information, packed tighter:
+//
+//   entry    := address (u64) | length (u8) | instruction bytes | count (u8) | register*
+//   register := index into REGISTER_INFOS (u16) | value (however big the register is)
+//
+// with everything little-endian.  The register indexes are only stable for a given version of the
+// debugger, which is fine for comparing two traces taken with it.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum TraceFormat {
+    Binary,
+    #[default]
+    Text,
+}
+
+impl FromStr for TraceFormat {
+    type Err = DrbugError;
+
+    fn from_str(s: &str) -> DrbugResult<Self> {
+        match s {
+            "b" | "bin" | "binary" => Ok(TraceFormat::Binary),
+            "t" | "txt" | "text" => Ok(TraceFormat::Text),
+            _ => Err(DrbugError::InvalidTraceFormat(s.into())),
+        }
+    }
+}
+
+impl fmt::Display for TraceFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TraceFormat::Binary => write!(f, "binary"),
+            TraceFormat::Text => write!(f, "text"),
+        }
+    }
+}
+
+// Where to stop tracing: either after some number of instructions, or when we're about to run the
+// instruction at an address (which doesn't get traced)
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TraceEnd {
+    Address(VirtAddr),
+    Count(usize),
+}
+
+// One executed instruction, along with the new values of the registers it changed
+#[derive(Clone, Debug)]
+pub struct TraceEntry {
+    pub instruction: Instruction,
+    pub bytes: Vec<u8>,
+    pub changed: Vec<(RegisterId, RegisterValue)>,
+}
+
+impl TraceEntry {
+    pub fn addr(&self) -> VirtAddr {
+        VirtAddr(self.instruction.ip())
+    }
+}
+
+pub struct TraceWriter<W: Write> {
+    out: W,
+    format: TraceFormat,
+    formatter: GasFormatter,
+    line: String,
+}
+
+impl<W: Write> TraceWriter<W> {
+    pub fn new(mut out: W, format: TraceFormat) -> DrbugResult<Self> {
+        if format == TraceFormat::Binary {
+            out.write_all(TRACE_MAGIC)?;
+            out.write_all(&[TRACE_VERSION])?;
+        }
+
+        let mut formatter = GasFormatter::new();
+        formatter.options_mut().set_uppercase_hex(false);
+        Ok(TraceWriter { out, format, formatter, line: String::new() })
+    }
+
+    pub fn write(&mut self, entry: &TraceEntry) -> Empty {
+        match self.format {
+            TraceFormat::Binary => self.write_binary(entry),
+            TraceFormat::Text => self.write_text(entry),
+        }
+    }
+
+    pub fn finish(mut self) -> DrbugResult<W> {
+        self.out.flush()?;
+        Ok(self.out)
+    }
+
+    fn write_binary(&mut self, entry: &TraceEntry) -> Empty {
+        self.out.write_all(&entry.addr().0.to_le_bytes())?;
+        self.out.write_all(&[entry.bytes.len() as u8])?;
+        self.out.write_all(&entry.bytes)?;
+        self.out.write_all(&[entry.changed.len() as u8])?;
+        for (id, val) in &entry.changed {
+            let index = REGISTER_INFOS.iter().position(|info| info.id == *id).unwrap() as u16;
+            let size = register_info_by_id(id).size;
+            self.out.write_all(&index.to_le_bytes())?;
+            self.out.write_all(&Byte512::from(val)[..size])?;
+        }
+        Ok(())
+    }
+
+    fn write_text(&mut self, entry: &TraceEntry) -> Empty {
+        self.line.clear();
+        self.formatter.format(&entry.instruction, &mut self.line);
+        write!(self.out, "{:#018x}: {:<40}", entry.addr().0, self.line)?;
+        for (id, val) in &entry.changed {
+            write!(self.out, " {}={val}", register_info_by_id(id).name)?;
+        }
+        writeln!(self.out)?;
+        Ok(())
+    }
+}
+
+// Reads back a trace written in the binary format
+pub fn read_trace(mut input: impl Read) -> DrbugResult<Vec<TraceEntry>> {
+    let mut data = vec![];
+    input.read_to_end(&mut data)?;
+    let mut reader = ByteReader { data: &data, pos: 0 };
+
+    if reader.take(TRACE_MAGIC.len())? != TRACE_MAGIC || reader.take(1)? != [TRACE_VERSION] {
+        return Err(DrbugError::InvalidTrace("not a drbug trace, or from a different version".into()));
+    }
+
+    let mut entries = vec![];
+    while reader.pos < data.len() {
+        let addr = u64::from_le_bytes(reader.take(8)?.try_into()?);
+        let len = reader.take(1)?[0] as usize;
+        let bytes = reader.take(len)?.to_vec();
+        let instruction = Decoder::with_ip(BITNESS, &bytes, addr, DecoderOptions::NONE).decode();
+
+        let count = reader.take(1)?[0];
+        let mut changed = vec![];
+        for _ in 0..count {
+            let index = u16::from_le_bytes(reader.take(2)?.try_into()?) as usize;
+            let Some(info) = REGISTER_INFOS.get(index) else {
+                return Err(DrbugError::InvalidTrace(format!("unknown register index {index}")));
+            };
+            changed.push((info.id, decode_value(info, reader.take(info.size)?)?));
+        }
+        entries.push(TraceEntry { instruction, bytes, changed });
+    }
+    Ok(entries)
+}
+
+struct ByteReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    fn take(&mut self, n: usize) -> DrbugResult<&'a [u8]> {
+        let Some(bytes) = self.data.get(self.pos..self.pos + n) else {
+            return Err(DrbugError::InvalidTrace(format!("truncated at byte {}", self.pos)));
+        };
+        self.pos += n;
+        Ok(bytes)
+    }
+}
+
+impl Process {
+    // Single-steps until we reach the end of the trace, writing out each instruction as we go.
+    // Anything that would stop a watch-step (breakpoints, watchpoints, signals) ends the trace
+    // early, as does the process exiting.
+    pub fn trace<W: Write>(&mut self, end: TraceEnd, writer: &mut TraceWriter<W>) -> DrbugResult<ProcessState> {
+        let mut count = 0;
+        while self.state.is_stopped() {
+            let pc = self.get_pc()?;
+            if end == TraceEnd::Count(count) || end == TraceEnd::Address(pc) {
+                break;
+            }
+
+            let Some(instruction) = Disassembler::new(self).disassemble(None, 1)?.first().copied() else {
+                break;
+            };
+            let bytes = self.read_memory_without_traps(pc, instruction.len())?;
+            let before = self.registers.snapshot();
+
+            let state = self.step_instruction()?;
+            let changed = if state.is_stopped() { self.registers.changed_registers(&before)? } else { vec![] };
+            writer.write(&TraceEntry { instruction, bytes, changed })?;
+            count += 1;
+
+            if state.is_stopped() && self.watch_step_should_stop()? {
+                break;
+            }
+        }
+        Ok(self.state)
+    }
+}
@@ -0,0 +1,136 @@
+use iced_x86::{
+    Decoder,
+    DecoderOptions,
+    Instruction,
+    Mnemonic,
+    OpKind,
+    Register,
+};
+
+use super::Process;
+use crate::DrbugResult;
+use crate::address::VirtAddr;
+use crate::disassembly::{
+    BITNESS,
+    Disassembler,
+};
+use crate::register::info::{
+    RegisterId,
+    register_info_by_id,
+};
+use crate::register::value::RegisterValue;
+
+const MAX_PROLOGUE_INSTRUCTIONS: usize = 16;
+
+impl Process {
+    // Returns where the current function is going to return to, along with the stack pointer right
+    // after it does (which is also the stack pointer right before the call, a.k.a. the canonical
+    // frame address).  We don't have any call frame info to go on, so we work it out from the
+    // function's prologue (or its epilogue, if it's already on the way out); if we can't figure out
+    // which function we're in, we fall back to the frame pointer chain, which works as long as the
+    // code was built with frame pointers.
+    pub fn return_frame(&mut self) -> DrbugResult<(VirtAddr, VirtAddr)> {
+        let pc = self.get_pc()?;
+        let start = self.function_start(pc)?;
+        let cfa = if self.in_epilogue(start, pc)? {
+            self.epilogue_cfa()?
+        } else if let Some(start) = start {
+            self.prologue_cfa(start, pc)?
+        } else {
+            self.get_rbp()?.add(16)
+        };
+
+        let data = self.read_memory(VirtAddr(cfa.0 - 8), 8)?;
+        Ok((VirtAddr(u64::from_le_bytes(data[..8].try_into()?)), cfa))
+    }
+
+    fn get_rbp(&self) -> DrbugResult<VirtAddr> {
+        let rbp_info = register_info_by_id(&RegisterId::rbp);
+        self.registers.read(rbp_info).map(|v| match v {
+            RegisterValue::U64(rbp) => VirtAddr(rbp),
+            _ => panic!("should never happen"),
+        })
+    }
+
+    // Replays the stack adjustments that the prologue has made so far (the pushes and the `sub rsp`
+    // that makes room for locals); once it's set up a frame pointer, that's more reliable, since the
+    // function body might move the stack pointer around (e.g., with `alloca`).  The `endbr64` that
+    // CET builds (i.e., `-fcf-protection`, which most distros turn on) put at the start of every
+    // function doesn't touch the stack, and neither does any padding.
+    fn prologue_cfa(&mut self, start: VirtAddr, pc: VirtAddr) -> DrbugResult<VirtAddr> {
+        let instructions = Disassembler::new(self).disassemble(Some(start), MAX_PROLOGUE_INSTRUCTIONS)?;
+        let mut pushed = 0;
+        let mut has_frame_pointer = false;
+        for instr in instructions.iter().take_while(|instr| instr.ip() < pc.0) {
+            match instr.mnemonic() {
+                Mnemonic::Endbr64 | Mnemonic::Nop => (),
+                Mnemonic::Push => pushed += 8,
+                Mnemonic::Sub if instr.op0_register() == Register::RSP && is_immediate(instr) => {
+                    pushed += instr.immediate(1)
+                },
+                Mnemonic::Mov if instr.op0_register() == Register::RBP && instr.op1_register() == Register::RSP => {
+                    has_frame_pointer = true
+                },
+                _ => break,
+            }
+        }
+
+        if has_frame_pointer {
+            return Ok(self.get_rbp()?.add(16));
+        }
+        Ok(self.get_sp()?.add(pushed as usize + 8))
+    }
+
+    // Once the epilogue has done a `leave` or `pop rbp`, rbp holds the caller's frame pointer, so it's
+    // no good to us until the `ret` (anything after the `ret` must get jumped to from the body, where
+    // rbp is still ours).  Functions without a frame pointer don't leave anything like that behind,
+    // so we look ahead as well: if there's nothing but pops and `add rsp`s between us and the `ret`,
+    // the body is done with the stack, even if we don't know where the function started.
+    fn in_epilogue(&mut self, start: Option<VirtAddr>, pc: VirtAddr) -> DrbugResult<bool> {
+        let instructions = Disassembler::new(self).disassemble(None, MAX_PROLOGUE_INSTRUCTIONS)?;
+        let ret = instructions.iter().position(|instr| instr.mnemonic() == Mnemonic::Ret);
+        if ret.is_some_and(|ret| instructions[..ret].iter().all(|instr| stack_adjustment(instr).is_some())) {
+            return Ok(true);
+        }
+        let Some(start) = start else {
+            return Ok(false);
+        };
+
+        let code = self.read_memory_without_traps(start, (pc.0 - start.0) as usize)?;
+        let mut in_epilogue = false;
+        for instr in Decoder::with_ip(BITNESS, &code, start.0, DecoderOptions::NONE) {
+            match instr.mnemonic() {
+                Mnemonic::Leave => in_epilogue = true,
+                Mnemonic::Pop if instr.op0_register() == Register::RBP => in_epilogue = true,
+                Mnemonic::Ret | Mnemonic::Jmp => in_epilogue = false,
+                _ => (),
+            }
+        }
+        Ok(in_epilogue)
+    }
+
+    // All that's left on the stack is whatever the epilogue still has to pop (or add back to rsp)
+    // before the `ret`, and then the return address
+    fn epilogue_cfa(&mut self) -> DrbugResult<VirtAddr> {
+        let instructions = Disassembler::new(self).disassemble(None, MAX_PROLOGUE_INSTRUCTIONS)?;
+        let adjustment: u64 = instructions
+            .iter()
+            .take_while(|instr| instr.mnemonic() != Mnemonic::Ret)
+            .filter_map(stack_adjustment)
+            .sum();
+        Ok(self.get_sp()?.add(adjustment as usize + 8))
+    }
+}
+
+// How many bytes an epilogue instruction takes off the stack, if it's one that does
+fn stack_adjustment(instr: &Instruction) -> Option<u64> {
+    match instr.mnemonic() {
+        Mnemonic::Pop => Some(8),
+        Mnemonic::Add if instr.op0_register() == Register::RSP && is_immediate(instr) => Some(instr.immediate(1)),
+        _ => None,
+    }
+}
+
+fn is_immediate(instr: &Instruction) -> bool {
+    matches!(instr.op1_kind(), OpKind::Immediate8to64 | OpKind::Immediate32to64 | OpKind::Immediate32)
+}
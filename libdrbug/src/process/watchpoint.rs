@@ -0,0 +1,316 @@
+use libc::PROT_WRITE;
+use nix::errno::Errno;
+use nix::sys::ptrace;
+use nix::sys::wait::waitpid;
+
+use super::Process;
+use crate::address::VirtAddr;
+use crate::breakpoint::{
+    BreakList,
+    Breakable,
+};
+use crate::register::info::{
+    RegisterId,
+    register_info_by_id,
+};
+use crate::register::value::RegisterValue;
+use crate::watchpoint::{
+    DEBUG_ADDR_REG_COUNT,
+    PAGE_SIZE,
+    PageWatchpoint,
+    SoftwareWatchpoint,
+    WatchMode,
+    WatchTarget,
+    Watchpoint,
+};
+use crate::{
+    Byte512,
+    DrbugError,
+    DrbugResult,
+    Empty,
+    syscall_error,
+};
+
+impl Process {
+    pub fn create_watchpoint(&mut self, addr: VirtAddr, mode: WatchMode, size: usize) -> DrbugResult<Watchpoint> {
+        if let Some(wp) = self.watchpoints.get_by_addr(&addr) {
+            return Err(DrbugError::WatchpointExists(wp.id(), addr));
+        }
+
+        let wp = Watchpoint::new(self.pid, self.registers.debug_registers(), addr, mode, size)?;
+        self.watchpoints.add(wp.clone());
+        Ok(wp)
+    }
+
+    pub fn create_page_watchpoint(&mut self, addr: VirtAddr, size: usize) -> DrbugResult<PageWatchpoint> {
+        if size == 0 {
+            return Err(DrbugError::InvalidWatchpointSize(0));
+        }
+
+        let maps = self.memory_maps()?;
+        let first_page = addr.0 & !(PAGE_SIZE - 1);
+        let last_page = (addr.0 + size as u64 - 1) & !(PAGE_SIZE - 1);
+        let mut pages = vec![];
+        for page in (first_page..=last_page).step_by(PAGE_SIZE as usize).map(VirtAddr) {
+            // If some other watchpoint has already revoked write access to this page, the maps
+            // file won't tell us what the permissions were originally
+            let prot = match self.page_watchpoints.values().find_map(|wp| wp.page_prot(page)) {
+                Some(prot) => prot,
+                None => maps
+                    .iter()
+                    .find(|m| m.contains(page))
+                    .ok_or(DrbugError::UnmappedAddress(page))?
+                    .prot_flags(),
+            };
+            pages.push((page, prot));
+        }
+
+        let wp = PageWatchpoint::new(addr, size, pages, self.read_memory(addr, size)?);
+        self.page_watchpoints.insert(wp.id(), wp.clone());
+        Ok(wp)
+    }
+
+    pub fn create_software_watchpoint(&mut self, target: WatchTarget) -> DrbugResult<SoftwareWatchpoint> {
+        if let WatchTarget::Memory { size: 0, .. } = target {
+            return Err(DrbugError::InvalidWatchpointSize(0));
+        }
+
+        let wp = SoftwareWatchpoint::new(target, self.read_watch_target(target)?);
+        self.software_watchpoints.insert(wp.id(), wp.clone());
+        Ok(wp)
+    }
+
+    pub fn page_watchpoint(&self, id: &usize) -> Option<PageWatchpoint> {
+        self.page_watchpoints.get(id).cloned()
+    }
+
+    pub fn page_watchpoints(&self) -> impl Iterator<Item = &PageWatchpoint> {
+        self.page_watchpoints.values()
+    }
+
+    pub fn remove_page_watchpoint(&mut self, id: &usize) -> DrbugResult<Option<PageWatchpoint>> {
+        let Some(wp) = self.page_watchpoints.remove(id) else {
+            return Ok(None);
+        };
+
+        if wp.is_armed() {
+            self.disarm_page_watchpoint(&wp)?;
+        }
+        Ok(Some(wp))
+    }
+
+    pub fn remove_software_watchpoint(&mut self, id: &usize) -> Option<SoftwareWatchpoint> {
+        self.software_watchpoints.remove(id)
+    }
+
+    pub fn software_watchpoint(&self, id: &usize) -> Option<SoftwareWatchpoint> {
+        self.software_watchpoints.get(id).cloned()
+    }
+
+    pub fn software_watchpoints(&self) -> impl Iterator<Item = &SoftwareWatchpoint> {
+        self.software_watchpoints.values()
+    }
+
+    pub fn triggered_page_watchpoint(&self) -> Option<&PageWatchpoint> {
+        self.triggered_page_watchpoint.as_ref()
+    }
+
+    pub fn triggered_software_watchpoint(&self) -> Option<&SoftwareWatchpoint> {
+        self.triggered_software_watchpoint.as_ref()
+    }
+
+    pub fn triggered_watchpoint(&self) -> Option<&Watchpoint> {
+        self.triggered_watchpoint.as_ref()
+    }
+
+    pub fn watchpoints(&self) -> &BreakList<Watchpoint> {
+        &self.watchpoints
+    }
+
+    pub fn watchpoints_mut(&mut self) -> &mut BreakList<Watchpoint> {
+        &mut self.watchpoints
+    }
+
+    // DR6 is the debug status register; the low four bits tell us which of DR0-DR3 caused the
+    // most recent debug exception.  The registers have already been re-loaded by the time this is
+    // called, so we can just read it out of the cache.
+    pub(super) fn find_triggered_watchpoint(&self) -> DrbugResult<Option<Watchpoint>> {
+        let dr6_info = register_info_by_id(&RegisterId::dr6);
+        let RegisterValue::U64(status) = self.registers.read(dr6_info)? else {
+            panic!("should never happen");
+        };
+
+        let Some(index) = (0..DEBUG_ADDR_REG_COUNT).find(|&i| status & (1u64 << i) != 0) else {
+            return Ok(None);
+        };
+
+        let maybe_wp = self
+            .watchpoints
+            .iter()
+            .map(|(_, wp)| wp)
+            .find(|wp| wp.hw_index() == Some(index));
+        if let Some(wp) = maybe_wp {
+            wp.update_data()?;
+        }
+        Ok(maybe_wp.cloned())
+    }
+
+    pub(super) fn disarm_all_watchpoints(&mut self) -> Empty {
+        for (_, wp) in self.watchpoints.iter() {
+            wp.clone().disable()?;
+        }
+
+        let armed: Vec<_> = self.page_watchpoints.values().filter(|wp| wp.is_armed()).cloned().collect();
+        for wp in armed {
+            self.disarm_page_watchpoint(&wp)?;
+        }
+        Ok(())
+    }
+
+    // Called after a restart: hardware watchpoints get re-installed in the new process, but page
+    // watchpoints and software watchpoints on memory that isn't mapped yet get disabled, since
+    // there's nothing for them to watch (yet).  With ASLR disabled, the user can turn them back on
+    // once the program gets far enough along.
+    pub(super) fn carry_over_watchpoints(&mut self) -> Empty {
+        let old: Vec<_> = self.watchpoints.iter().map(|(_, wp)| wp.clone()).collect();
+        self.watchpoints = BreakList::new();
+        for wp in old {
+            let mut new_wp = wp.rebind(self.pid, self.registers.debug_registers());
+            if wp.enabled() {
+                new_wp.enable()?;
+            }
+            self.watchpoints.add(new_wp);
+        }
+
+        for wp in self.page_watchpoints.values_mut() {
+            wp.set_armed(false);
+            wp.disable();
+        }
+
+        let software: Vec<_> = self.software_watchpoints.values().cloned().collect();
+        for mut wp in software {
+            match self.read_watch_target(wp.target()) {
+                Ok(data) => wp.set_data(data),
+                Err(_) => wp.disable(),
+            }
+        }
+        Ok(())
+    }
+
+    pub(super) fn find_triggered_software_watchpoint(&self) -> DrbugResult<Option<SoftwareWatchpoint>> {
+        // Update _all_ of the watchpoints, even once we've found one that changed, so that they
+        // all have the right baseline for the next comparison
+        let mut triggered = None;
+        for wp in self.software_watchpoints.values().filter(|wp| wp.enabled()) {
+            if wp.update_data(self.read_watch_target(wp.target())?) && triggered.is_none() {
+                triggered = Some(wp.clone());
+            }
+        }
+        Ok(triggered)
+    }
+
+    pub(super) fn refresh_software_watchpoints(&self) -> Empty {
+        for wp in self.software_watchpoints.values().filter(|wp| wp.enabled()) {
+            wp.set_data(self.read_watch_target(wp.target())?);
+        }
+        Ok(())
+    }
+
+    fn read_watch_target(&self, target: WatchTarget) -> DrbugResult<Vec<u8>> {
+        match target {
+            WatchTarget::Memory { addr, size } => self.read_memory_without_traps(addr, size),
+            WatchTarget::Register(id) => {
+                let info = register_info_by_id(&id);
+                let bytes: Byte512 = self.registers.read(info)?.into();
+                Ok(bytes[..info.size].to_vec())
+            },
+        }
+    }
+
+    // Returns true if the fault was caused by one of our page watchpoints (whether or not it
+    // actually hit the watched range), in which case the faulting instruction has now been executed
+    // and the process is stopped right after it
+    pub(super) fn handle_page_fault(&mut self) -> DrbugResult<bool> {
+        let info = syscall_error!(ptrace::getsiginfo(self.pid))?;
+        // SAFETY: si_addr is always populated for SIGSEGV
+        let fault_addr = VirtAddr(unsafe { info.si_addr() } as u64);
+        let page = VirtAddr(fault_addr.0 & !(PAGE_SIZE - 1));
+        let Some(prot) = self
+            .page_watchpoints
+            .values()
+            .filter(|wp| wp.is_armed())
+            .find_map(|wp| wp.page_prot(page))
+        else {
+            return Ok(false);
+        };
+
+        // Let the write through, and then lock the page back down
+        self.mprotect(page, prot)?;
+        self.flush_registers()?;
+        syscall_error!(ptrace::step(self.pid, None))?;
+        self.state = syscall_error!(waitpid(self.pid, None))?.into();
+        if !self.state.is_stopped() {
+            return Ok(true);
+        }
+        self.mprotect(page, prot & !PROT_WRITE)?;
+
+        let hits: Vec<_> = self
+            .page_watchpoints
+            .values()
+            .filter(|wp| wp.is_armed() && wp.contains(fault_addr))
+            .cloned()
+            .collect();
+        for wp in hits {
+            wp.update_data(fault_addr, self.read_memory(wp.addr(), wp.size())?);
+            if self.triggered_page_watchpoint.is_none() {
+                self.triggered_page_watchpoint = Some(wp);
+            }
+        }
+        Ok(true)
+    }
+
+    // Enabling or disabling a page watchpoint means injecting syscalls into the inferior, so
+    // instead of doing that right away, we sync everything up just before the inferior runs again
+    pub(super) fn sync_page_watchpoints(&mut self) -> Empty {
+        let wps: Vec<_> = self.page_watchpoints.values().cloned().collect();
+        for wp in wps {
+            if wp.enabled() && !wp.is_armed() {
+                self.arm_page_watchpoint(&wp)?;
+            } else if !wp.enabled() && wp.is_armed() {
+                self.disarm_page_watchpoint(&wp)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn arm_page_watchpoint(&mut self, wp: &PageWatchpoint) -> Empty {
+        for &(page, prot) in wp.pages() {
+            self.mprotect(page, prot & !PROT_WRITE)?;
+        }
+        wp.set_armed(true);
+        Ok(())
+    }
+
+    fn disarm_page_watchpoint(&mut self, wp: &PageWatchpoint) -> Empty {
+        wp.set_armed(false);
+        for &(page, prot) in wp.pages() {
+            // Leave the page alone if some other watchpoint still needs it locked down
+            if !self
+                .page_watchpoints
+                .values()
+                .any(|other| other.is_armed() && other.page_prot(page).is_some())
+            {
+                self.mprotect(page, prot)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn mprotect(&mut self, page: VirtAddr, prot: i32) -> Empty {
+        let res = self.inject_syscall(libc::SYS_mprotect, &[page.0, PAGE_SIZE, prot as u64])?;
+        if res < 0 {
+            return Err(DrbugError::SyscallFailed("mprotect", Errno::from_raw(-res as i32)));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,257 @@
+use std::fmt;
+
+use super::info::{
+    RegisterId,
+    RegisterInfo,
+};
+use super::value::RegisterValue;
+use crate::{
+    DrbugError,
+    DrbugResult,
+    Empty,
+};
+
+// A named group of bits in a flags or control register; most of them are a single bit, but some
+// (like the rounding mode) are wider, and a few of those have names for each of their values
+#[derive(Debug)]
+pub struct FlagField {
+    pub name: &'static str,
+    pub shift: u32,
+    pub width: u32,
+    pub values: &'static [&'static str],
+}
+
+impl FlagField {
+    fn mask(&self) -> u64 {
+        ((1 << self.width) - 1) << self.shift
+    }
+
+    fn get(&self, val: u64) -> u64 {
+        (val & self.mask()) >> self.shift
+    }
+}
+
+macro_rules! flag {
+    ($name:literal, $shift:literal) => {
+        FlagField { name: $name, shift: $shift, width: 1, values: &[] }
+    };
+    ($name:literal, $shift:literal, $width:literal) => {
+        FlagField {
+            name: $name,
+            shift: $shift,
+            width: $width,
+            values: &[],
+        }
+    };
+    ($name:literal, $shift:literal, $width:literal, $values:expr) => {
+        FlagField {
+            name: $name,
+            shift: $shift,
+            width: $width,
+            values: $values,
+        }
+    };
+}
+
+const ROUNDING_MODES: &[&str] = &["nearest", "down", "up", "zero"];
+const WATCH_MODES: &[&str] = &["x", "w", "io", "rw"];
+const WATCH_SIZES: &[&str] = &["1", "2", "8", "4"]; // yes, 8 really does come before 4
+
+const EFLAGS_FIELDS: &[FlagField] = &[
+    flag!("CF", 0),
+    flag!("PF", 2),
+    flag!("AF", 4),
+    flag!("ZF", 6),
+    flag!("SF", 7),
+    flag!("TF", 8),
+    flag!("IF", 9),
+    flag!("DF", 10),
+    flag!("OF", 11),
+    flag!("IOPL", 12, 2),
+    flag!("NT", 14),
+    flag!("RF", 16),
+    flag!("VM", 17),
+    flag!("AC", 18),
+    flag!("VIF", 19),
+    flag!("VIP", 20),
+    flag!("ID", 21),
+];
+
+const MXCSR_FIELDS: &[FlagField] = &[
+    flag!("IE", 0),
+    flag!("DE", 1),
+    flag!("ZE", 2),
+    flag!("OE", 3),
+    flag!("UE", 4),
+    flag!("PE", 5),
+    flag!("DAZ", 6),
+    flag!("IM", 7),
+    flag!("DM", 8),
+    flag!("ZM", 9),
+    flag!("OM", 10),
+    flag!("UM", 11),
+    flag!("PM", 12),
+    flag!("RC", 13, 2, ROUNDING_MODES),
+    flag!("FZ", 15),
+];
+
+const FSW_FIELDS: &[FlagField] = &[
+    flag!("IE", 0),
+    flag!("DE", 1),
+    flag!("ZE", 2),
+    flag!("OE", 3),
+    flag!("UE", 4),
+    flag!("PE", 5),
+    flag!("SF", 6),
+    flag!("ES", 7),
+    flag!("C0", 8),
+    flag!("C1", 9),
+    flag!("C2", 10),
+    flag!("TOP", 11, 3),
+    flag!("C3", 14),
+    flag!("B", 15),
+];
+
+const FCW_FIELDS: &[FlagField] = &[
+    flag!("IM", 0),
+    flag!("DM", 1),
+    flag!("ZM", 2),
+    flag!("OM", 3),
+    flag!("UM", 4),
+    flag!("PM", 5),
+    flag!("PC", 8, 2, &["single", "reserved", "double", "extended"]),
+    flag!("RC", 10, 2, ROUNDING_MODES),
+    flag!("X", 12),
+];
+
+const DR6_FIELDS: &[FlagField] = &[
+    flag!("B0", 0),
+    flag!("B1", 1),
+    flag!("B2", 2),
+    flag!("B3", 3),
+    flag!("BD", 13),
+    flag!("BS", 14),
+    flag!("BT", 15),
+];
+
+const DR7_FIELDS: &[FlagField] = &[
+    flag!("L0", 0),
+    flag!("G0", 1),
+    flag!("L1", 2),
+    flag!("G1", 3),
+    flag!("L2", 4),
+    flag!("G2", 5),
+    flag!("L3", 6),
+    flag!("G3", 7),
+    flag!("LE", 8),
+    flag!("GE", 9),
+    flag!("GD", 13),
+    flag!("RW0", 16, 2, WATCH_MODES),
+    flag!("LEN0", 18, 2, WATCH_SIZES),
+    flag!("RW1", 20, 2, WATCH_MODES),
+    flag!("LEN1", 22, 2, WATCH_SIZES),
+    flag!("RW2", 24, 2, WATCH_MODES),
+    flag!("LEN2", 26, 2, WATCH_SIZES),
+    flag!("RW3", 28, 2, WATCH_MODES),
+    flag!("LEN3", 30, 2, WATCH_SIZES),
+];
+
+pub fn flag_fields(id: &RegisterId) -> Option<&'static [FlagField]> {
+    match id {
+        RegisterId::eflags => Some(EFLAGS_FIELDS),
+        RegisterId::mxcsr => Some(MXCSR_FIELDS),
+        RegisterId::fsw => Some(FSW_FIELDS),
+        RegisterId::fcw => Some(FCW_FIELDS),
+        RegisterId::dr6 => Some(DR6_FIELDS),
+        RegisterId::dr7 => Some(DR7_FIELDS),
+        _ => None,
+    }
+}
+
+// The value of a flags register along with what its bits mean
+#[derive(Clone, Debug)]
+pub struct Flags {
+    fields: &'static [FlagField],
+    size: usize,
+    value: u64,
+}
+
+impl Flags {
+    // Returns None if the register isn't one we know how to decode
+    pub fn decode(info: &RegisterInfo, val: &RegisterValue) -> Option<Self> {
+        let fields = flag_fields(&info.id)?;
+        let value = match val {
+            RegisterValue::U16(v) => *v as u64,
+            RegisterValue::U32(v) => *v as u64,
+            RegisterValue::U64(v) => *v,
+            _ => return None,
+        };
+        Some(Flags { fields, size: info.size, value })
+    }
+
+    pub fn value(&self) -> u64 {
+        self.value
+    }
+
+    // Looks a field up by name (ignoring case), and returns its value
+    pub fn get(&self, name: &str) -> Option<u64> {
+        self.field(name).map(|field| field.get(self.value))
+    }
+
+    // Changes are written like `+ZF` (set a flag), `-CF` (clear it), or `RC=up` (set a wider field,
+    // by name or number), and there can be several of them separated by spaces
+    pub fn apply(&mut self, changes: &str) -> Empty {
+        for change in changes.split_whitespace() {
+            let invalid = || DrbugError::InvalidFlagChange(change.into());
+            let (field, val) = if let Some(name) = change.strip_prefix('+') {
+                (self.field(name).filter(|f| f.width == 1).ok_or_else(invalid)?, 1)
+            } else if let Some(name) = change.strip_prefix('-') {
+                (self.field(name).filter(|f| f.width == 1).ok_or_else(invalid)?, 0)
+            } else {
+                let (name, val_str) = change.split_once('=').ok_or_else(invalid)?;
+                let field = self.field(name).ok_or_else(invalid)?;
+                let val = match field.values.iter().position(|v| v.eq_ignore_ascii_case(val_str)) {
+                    Some(pos) => pos as u64,
+                    None => val_str.parse::<u64>().map_err(|_| invalid())?,
+                };
+                if val >= 1 << field.width {
+                    return Err(invalid());
+                }
+                (field, val)
+            };
+            self.value = (self.value & !field.mask()) | (val << field.shift);
+        }
+        Ok(())
+    }
+
+    pub fn to_register_value(&self) -> RegisterValue {
+        match self.size {
+            2 => RegisterValue::U16(self.value as u16),
+            4 => RegisterValue::U32(self.value as u32),
+            _ => RegisterValue::U64(self.value),
+        }
+    }
+
+    fn field(&self, name: &str) -> Option<&'static FlagField> {
+        self.fields.iter().find(|field| field.name.eq_ignore_ascii_case(name))
+    }
+}
+
+// Only shows the flags that are set (and the wider fields that aren't zero), which is how most
+// debuggers do it; e.g., `[ PF ZF IF ]` for a typical eflags
+impl fmt::Display for Flags {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[")?;
+        for field in self.fields {
+            match field.get(self.value) {
+                0 => (),
+                _ if field.width == 1 => write!(f, " {}", field.name)?,
+                val => match field.values.get(val as usize) {
+                    Some(name) => write!(f, " {}={name}", field.name)?,
+                    None => write!(f, " {}={val}", field.name)?,
+                },
+            }
+        }
+        write!(f, " ]")
+    }
+}
@@ -8,6 +8,11 @@ use libc::{
     user_regs_struct,
 };
 
+use super::xstate::{
+    XstateComponent,
+    XstatePiece,
+    component_enabled,
+};
 use crate::{
     DrbugError,
     DrbugResult,
@@ -18,6 +23,8 @@ pub enum RegisterType {
     General,
     SubGeneral,
     FloatingPoint,
+    Avx,
+    Avx512,
     Debug,
 }
 
@@ -40,6 +47,15 @@ pub struct RegisterInfo {
     pub format: RegisterFormat,
 
     pub(crate) offset: usize,
+    // Registers that live in the XSAVE area rather than the user area; for these `offset` is unused
+    pub(crate) xstate: &'static [XstatePiece],
+}
+
+impl RegisterInfo {
+    // Whether the processor (and OS) we're running on actually has this register
+    pub fn available(&self) -> bool {
+        self.xstate.iter().all(|piece| component_enabled(piece.component))
+    }
 }
 
 macro_rules! gpr_offset {
@@ -60,6 +76,16 @@ macro_rules! debug_offset {
     };
 }
 
+macro_rules! xstate_piece {
+    ($component:ident, $offset:expr, $size:literal) => {
+        XstatePiece {
+            component: XstateComponent::$component,
+            offset: $offset,
+            size: $size,
+        }
+    };
+}
+
 pub const fn size_of_return_value<F, T, U>(_f: &F) -> usize
 where
     F: FnOnce(T) -> U,
@@ -98,6 +124,7 @@ macro_rules! define_registers {
             offset: gpr_offset!($name),
             type_: RegisterType::General,
             format: RegisterFormat::Uint,
+            xstate: &[],
         }
     };
 
@@ -110,6 +137,7 @@ macro_rules! define_registers {
             offset: gpr_offset!($name),
             type_: RegisterType::General,
             format: RegisterFormat::Uint,
+            xstate: &[],
         }
     };
 
@@ -122,6 +150,7 @@ macro_rules! define_registers {
             offset: gpr_offset!($super),
             type_: RegisterType::SubGeneral,
             format: RegisterFormat::Uint,
+            xstate: &[],
         }
     };
 
@@ -134,6 +163,7 @@ macro_rules! define_registers {
             offset: gpr_offset!($super),
             type_: RegisterType::SubGeneral,
             format: RegisterFormat::Uint,
+            xstate: &[],
         }
     };
 
@@ -146,6 +176,7 @@ macro_rules! define_registers {
             offset: gpr_offset!($super),
             type_: RegisterType::SubGeneral,
             format: RegisterFormat::Uint,
+            xstate: &[],
         }
     };
 
@@ -158,6 +189,7 @@ macro_rules! define_registers {
             offset: gpr_offset!($super),
             type_: RegisterType::SubGeneral,
             format: RegisterFormat::Uint,
+            xstate: &[],
         }
     };
 
@@ -170,6 +202,7 @@ macro_rules! define_registers {
             offset: fpr_offset!($user_name),
             type_: RegisterType::FloatingPoint,
             format: RegisterFormat::Uint,
+            xstate: &[],
         }
     };
 
@@ -182,6 +215,7 @@ macro_rules! define_registers {
             offset: fpr_offset!($user_name),
             type_: RegisterType::FloatingPoint,
             format: RegisterFormat::Uint,
+            xstate: &[],
         }
     };
 
@@ -194,6 +228,7 @@ macro_rules! define_registers {
             offset: fpr_offset!(st_space) + $id * 16,
             type_: RegisterType::FloatingPoint,
             format: RegisterFormat::LongDouble,
+            xstate: &[],
         }
     };
 
@@ -207,6 +242,7 @@ macro_rules! define_registers {
             offset: fpr_offset!(st_space) + $id * 16,
             type_: RegisterType::FloatingPoint,
             format: RegisterFormat::Vector,
+            xstate: &[],
         }
     };
 
@@ -219,6 +255,81 @@ macro_rules! define_registers {
             offset: fpr_offset!(xmm_space) + $id * 16,
             type_: RegisterType::FloatingPoint,
             format: RegisterFormat::Vector,
+            xstate: &[],
+        }
+    };
+
+    // The low halves of the ymm registers are the xmm registers, which are in the legacy area
+    (@generate_call YMM, $name:ident, $id:tt) => {
+        RegisterInfo {
+            id: RegisterId::$name,
+            name: stringify!($name),
+            dwarf_id: None,
+            size: 32,
+            offset: 0,
+            type_: RegisterType::Avx,
+            format: RegisterFormat::Vector,
+            xstate: &[
+                xstate_piece!(Sse, offset_of!(user_fpregs_struct, xmm_space) + $id * 16, 16),
+                xstate_piece!(Avx, $id * 16, 16),
+            ],
+        }
+    };
+
+    (@generate_call ZMM_LO, $name:ident, $id:tt) => {
+        RegisterInfo {
+            id: RegisterId::$name,
+            name: stringify!($name),
+            dwarf_id: None,
+            size: 64,
+            offset: 0,
+            type_: RegisterType::Avx512,
+            format: RegisterFormat::Vector,
+            xstate: &[
+                xstate_piece!(Sse, offset_of!(user_fpregs_struct, xmm_space) + $id * 16, 16),
+                xstate_piece!(Avx, $id * 16, 16),
+                xstate_piece!(ZmmHi256, $id * 32, 32),
+            ],
+        }
+    };
+
+    // zmm16-31 (and the xmm registers at the bottom of them) are all in one place
+    (@generate_call ZMM_HI, $name:ident, $id:tt) => {
+        RegisterInfo {
+            id: RegisterId::$name,
+            name: stringify!($name),
+            dwarf_id: None,
+            size: 64,
+            offset: 0,
+            type_: RegisterType::Avx512,
+            format: RegisterFormat::Vector,
+            xstate: &[xstate_piece!(Hi16Zmm, ($id - 16) * 64, 64)],
+        }
+    };
+
+    (@generate_call XMM_HI, $name:ident, $id:tt) => {
+        RegisterInfo {
+            id: RegisterId::$name,
+            name: stringify!($name),
+            dwarf_id: Some(51 + $id),
+            size: 16,
+            offset: 0,
+            type_: RegisterType::Avx512,
+            format: RegisterFormat::Vector,
+            xstate: &[xstate_piece!(Hi16Zmm, ($id - 16) * 64, 16)],
+        }
+    };
+
+    (@generate_call OPMASK, $name:ident, $id:tt) => {
+        RegisterInfo {
+            id: RegisterId::$name,
+            name: stringify!($name),
+            dwarf_id: Some(118 + $id),
+            size: 8,
+            offset: 0,
+            type_: RegisterType::Avx512,
+            format: RegisterFormat::Uint,
+            xstate: &[xstate_piece!(Opmask, $id * 8, 8)],
         }
     };
 
@@ -231,6 +342,7 @@ macro_rules! define_registers {
             offset: debug_offset!($id),
             type_: RegisterType::Debug,
             format: RegisterFormat::Uint,
+            xstate: &[],
         }
     };
 }
@@ -362,6 +474,82 @@ define_registers!(
     (xmm14, 14): FP_XMM,
     (xmm15, 15): FP_XMM,
 
+    (ymm0, 0): YMM,
+    (ymm1, 1): YMM,
+    (ymm2, 2): YMM,
+    (ymm3, 3): YMM,
+    (ymm4, 4): YMM,
+    (ymm5, 5): YMM,
+    (ymm6, 6): YMM,
+    (ymm7, 7): YMM,
+    (ymm8, 8): YMM,
+    (ymm9, 9): YMM,
+    (ymm10, 10): YMM,
+    (ymm11, 11): YMM,
+    (ymm12, 12): YMM,
+    (ymm13, 13): YMM,
+    (ymm14, 14): YMM,
+    (ymm15, 15): YMM,
+
+    (zmm0, 0): ZMM_LO,
+    (zmm1, 1): ZMM_LO,
+    (zmm2, 2): ZMM_LO,
+    (zmm3, 3): ZMM_LO,
+    (zmm4, 4): ZMM_LO,
+    (zmm5, 5): ZMM_LO,
+    (zmm6, 6): ZMM_LO,
+    (zmm7, 7): ZMM_LO,
+    (zmm8, 8): ZMM_LO,
+    (zmm9, 9): ZMM_LO,
+    (zmm10, 10): ZMM_LO,
+    (zmm11, 11): ZMM_LO,
+    (zmm12, 12): ZMM_LO,
+    (zmm13, 13): ZMM_LO,
+    (zmm14, 14): ZMM_LO,
+    (zmm15, 15): ZMM_LO,
+    (zmm16, 16): ZMM_HI,
+    (zmm17, 17): ZMM_HI,
+    (zmm18, 18): ZMM_HI,
+    (zmm19, 19): ZMM_HI,
+    (zmm20, 20): ZMM_HI,
+    (zmm21, 21): ZMM_HI,
+    (zmm22, 22): ZMM_HI,
+    (zmm23, 23): ZMM_HI,
+    (zmm24, 24): ZMM_HI,
+    (zmm25, 25): ZMM_HI,
+    (zmm26, 26): ZMM_HI,
+    (zmm27, 27): ZMM_HI,
+    (zmm28, 28): ZMM_HI,
+    (zmm29, 29): ZMM_HI,
+    (zmm30, 30): ZMM_HI,
+    (zmm31, 31): ZMM_HI,
+
+    (xmm16, 16): XMM_HI,
+    (xmm17, 17): XMM_HI,
+    (xmm18, 18): XMM_HI,
+    (xmm19, 19): XMM_HI,
+    (xmm20, 20): XMM_HI,
+    (xmm21, 21): XMM_HI,
+    (xmm22, 22): XMM_HI,
+    (xmm23, 23): XMM_HI,
+    (xmm24, 24): XMM_HI,
+    (xmm25, 25): XMM_HI,
+    (xmm26, 26): XMM_HI,
+    (xmm27, 27): XMM_HI,
+    (xmm28, 28): XMM_HI,
+    (xmm29, 29): XMM_HI,
+    (xmm30, 30): XMM_HI,
+    (xmm31, 31): XMM_HI,
+
+    (k0, 0): OPMASK,
+    (k1, 1): OPMASK,
+    (k2, 2): OPMASK,
+    (k3, 3): OPMASK,
+    (k4, 4): OPMASK,
+    (k5, 5): OPMASK,
+    (k6, 6): OPMASK,
+    (k7, 7): OPMASK,
+
     (dr0, 0): DEBUG,
     (dr1, 1): DEBUG,
     (dr2, 2): DEBUG,
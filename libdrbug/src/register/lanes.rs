@@ -0,0 +1,228 @@
+use std::fmt;
+use std::str::FromStr;
+
+use super::value::RegisterValue;
+use crate::{
+    Byte512,
+    DrbugError,
+    DrbugResult,
+};
+
+// The type of each element when a vector register is treated as an array, the way SIMD instructions
+// do; the register itself doesn't know or care which of these it's holding
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum LaneType {
+    I8,
+    I16,
+    I32,
+    I64,
+    U8,
+    U16,
+    U32,
+    U64,
+    F32,
+    F64,
+}
+
+impl LaneType {
+    pub fn size(&self) -> usize {
+        match self {
+            LaneType::I8 | LaneType::U8 => 1,
+            LaneType::I16 | LaneType::U16 => 2,
+            LaneType::I32 | LaneType::U32 | LaneType::F32 => 4,
+            LaneType::I64 | LaneType::U64 | LaneType::F64 => 8,
+        }
+    }
+
+    pub fn is_floating_point(&self) -> bool {
+        matches!(self, LaneType::F32 | LaneType::F64)
+    }
+
+    fn decode(&self, bytes: &[u8]) -> DrbugResult<RegisterValue> {
+        Ok(match self {
+            LaneType::I8 => RegisterValue::I8(bytes[0] as i8),
+            LaneType::I16 => RegisterValue::I16(i16::from_le_bytes(bytes.try_into()?)),
+            LaneType::I32 => RegisterValue::I32(i32::from_le_bytes(bytes.try_into()?)),
+            LaneType::I64 => RegisterValue::I64(i64::from_le_bytes(bytes.try_into()?)),
+            LaneType::U8 => RegisterValue::U8(bytes[0]),
+            LaneType::U16 => RegisterValue::U16(u16::from_le_bytes(bytes.try_into()?)),
+            LaneType::U32 => RegisterValue::U32(u32::from_le_bytes(bytes.try_into()?)),
+            LaneType::U64 => RegisterValue::U64(u64::from_le_bytes(bytes.try_into()?)),
+            LaneType::F32 => RegisterValue::F32(f32::from_le_bytes(bytes.try_into()?)),
+            LaneType::F64 => RegisterValue::F64(f64::from_le_bytes(bytes.try_into()?)),
+        })
+    }
+
+    // The lane has to be exactly the right kind of value; we don't try to convert between them
+    fn encode(&self, val: &RegisterValue) -> DrbugResult<Byte512> {
+        if val.size() != self.size() || val.is_floating_point() != self.is_floating_point() {
+            return Err(DrbugError::InvalidRegisterValue(val.clone()));
+        }
+        Ok(val.into())
+    }
+}
+
+impl FromStr for LaneType {
+    type Err = DrbugError;
+
+    fn from_str(s: &str) -> DrbugResult<Self> {
+        match s {
+            "i8" => Ok(LaneType::I8),
+            "i16" => Ok(LaneType::I16),
+            "i32" => Ok(LaneType::I32),
+            "i64" => Ok(LaneType::I64),
+            "u8" => Ok(LaneType::U8),
+            "u16" => Ok(LaneType::U16),
+            "u32" => Ok(LaneType::U32),
+            "u64" => Ok(LaneType::U64),
+            "f32" => Ok(LaneType::F32),
+            "f64" => Ok(LaneType::F64),
+            _ => Err(DrbugError::InvalidLaneFormat(s.into())),
+        }
+    }
+}
+
+impl fmt::Display for LaneType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            LaneType::I8 => "i8",
+            LaneType::I16 => "i16",
+            LaneType::I32 => "i32",
+            LaneType::I64 => "i64",
+            LaneType::U8 => "u8",
+            LaneType::U16 => "u16",
+            LaneType::U32 => "u32",
+            LaneType::U64 => "u64",
+            LaneType::F32 => "f32",
+            LaneType::F64 => "f64",
+        };
+        write!(f, "{name}")
+    }
+}
+
+// Something like `f32x4`; the count can be left off (just `f32`), in which case it's however many
+// lanes fit in the register, so the same format works for xmm, ymm, and zmm registers
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct LaneFormat {
+    pub lane: LaneType,
+    pub count: Option<usize>,
+}
+
+impl LaneFormat {
+    // Returns the number of lanes in a vector of `size` bytes, if this format fits it exactly
+    fn lanes_in(&self, size: usize) -> DrbugResult<usize> {
+        match self.count {
+            None if size % self.lane.size() == 0 => Ok(size / self.lane.size()),
+            Some(count) if count * self.lane.size() == size => Ok(count),
+            _ => Err(DrbugError::LaneFormatMismatch(*self, size)),
+        }
+    }
+}
+
+impl FromStr for LaneFormat {
+    type Err = DrbugError;
+
+    fn from_str(s: &str) -> DrbugResult<Self> {
+        let (lane, count) = match s.split_once('x') {
+            Some((lane, count)) => {
+                let count = count.parse().map_err(|_| DrbugError::InvalidLaneFormat(s.into()))?;
+                (lane, Some(count))
+            },
+            None => (s, None),
+        };
+
+        match (lane.parse::<LaneType>(), count) {
+            (Ok(_), Some(0)) | (Err(_), _) => Err(DrbugError::InvalidLaneFormat(s.into())),
+            (Ok(lane), count) => Ok(LaneFormat { lane, count }),
+        }
+    }
+}
+
+impl fmt::Display for LaneFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.count {
+            Some(count) => write!(f, "{}x{count}", self.lane),
+            None => write!(f, "{}", self.lane),
+        }
+    }
+}
+
+// A vector register split up into lanes, lowest lane first (which is also how the processor numbers
+// them, so `xmm0[0]` is the first one here).  Unlike a plain RegisterValue, the integer lanes print
+// in decimal, since that's usually what you're after when you ask for them this way.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Lanes(pub Vec<RegisterValue>);
+
+impl fmt::Display for Lanes {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let lanes: Vec<String> = self
+            .0
+            .iter()
+            .map(|val| match val {
+                RegisterValue::I8(v) => v.to_string(),
+                RegisterValue::I16(v) => v.to_string(),
+                RegisterValue::I32(v) => v.to_string(),
+                RegisterValue::I64(v) => v.to_string(),
+                RegisterValue::U8(v) => v.to_string(),
+                RegisterValue::U16(v) => v.to_string(),
+                RegisterValue::U32(v) => v.to_string(),
+                RegisterValue::U64(v) => v.to_string(),
+                val => val.to_string(),
+            })
+            .collect();
+        write!(f, "[{}]", lanes.join(", "))
+    }
+}
+
+impl RegisterValue {
+    pub fn lanes(&self, format: LaneFormat) -> DrbugResult<Lanes> {
+        if !self.is_vector() {
+            return Err(DrbugError::InvalidRegisterValue(self.clone()));
+        }
+
+        let count = format.lanes_in(self.size())?;
+        let bytes = Byte512::from(self);
+        let lane_size = format.lane.size();
+        let lanes = (0..count)
+            .map(|i| format.lane.decode(&bytes[i * lane_size..(i + 1) * lane_size]))
+            .collect::<DrbugResult<_>>()?;
+        Ok(Lanes(lanes))
+    }
+
+    // Returns a copy of this vector with lane `index` (counting in `lane`-sized pieces) replaced
+    pub fn with_lane(&self, lane: LaneType, index: usize, val: &RegisterValue) -> DrbugResult<RegisterValue> {
+        if !self.is_vector() {
+            return Err(DrbugError::InvalidRegisterValue(self.clone()));
+        }
+
+        let count = self.size() / lane.size();
+        if index >= count {
+            return Err(DrbugError::LaneOutOfRange(index, count));
+        }
+
+        let mut bytes = Byte512::from(self);
+        let start = index * lane.size();
+        bytes[start..start + lane.size()].copy_from_slice(&lane.encode(val)?[..lane.size()]);
+        vector_from_bytes(&bytes[..self.size()])
+    }
+
+    // Builds a vector out of a whole set of lanes; there has to be the right number of them to
+    // make up one of the register sizes
+    pub fn from_lanes(lane: LaneType, vals: &[RegisterValue]) -> DrbugResult<RegisterValue> {
+        let mut bytes = Vec::with_capacity(vals.len() * lane.size());
+        for val in vals {
+            bytes.extend_from_slice(&lane.encode(val)?[..lane.size()]);
+        }
+        vector_from_bytes(&bytes)
+    }
+}
+
+fn vector_from_bytes(bytes: &[u8]) -> DrbugResult<RegisterValue> {
+    Ok(match bytes.len() {
+        8 => RegisterValue::B64(bytes.try_into()?),
+        16 => RegisterValue::B128(bytes.try_into()?),
+        32 => RegisterValue::B256(bytes.try_into()?),
+        64 => RegisterValue::B512(bytes.try_into()?),
+        len => return Err(DrbugError::InvalidRegisterSize(len)),
+    })
+}
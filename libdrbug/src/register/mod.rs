@@ -1,9 +1,28 @@
+pub mod flags;
 pub mod info;
+pub mod lanes;
 pub mod value;
+pub mod x87;
+mod xstate;
 
-use std::mem::MaybeUninit;
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::mem::{
+    MaybeUninit,
+    offset_of,
+};
+use std::rc::Rc;
 
-use libc::user;
+use libc::{
+    PTRACE_GETREGSET,
+    PTRACE_SETREGSET,
+    c_void,
+    iovec,
+    user,
+    user_fpregs_struct,
+    user_regs_struct,
+};
+use nix::errno::Errno;
 use nix::sys::ptrace;
 use nix::sys::ptrace::AddressType;
 use nix::unistd::Pid;
@@ -12,11 +31,22 @@ use self::info::{
     DEBUG_REGISTER_IDS,
     REGISTER_INFOS,
     RegisterFormat,
+    RegisterId,
     RegisterInfo,
     RegisterType,
     register_info_by_id,
 };
 use self::value::RegisterValue;
+use self::x87::{
+    F80,
+    X87Stack,
+};
+use self::xstate::{
+    NT_X86_XSTATE,
+    XSTATE_BV_OFFSET,
+    XSTATE_LAYOUT,
+    XstateComponent,
+};
 use crate::util::{
     as_bytes,
     as_bytes_mut,
@@ -24,16 +54,87 @@ use crate::util::{
 };
 use crate::{
     Byte128,
+    Byte512,
     DrbugError,
     DrbugResult,
     Empty,
     syscall_error,
 };
 
+// The parts of the user area that the instruction recorder keeps track of; everything in here is a
+// whole number of 8-byte words, so we can diff them a word at a time
+const RECORDED_AREAS: [(usize, usize); 2] = [
+    (offset_of!(user, regs), size_of::<user_regs_struct>()),
+    (offset_of!(user, i387), size_of::<user_fpregs_struct>()),
+];
+
+// How many stops' worth of register values we hang on to
+const HISTORY_LIMIT: usize = 64;
+
 #[derive(Debug)]
 pub struct Registers {
     pid: Pid,
     data: user,
+    xstate: Vec<u8>,                // the raw XSAVE area, empty if the processor doesn't have one
+    debug_regs: Rc<DebugRegisters>, // these live outside of `data`, see below
+    dirty: DirtyAreas,
+    loaded: bool,            // false from the time the process starts running until the next `load_all`
+    history: VecDeque<user>, // the values at each of the last few recorded stops, oldest first
+}
+
+// The parts of the cache that have been written to since they were loaded, which all have to be
+// sent back out to the process before it runs again
+#[derive(Debug, Default)]
+struct DirtyAreas {
+    gprs: bool,
+    fprs: bool,
+    xstate: bool,
+}
+
+// The debug registers get written by hardware watchpoints as well as by the user, so their part of
+// the cache is shared with every watchpoint in the process; that way they all see each other's
+// changes, and nobody's write gets clobbered by a stale value at the next `flush`.  The process
+// can't change them itself (other than the CPU updating DR6 when it traps), so unlike the rest of
+// the cache, they're still good while it's running.
+#[derive(Debug, Default, Eq, PartialEq)]
+pub(crate) struct DebugRegisters {
+    values: Cell<[u64; 8]>,
+    dirty: Cell<u8>, // one bit for each register, since we can only write them one at a time
+}
+
+impl DebugRegisters {
+    pub(crate) fn read(&self, index: usize) -> u64 {
+        self.values.get()[index]
+    }
+
+    pub(crate) fn write(&self, index: usize, val: u64) {
+        let mut values = self.values.get();
+        values[index] = val;
+        self.values.set(values);
+        self.dirty.set(self.dirty.get() | 1 << index);
+    }
+
+    fn flush(&self, pid: Pid) -> Empty {
+        for (i, dr) in DEBUG_REGISTER_IDS.iter().enumerate() {
+            if self.dirty.get() & (1 << i) != 0 {
+                let info = register_info_by_id(dr);
+                syscall_error!(ptrace::write_user(pid, info.offset as AddressType, self.read(i) as i64))?;
+            }
+        }
+        self.dirty.set(0);
+        Ok(())
+    }
+
+    fn load(&self, pid: Pid) -> Empty {
+        let mut values = [0; 8];
+        for (i, dr) in DEBUG_REGISTER_IDS.iter().enumerate() {
+            let info = register_info_by_id(dr);
+            values[i] = syscall_error!(ptrace::read_user(pid, info.offset as AddressType))? as u64;
+        }
+        self.values.set(values);
+        self.dirty.set(0);
+        Ok(())
+    }
 }
 
 impl Registers {
@@ -42,85 +143,300 @@ impl Registers {
         // recommended/only way to construct most of the structs in libc.  Also AFAICT the data
         // struct in the equivalent C code is also uninitialized so maybe it's fine?
         let data = MaybeUninit::zeroed();
-        Registers { data: unsafe { data.assume_init() }, pid }
+        let xstate = vec![0; XSTATE_LAYOUT.as_ref().map_or(0, |layout| layout.size)];
+        Registers {
+            data: unsafe { data.assume_init() },
+            xstate,
+            pid,
+            debug_regs: Rc::new(DebugRegisters::default()),
+            dirty: DirtyAreas::default(),
+            loaded: false,
+            history: VecDeque::new(),
+        }
     }
 
     // N.B. The read_* functions are reading the "cached" register values in the Registers.data
     // field; they are _not_ reading from the actual registers via ptrace.  That is done in the
-    // `load_all` call below, which is executed whenever the process halts; however, there is a
-    // possibility of a state mismatch if we try to read from a register and somehow the "load_all"
-    // call hasn't been done yet.
-    pub fn read_group(&self, group: Option<RegisterType>) -> DrbugResult<Vec<(&'static str, Option<RegisterValue>)>> {
+    // `load_all` call below, which is executed whenever the process halts.  Once the process starts
+    // running again the cache is out of date, so reading from it is an error until the next stop.
+    pub fn read_group(&self, group: Option<RegisterType>) -> DrbugResult<Vec<(&'static str, RegisterValue)>> {
         REGISTER_INFOS
             .iter()
-            .filter_map(|info| {
-                if group.is_none_or(|g| info.type_ == g) {
-                    if info.format == RegisterFormat::LongDouble {
-                        Some(Ok((info.name, None)))
-                    } else {
-                        Some(self.read(info).map(|v| (info.name, Some(v))))
-                    }
-                } else {
-                    None
-                }
-            })
+            .filter(|info| group.is_none_or(|g| info.type_ == g) && info.available())
+            .map(|info| self.read(info).map(|v| (info.name, v)))
             .collect() // collect pulls a Vec of results into a result of vec
     }
 
     pub fn read(&self, info: &RegisterInfo) -> DrbugResult<RegisterValue> {
+        self.check_loaded()?;
+        if !info.xstate.is_empty() {
+            return decode_value(info, &self.read_xstate(info)?);
+        }
+        if info.type_ == RegisterType::Debug {
+            return decode_value(info, &self.debug_regs.read(debug_index(info)).to_le_bytes());
+        }
+
         // SAFETY: self.data is #[repr(C)], is not null, and valid for reads; it will not be
         // mutated while in this block, and the total size is less than isize::MAX
         let bytes: &[u8] = as_bytes(&self.data);
-        let res = match info.format {
-            RegisterFormat::Uint => match info.size {
-                1 => RegisterValue::U8(bytes[info.offset]),
-                2 => RegisterValue::U16(u16::from_le_bytes(bytes[info.offset..info.offset + info.size].try_into()?)),
-                4 => RegisterValue::U32(u32::from_le_bytes(bytes[info.offset..info.offset + info.size].try_into()?)),
-                8 => RegisterValue::U64(u64::from_le_bytes(bytes[info.offset..info.offset + info.size].try_into()?)),
-                _ => return Err(DrbugError::InvalidRegisterSize(info.size)),
-            },
-            RegisterFormat::DoubleFloat => {
-                RegisterValue::F64(f64::from_le_bytes(bytes[info.offset..info.offset + info.size].try_into()?))
-            },
-            RegisterFormat::LongDouble => {
-                unimplemented!("f80 floating point values not supported")
-            },
-            RegisterFormat::Vector => match info.size {
-                8 => RegisterValue::B64(bytes[info.offset..info.offset + info.size].try_into()?),
-                16 => RegisterValue::B128(bytes[info.offset..info.offset + info.size].try_into()?),
-                _ => return Err(DrbugError::InvalidRegisterSize(info.size)),
-            },
-        };
-        Ok(res)
+        decode_value(info, &bytes[info.offset..info.offset + info.size])
+    }
+
+    // The x87 registers laid out as the stack they really are, with TOP and the tag word decoded
+    pub fn x87_stack(&self) -> DrbugResult<X87Stack> {
+        self.check_loaded()?;
+        let fpregs = &self.data.i387;
+        let values = std::array::from_fn(|i| {
+            let bytes: [u8; 10] = as_bytes(&fpregs.st_space)[i * 16..i * 16 + 10].try_into().unwrap();
+            F80::from_le_bytes(bytes)
+        });
+        Ok(X87Stack::new(fpregs.swd, fpregs.ftw, values))
     }
 
+    // Writes only go into the cache; they get sent to the process all at once by `flush`, right
+    // before it resumes
     pub fn write(&mut self, info: &RegisterInfo, val: RegisterValue) -> Empty {
+        self.check_loaded()?;
+        if !info.xstate.is_empty() {
+            return self.write_xstate(info, &val);
+        }
+
+        let wide_val_bytes = widen(&val, info)?;
+        if info.type_ == RegisterType::Debug {
+            self.debug_regs
+                .write(debug_index(info), u64::from_le_bytes(wide_val_bytes[..8].try_into()?));
+            return Ok(());
+        }
+
         // SAFETY: self.data is #[repr(C)], is not null, and valid for reads; it will not be
         // read or mutated while in this block, and the total size is less than isize::MAX
         let bytes: &mut [u8] = as_bytes_mut(&mut self.data);
 
-        let wide_val_bytes = widen(&val, info)?;
-        copy_bytes(&mut bytes[info.offset..], &wide_val_bytes);
+        // The whole area gets written back, so we can only touch the register's own bytes; anything
+        // past the end of it belongs to some other register
+        copy_bytes(&mut bytes[info.offset..info.offset + info.size], &wide_val_bytes[..info.size]);
+
+        match info.type_ {
+            RegisterType::FloatingPoint => self.dirty.fprs = true,
+            _ => self.dirty.gprs = true,
+        }
+        Ok(())
+    }
+
+    // Sends everything that's been written since the last `load_all` out to the process; this has to
+    // happen before it runs again, or before anything goes around the cache to ptrace directly
+    pub(crate) fn flush(&mut self) -> Empty {
+        if self.dirty.gprs {
+            self.commit_gprs()?;
+        }
+
+        // The XSAVE area has its own copy of the floating-point registers, so sending it covers both
+        if self.dirty.xstate {
+            self.commit_xstate()?;
+        } else if self.dirty.fprs {
+            self.commit_fprs()?;
+        }
+
+        self.debug_regs.flush(self.pid)?;
+        self.dirty = DirtyAreas::default();
+        Ok(())
+    }
+
+    // The hardware watchpoints' handle on the debug registers
+    pub(crate) fn debug_registers(&self) -> Rc<DebugRegisters> {
+        self.debug_regs.clone()
+    }
+
+    // Called whenever the process starts running, since none of the values we have are right anymore
+    pub(crate) fn invalidate(&mut self) {
+        self.loaded = false;
+    }
+
+    // Returns the (offset, old value) of every word of the general-purpose and floating-point
+    // registers that's different now than it was in `before`
+    pub(crate) fn changed_words(&self, before: &user) -> Vec<(usize, u64)> {
+        let (old, new) = (as_bytes(before), as_bytes(&self.data));
+        RECORDED_AREAS
+            .iter()
+            .flat_map(|&(start, len)| (start..start + len).step_by(8))
+            .filter(|&offset| old[offset..offset + 8] != new[offset..offset + 8])
+            .map(|offset| (offset, u64::from_le_bytes(old[offset..offset + 8].try_into().unwrap())))
+            .collect()
+    }
+
+    // A copy of the XSAVE area, for `changed_xstate_words` to diff against later
+    pub(crate) fn xstate_snapshot(&self) -> Vec<u8> {
+        self.xstate.clone()
+    }
+
+    // Like `changed_words`, but for the XSAVE area.  The legacy area at the start of it is just
+    // another copy of the floating-point registers, which `changed_words` already covers, so this
+    // starts at the XSAVE header (which has to come along, since it says which components are in use).
+    pub(crate) fn changed_xstate_words(&self, before: &[u8]) -> Vec<(usize, u64)> {
+        (XSTATE_BV_OFFSET..self.xstate.len().min(before.len()))
+            .step_by(8)
+            .filter(|&offset| before[offset..offset + 8] != self.xstate[offset..offset + 8])
+            .map(|offset| (offset, u64::from_le_bytes(before[offset..offset + 8].try_into().unwrap())))
+            .collect()
+    }
+
+    // Returns the current value of every register (other than the pc) that's different now than it
+    // was in `before`; sub-registers are left out, since they'd just repeat what's in the registers
+    // they're part of, and so are the debug registers, which belong to us rather than the program
+    pub(crate) fn changed_registers(&self, before: &user) -> DrbugResult<Vec<(RegisterId, RegisterValue)>> {
+        let (old, new) = (as_bytes(before), as_bytes(&self.data));
+        REGISTER_INFOS
+            .iter()
+            .filter(|info| matches!(info.type_, RegisterType::General | RegisterType::FloatingPoint))
+            .filter(|info| info.id != RegisterId::rip)
+            .filter(|info| old[info.offset..info.offset + info.size] != new[info.offset..info.offset + info.size])
+            .map(|info| self.read(info).map(|val| (info.id, val)))
+            .collect()
+    }
+
+    // Saves the values from the current stop in the history.  This doesn't happen automatically in
+    // `load_all`, since most stops (like stepping over a breakpoint, or a condition that doesn't
+    // hold) are only for the debugger's benefit; it's up to whoever's reporting stops to the user to
+    // call this right after each one.
+    pub fn record_stop(&mut self) -> Empty {
+        self.check_loaded()?;
+        if self.history.len() == HISTORY_LIMIT {
+            self.history.pop_front();
+        }
+        self.history.push_back(self.snapshot());
+        Ok(())
+    }
+
+    // The value of a register at each of the recorded stops, oldest first; only the registers in the
+    // user area are kept, since the XSAVE area is several times bigger and rarely worth the copy
+    pub fn history(&self, info: &RegisterInfo) -> DrbugResult<Vec<RegisterValue>> {
+        if !info.xstate.is_empty() {
+            return Err(DrbugError::NoRegisterHistory(info.name));
+        }
+
+        self.history
+            .iter()
+            .map(|stop| decode_value(info, &as_bytes(stop)[info.offset..info.offset + info.size]))
+            .collect()
+    }
+
+    // Returns true if the register is different now than it was at the stop before this one
+    pub fn changed_since_last_stop(&self, info: &RegisterInfo) -> bool {
+        match self.previous_stop() {
+            Some(before) if info.xstate.is_empty() => {
+                let range = info.offset..info.offset + info.size;
+                as_bytes(before)[range.clone()] != as_bytes(&self.snapshot())[range]
+            },
+            _ => false,
+        }
+    }
 
-        if info.type_ == RegisterType::FloatingPoint {
-            self.commit_fprs()
-        } else {
-            self.commit_user_area(info.offset, &wide_val_bytes)
+    // Every register that's changed since the stop before this one (leaving out the same ones as
+    // `changed_registers`), along with its current value
+    pub fn changes_since_last_stop(&self) -> DrbugResult<Vec<(RegisterId, RegisterValue)>> {
+        match self.previous_stop() {
+            Some(before) => self.changed_registers(before),
+            None => Ok(vec![]),
         }
     }
 
     pub(crate) fn load_all(&mut self) -> Empty {
         self.data.regs = syscall_error!(ptrace::getregs(self.pid))?;
         self.data.i387 = syscall_error!(ptrace::getfpregs(self.pid))?;
-        for (i, dr) in DEBUG_REGISTER_IDS.iter().enumerate() {
-            let info = register_info_by_id(dr);
-            let val = syscall_error!(ptrace::read_user(self.pid, info.offset as AddressType))?;
-            self.data.u_debugreg[i] = val as u64;
+        self.debug_regs.load(self.pid)?;
+        if !self.xstate.is_empty() {
+            self.load_xstate()?;
+        }
+        self.dirty = DirtyAreas::default();
+        self.loaded = true;
+        Ok(())
+    }
+
+    // Puts back the words from `changed_words`; like any other write, they don't get to the process
+    // until the next `flush`
+    pub(crate) fn restore_words(&mut self, words: &[(usize, u64)]) {
+        let bytes: &mut [u8] = as_bytes_mut(&mut self.data);
+        for (offset, word) in words {
+            bytes[*offset..*offset + 8].copy_from_slice(&word.to_le_bytes());
+        }
+        self.dirty.gprs = true;
+        self.dirty.fprs = true;
+    }
+
+    // ...and the same for the words from `changed_xstate_words`
+    pub(crate) fn restore_xstate_words(&mut self, words: &[(usize, u64)]) {
+        for (offset, word) in words {
+            self.xstate[*offset..*offset + 8].copy_from_slice(&word.to_le_bytes());
+        }
+        if !words.is_empty() {
+            self.dirty.xstate = true;
+        }
+    }
+
+    // Stitches together the pieces of an XSAVE register; the legacy (SSE) parts come out of the user
+    // area instead of the XSAVE area, since that's the copy that gets updated when we write an xmm
+    fn read_xstate(&self, info: &RegisterInfo) -> DrbugResult<Vec<u8>> {
+        let layout = XSTATE_LAYOUT.as_ref().filter(|_| info.available());
+        let layout = layout.ok_or(DrbugError::RegisterUnavailable(info.name))?;
+
+        let mut bytes = Vec::with_capacity(info.size);
+        for piece in info.xstate {
+            if piece.component == XstateComponent::Sse {
+                let start = offset_of!(user, i387) + piece.offset;
+                bytes.extend_from_slice(&as_bytes(&self.data)[start..start + piece.size]);
+            } else {
+                let start = layout.offset(piece.component) + piece.offset;
+                bytes.extend_from_slice(&self.xstate[start..start + piece.size]);
+            }
+        }
+        Ok(bytes)
+    }
+
+    fn write_xstate(&mut self, info: &RegisterInfo, val: &RegisterValue) -> Empty {
+        let layout = XSTATE_LAYOUT.as_ref().filter(|_| info.available());
+        let layout = layout.ok_or(DrbugError::RegisterUnavailable(info.name))?;
+        if val.size() > info.size {
+            return Err(DrbugError::InvalidRegisterValue(val.clone()));
+        }
+
+        // widen only knows about the first 16 bytes, which is plenty for the opmask registers
+        let mut bytes: Byte512 = [0; 64];
+        match info.format {
+            RegisterFormat::Vector => bytes = val.into(),
+            _ => copy_bytes(&mut bytes, &widen(val, info)?),
+        }
+
+        // The kernel resets any component that isn't in XSTATE_BV back to its initial state, so we
+        // have to mark the ones we touch (and the legacy area, which we always send a full copy of)
+        let mut xstate_bv = u64::from_le_bytes(self.xstate[XSTATE_BV_OFFSET..XSTATE_BV_OFFSET + 8].try_into()?);
+        xstate_bv |= 0b11; // x87 and SSE
+
+        let mut pos = 0;
+        for piece in info.xstate {
+            let src = &bytes[pos..pos + piece.size];
+            if piece.component == XstateComponent::Sse {
+                let start = offset_of!(user, i387) + piece.offset;
+                as_bytes_mut(&mut self.data)[start..start + piece.size].copy_from_slice(src);
+            } else {
+                let start = layout.offset(piece.component) + piece.offset;
+                self.xstate[start..start + piece.size].copy_from_slice(src);
+                xstate_bv |= 1 << piece.component as u64;
+            }
+            pos += piece.size;
         }
+
+        self.xstate[XSTATE_BV_OFFSET..XSTATE_BV_OFFSET + 8].copy_from_slice(&xstate_bv.to_le_bytes());
+        self.dirty.xstate = true;
         Ok(())
     }
 
-    #[allow(dead_code)] // will use this in a later chapter
+    // The whole user area, with the debug registers filled in from wherever they really are
+    pub(crate) fn snapshot(&self) -> user {
+        let mut data = self.data;
+        data.u_debugreg = self.debug_regs.values.get();
+        data
+    }
+
     fn commit_gprs(&self) -> Empty {
         syscall_error!(ptrace::setregs(self.pid, self.data.regs))
     }
@@ -129,14 +445,83 @@ impl Registers {
         syscall_error!(ptrace::setfpregs(self.pid, self.data.i387))
     }
 
-    fn commit_user_area(&mut self, offset: usize, wide_val_bytes: &Byte128) -> Empty {
-        let aligned_offset = offset & !0b111;
-        syscall_error!(ptrace::write_user(
-            self.pid,
-            aligned_offset as AddressType,
-            i64::from_le_bytes(wide_val_bytes[..8].try_into().unwrap()),
-        ))
+    // nix only knows about the general-purpose and floating-point regsets, so we have to go through
+    // libc for the XSAVE one
+    fn load_xstate(&mut self) -> Empty {
+        let mut iov = iovec {
+            iov_base: self.xstate.as_mut_ptr().cast(),
+            iov_len: self.xstate.len(),
+        };
+        // SAFETY: iov points at a buffer that's iov_len bytes long, and the kernel won't write any
+        // more than that into it
+        let res = unsafe {
+            libc::ptrace(PTRACE_GETREGSET, self.pid.as_raw(), NT_X86_XSTATE as *mut c_void, &mut iov as *mut iovec)
+        };
+        Errno::result(res).map_err(|e| DrbugError::SyscallFailed("ptrace::getregset", e))?;
+        Ok(())
+    }
+
+    fn commit_xstate(&mut self) -> Empty {
+        // The legacy area might have changed since we loaded it (e.g., from writing an xmm register),
+        // so send the up-to-date copy from the user area
+        let legacy = as_bytes(&self.data.i387);
+        self.xstate[..legacy.len()].copy_from_slice(legacy);
+
+        let mut iov = iovec {
+            iov_base: self.xstate.as_mut_ptr().cast(),
+            iov_len: self.xstate.len(),
+        };
+        // SAFETY: iov points at a buffer that's iov_len bytes long, which the kernel only reads from
+        let res = unsafe {
+            libc::ptrace(PTRACE_SETREGSET, self.pid.as_raw(), NT_X86_XSTATE as *mut c_void, &mut iov as *mut iovec)
+        };
+        Errno::result(res).map_err(|e| DrbugError::SyscallFailed("ptrace::setregset", e))?;
+        Ok(())
+    }
+
+    // The newest stop in the history is the one we're at now, so the one before that is the last stop
+    fn previous_stop(&self) -> Option<&user> {
+        self.history.len().checked_sub(2).and_then(|i| self.history.get(i))
+    }
+
+    fn check_loaded(&self) -> Empty {
+        match self.loaded {
+            true => Ok(()),
+            false => Err(DrbugError::RegistersStale),
+        }
+    }
+}
+
+// Turns the raw (little-endian) bytes of a register into a value of the right type
+pub(crate) fn decode_value(info: &RegisterInfo, bytes: &[u8]) -> DrbugResult<RegisterValue> {
+    if bytes.len() != info.size {
+        return Err(DrbugError::InvalidRegisterSize(bytes.len()));
     }
+
+    let res = match info.format {
+        RegisterFormat::Uint => match info.size {
+            1 => RegisterValue::U8(bytes[0]),
+            2 => RegisterValue::U16(u16::from_le_bytes(bytes.try_into()?)),
+            4 => RegisterValue::U32(u32::from_le_bytes(bytes.try_into()?)),
+            8 => RegisterValue::U64(u64::from_le_bytes(bytes.try_into()?)),
+            _ => return Err(DrbugError::InvalidRegisterSize(info.size)),
+        },
+        RegisterFormat::DoubleFloat => RegisterValue::F64(f64::from_le_bytes(bytes.try_into()?)),
+        // The st registers are 16 bytes wide in the user area, but only the bottom 10 are the value
+        RegisterFormat::LongDouble => RegisterValue::F80(F80::from_le_bytes(bytes[..10].try_into()?)),
+        RegisterFormat::Vector => match info.size {
+            8 => RegisterValue::B64(bytes.try_into()?),
+            16 => RegisterValue::B128(bytes.try_into()?),
+            32 => RegisterValue::B256(bytes.try_into()?),
+            64 => RegisterValue::B512(bytes.try_into()?),
+            _ => return Err(DrbugError::InvalidRegisterSize(info.size)),
+        },
+    };
+    Ok(res)
+}
+
+fn debug_index(info: &RegisterInfo) -> usize {
+    (info.offset - offset_of!(user, u_debugreg)) / 8
 }
 
 fn widen(val: &RegisterValue, info: &RegisterInfo) -> DrbugResult<Byte128> {
@@ -148,7 +533,7 @@ fn widen(val: &RegisterValue, info: &RegisterInfo) -> DrbugResult<Byte128> {
         if info.format == RegisterFormat::DoubleFloat {
             return val.cast_to_bytes128::<f64>();
         } else if info.format == RegisterFormat::LongDouble {
-            unimplemented!("f80 floating point values not supported");
+            return val.cast_to_bytes128::<F80>();
         }
     } else if val.is_signed() && info.format == RegisterFormat::Uint {
         return match info.size {
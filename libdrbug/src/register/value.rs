@@ -1,10 +1,13 @@
 use std::fmt;
 
+use super::x87::F80;
 use crate::address::VirtAddr;
 use crate::util::copy_bytes;
 use crate::{
     Byte64,
     Byte128,
+    Byte256,
+    Byte512,
     DrbugError,
     DrbugResult,
 };
@@ -21,8 +24,11 @@ pub enum RegisterValue {
     I64(i64),
     F32(f32),
     F64(f64),
+    F80(F80),
     B64(Byte64),
     B128(Byte128),
+    B256(Byte256),
+    B512(Byte512),
 }
 
 impl RegisterValue {
@@ -38,8 +44,11 @@ impl RegisterValue {
             RegisterValue::I64(_) => size_of::<i64>(),
             RegisterValue::F32(_) => size_of::<f32>(),
             RegisterValue::F64(_) => size_of::<f64>(),
+            RegisterValue::F80(_) => 10,
             RegisterValue::B64(_) => 8,
             RegisterValue::B128(_) => 16,
+            RegisterValue::B256(_) => 32,
+            RegisterValue::B512(_) => 64,
         }
     }
 
@@ -48,7 +57,14 @@ impl RegisterValue {
     }
 
     pub fn is_floating_point(&self) -> bool {
-        matches!(self, RegisterValue::F32(_) | RegisterValue::F64(_))
+        matches!(self, RegisterValue::F32(_) | RegisterValue::F64(_) | RegisterValue::F80(_))
+    }
+
+    pub fn is_vector(&self) -> bool {
+        matches!(
+            self,
+            RegisterValue::B64(_) | RegisterValue::B128(_) | RegisterValue::B256(_) | RegisterValue::B512(_)
+        )
     }
 
     pub fn is_signed(&self) -> bool {
@@ -69,6 +85,7 @@ impl fmt::Display for RegisterValue {
             RegisterValue::I64(val) => write!(f, "0x{val:016x}"),
             RegisterValue::F32(val) => write!(f, "{val}"),
             RegisterValue::F64(val) => write!(f, "{val}"),
+            RegisterValue::F80(val) => write!(f, "{val}"),
             RegisterValue::B64(val) => {
                 let bytes: Vec<String> = val.iter().map(|b| format!("0x{b:02x}")).collect();
                 write!(f, "[{}]", bytes.join(", "))
@@ -77,6 +94,14 @@ impl fmt::Display for RegisterValue {
                 let bytes: Vec<String> = val.iter().map(|b| format!("0x{b:02x}")).collect();
                 write!(f, "[{}]", bytes.join(", "))
             },
+            RegisterValue::B256(val) => {
+                let bytes: Vec<String> = val.iter().map(|b| format!("0x{b:02x}")).collect();
+                write!(f, "[{}]", bytes.join(", "))
+            },
+            RegisterValue::B512(val) => {
+                let bytes: Vec<String> = val.iter().map(|b| format!("0x{b:02x}")).collect();
+                write!(f, "[{}]", bytes.join(", "))
+            },
         }
     }
 }
@@ -143,9 +168,32 @@ impl RegisterValueTarget for f64 {
     }
 }
 
+impl RegisterValueTarget for F80 {
+    fn from_register_value(value: &RegisterValue) -> DrbugResult<RegisterValue> {
+        match value {
+            RegisterValue::F32(v) => Ok(RegisterValue::F80((*v as f64).into())),
+            RegisterValue::F64(v) => Ok(RegisterValue::F80((*v).into())),
+            RegisterValue::F80(v) => Ok(RegisterValue::F80(*v)),
+            x => Err(DrbugError::RegisterValueConversionFailed("f80", x.clone())),
+        }
+    }
+}
+
 impl From<&RegisterValue> for Byte128 {
     fn from(val: &RegisterValue) -> Self {
-        let mut ret = [0; 16];
+        Byte512::from(val)[..16].try_into().unwrap()
+    }
+}
+
+impl From<RegisterValue> for Byte128 {
+    fn from(val: RegisterValue) -> Self {
+        Byte128::from(&val)
+    }
+}
+
+impl From<&RegisterValue> for Byte512 {
+    fn from(val: &RegisterValue) -> Self {
+        let mut ret = [0; 64];
         match val {
             RegisterValue::U8(v) => copy_bytes(&mut ret, v),
             RegisterValue::U16(v) => copy_bytes(&mut ret, v),
@@ -157,22 +205,25 @@ impl From<&RegisterValue> for Byte128 {
             RegisterValue::I64(v) => copy_bytes(&mut ret, v),
             RegisterValue::F32(v) => copy_bytes(&mut ret, v),
             RegisterValue::F64(v) => copy_bytes(&mut ret, v),
+            RegisterValue::F80(v) => copy_bytes(&mut ret, &v.to_le_bytes()),
             RegisterValue::B64(v) => copy_bytes(&mut ret, v),
             RegisterValue::B128(v) => copy_bytes(&mut ret, v),
+            RegisterValue::B256(v) => copy_bytes(&mut ret, v),
+            RegisterValue::B512(v) => copy_bytes(&mut ret, v),
         }
         ret
     }
 }
 
-impl From<RegisterValue> for Byte128 {
+impl From<RegisterValue> for Byte512 {
     fn from(val: RegisterValue) -> Self {
-        Byte128::from(&val)
+        Byte512::from(&val)
     }
 }
 
 impl PartialEq for RegisterValue {
     fn eq(&self, other: &Self) -> bool {
-        Into::<Byte128>::into(self) == Into::<Byte128>::into(other)
+        Into::<Byte512>::into(self) == Into::<Byte512>::into(other)
     }
 }
 
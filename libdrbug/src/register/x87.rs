@@ -0,0 +1,210 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::DrbugError;
+
+const EXPONENT_BIAS_80: i32 = 16383;
+const EXPONENT_BIAS_64: i32 = 1023;
+const INTEGER_BIT: u64 = 1 << 63;
+
+// An x87 80-bit extended-precision float: a sign bit, a 15-bit exponent, and a 64-bit mantissa
+// with an explicit integer bit (unlike f32 and f64, where the leading 1 is implied).  Rust doesn't
+// have a type for these, so we keep the raw bytes around and convert to f64 when we need to do
+// anything with the value; every f64 fits exactly, but going the other way loses precision.  So
+// that nothing gets lost, they're also printed and parsed as the raw 80 bits, written as one hex
+// number with the sign and exponent first (e.g., `0x3fff8000000000000000` is 1.0).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct F80([u8; 10]);
+
+impl F80 {
+    pub fn from_le_bytes(bytes: [u8; 10]) -> Self {
+        F80(bytes)
+    }
+
+    pub fn to_le_bytes(self) -> [u8; 10] {
+        self.0
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.exponent() == 0 && self.mantissa() == 0
+    }
+
+    // NaNs, infinities, denormals, and unnormals (a nonzero exponent without the integer bit),
+    // which is what the x87 tag word calls "special"
+    pub fn is_special(&self) -> bool {
+        match self.exponent() {
+            0x7fff => true,
+            0 => self.mantissa() != 0,
+            _ => self.mantissa() & INTEGER_BIT == 0,
+        }
+    }
+
+    pub fn to_f64(self) -> f64 {
+        let sign = if self.sign() { -1.0 } else { 1.0 };
+        let (exponent, mantissa) = (self.exponent() as i32, self.mantissa());
+        let magnitude = match exponent {
+            0x7fff if mantissa & !INTEGER_BIT == 0 => f64::INFINITY,
+            0x7fff => f64::NAN,
+            // Denormals use the same exponent as the smallest normal number
+            0 => scale(mantissa as f64, 1 - EXPONENT_BIAS_80 - 63),
+            _ => scale(mantissa as f64, exponent - EXPONENT_BIAS_80 - 63),
+        };
+        sign * magnitude
+    }
+
+    fn sign(&self) -> bool {
+        self.0[9] & 0x80 != 0
+    }
+
+    fn exponent(&self) -> u16 {
+        u16::from_le_bytes([self.0[8], self.0[9]]) & 0x7fff
+    }
+
+    fn mantissa(&self) -> u64 {
+        u64::from_le_bytes(self.0[..8].try_into().unwrap())
+    }
+
+    fn from_parts(sign: bool, exponent: u16, mantissa: u64) -> Self {
+        let mut bytes = [0; 10];
+        bytes[..8].copy_from_slice(&mantissa.to_le_bytes());
+        bytes[8..].copy_from_slice(&((sign as u16) << 15 | exponent).to_le_bytes());
+        F80(bytes)
+    }
+}
+
+impl From<f64> for F80 {
+    fn from(val: f64) -> Self {
+        let bits = val.to_bits();
+        let sign = bits >> 63 != 0;
+        let exponent = ((bits >> 52) & 0x7ff) as i32;
+        let mantissa = bits & ((1 << 52) - 1);
+
+        match exponent {
+            0 if mantissa == 0 => F80::from_parts(sign, 0, 0),
+            0 => {
+                // f64 denormals are normal numbers as far as f80 is concerned, we just have to
+                // shift the mantissa up until the integer bit is set
+                let shift = mantissa.leading_zeros() as i32;
+                let exponent = EXPONENT_BIAS_80 + 63 - 1074 - shift;
+                F80::from_parts(sign, exponent as u16, mantissa << shift)
+            },
+            0x7ff => F80::from_parts(sign, 0x7fff, INTEGER_BIT | mantissa << 11),
+            _ => {
+                let exponent = exponent - EXPONENT_BIAS_64 + EXPONENT_BIAS_80;
+                F80::from_parts(sign, exponent as u16, INTEGER_BIT | mantissa << 11)
+            },
+        }
+    }
+}
+
+impl From<F80> for f64 {
+    fn from(val: F80) -> Self {
+        val.to_f64()
+    }
+}
+
+impl FromStr for F80 {
+    type Err = DrbugError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let trimmed = s.trim();
+        if let Some(hex) = trimmed.strip_prefix("0x").or_else(|| trimmed.strip_prefix("0X")) {
+            if hex.len() != 20 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+                return Err(DrbugError::InvalidFloat(s.into()));
+            }
+            let raw = u128::from_str_radix(hex, 16)?;
+            return Ok(F80(raw.to_le_bytes()[..10].try_into().unwrap()));
+        }
+
+        let val: f64 = trimmed.parse().map_err(|_| DrbugError::InvalidFloat(s.into()))?;
+        Ok(val.into())
+    }
+}
+
+impl fmt::Display for F80 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let high = u16::from_le_bytes([self.0[8], self.0[9]]);
+        write!(f, "{} (0x{high:04x}{:016x})", self.to_f64(), self.mantissa())
+    }
+}
+
+// Multiplies by a power of two that might be too big (or small) for an f64 on its own
+fn scale(mut val: f64, mut exp: i32) -> f64 {
+    while exp > 1000 {
+        val *= 2f64.powi(1000);
+        exp -= 1000;
+    }
+    while exp < -1000 {
+        val *= 2f64.powi(-1000);
+        exp += 1000;
+    }
+    val * 2f64.powi(exp)
+}
+
+// What the full x87 tag word would say about each register.  FXSAVE (which is what ptrace gives
+// us) only keeps one bit per register, valid or empty, so for the valid ones we work the rest out
+// from the value, the same way the processor does when it expands the tag word again.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum X87Tag {
+    Valid,
+    Zero,
+    Special,
+    Empty,
+}
+
+impl fmt::Display for X87Tag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            X87Tag::Valid => write!(f, "valid"),
+            X87Tag::Zero => write!(f, "zero"),
+            X87Tag::Special => write!(f, "special"),
+            X87Tag::Empty => write!(f, "empty"),
+        }
+    }
+}
+
+// The x87 registers are a stack: st0 is whichever physical register TOP (from the status word)
+// points at, st1 is the one after that, and so on, wrapping around at 8
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct X87Stack {
+    pub top: u8,
+    pub registers: Vec<X87Register>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct X87Register {
+    pub st: u8,
+    pub physical: u8,
+    pub tag: X87Tag,
+    pub value: F80,
+}
+
+impl X87Stack {
+    // `values` are in stack order (st0 first), which is how FXSAVE lays them out, but the tag bits
+    // are in physical register order
+    pub(crate) fn new(fsw: u16, ftw: u16, values: [F80; 8]) -> Self {
+        let top = ((fsw >> 11) & 0x7) as u8;
+        let registers = (0..8)
+            .map(|st| {
+                let physical = (top + st) % 8;
+                let value = values[st as usize];
+                let tag = if ftw & (1 << physical) == 0 {
+                    X87Tag::Empty
+                } else if value.is_zero() {
+                    X87Tag::Zero
+                } else if value.is_special() {
+                    X87Tag::Special
+                } else {
+                    X87Tag::Valid
+                };
+                X87Register { st, physical, tag, value }
+            })
+            .collect();
+        X87Stack { top, registers }
+    }
+
+    // The registers that actually have something in them
+    pub fn depth(&self) -> usize {
+        self.registers.iter().filter(|reg| reg.tag != X87Tag::Empty).count()
+    }
+}
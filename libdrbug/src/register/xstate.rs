@@ -0,0 +1,87 @@
+use std::arch::asm;
+use std::arch::x86_64::__cpuid_count;
+use std::sync::LazyLock;
+
+// The note type for the whole XSAVE area in PTRACE_GETREGSET/SETREGSET
+pub(super) const NT_X86_XSTATE: usize = 0x202;
+
+// The XSTATE_BV field of the XSAVE header, which says which components are actually in the buffer;
+// anything not listed there is in its initial state (all zeros, for the ones we care about)
+pub(super) const XSTATE_BV_OFFSET: usize = 512;
+
+const OSXSAVE_BIT: u32 = 1 << 27;
+
+// The XSAVE components that registers live in; the discriminants are the component's bit in XCR0.
+// The SSE component is the legacy FXSAVE area, which we already have a copy of in the user area.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum XstateComponent {
+    Sse = 1,
+    Avx = 2,
+    Opmask = 5,
+    ZmmHi256 = 6,
+    Hi16Zmm = 7,
+}
+
+// A chunk of a register, `offset` bytes into its component.  Registers like zmm0 are stitched
+// together from up to three different components, low bytes first.
+#[derive(Debug)]
+pub(crate) struct XstatePiece {
+    pub(crate) component: XstateComponent,
+    pub(crate) offset: usize,
+    pub(crate) size: usize,
+}
+
+// Where the components are on this machine; in the standard (non-compacted) format that ptrace
+// uses, the offsets are fixed per-processor but not architecturally, so we have to ask cpuid
+#[derive(Debug)]
+pub(super) struct XstateLayout {
+    pub(super) size: usize,
+    xcr0: u64,
+    offsets: [usize; 8],
+}
+
+pub(super) static XSTATE_LAYOUT: LazyLock<Option<XstateLayout>> = LazyLock::new(XstateLayout::detect);
+
+impl XstateLayout {
+    fn detect() -> Option<Self> {
+        // If the OS hasn't turned on XSAVE, xgetbv is an illegal instruction, and there's no XSTATE
+        // regset for us to read anyways
+        if __cpuid_count(1, 0).ecx & OSXSAVE_BIT == 0 {
+            return None;
+        }
+
+        let mut offsets = [0; 8];
+        for (i, offset) in offsets.iter_mut().enumerate().skip(2) {
+            *offset = __cpuid_count(0xd, i as u32).ebx as usize;
+        }
+
+        Some(XstateLayout {
+            size: __cpuid_count(0xd, 0).ebx as usize,
+            xcr0: xgetbv(),
+            offsets,
+        })
+    }
+
+    pub(super) fn enabled(&self, component: XstateComponent) -> bool {
+        self.xcr0 & (1 << component as u64) != 0
+    }
+
+    pub(super) fn offset(&self, component: XstateComponent) -> usize {
+        self.offsets[component as usize]
+    }
+}
+
+// Returns true if the processor and OS both support `component`
+pub(super) fn component_enabled(component: XstateComponent) -> bool {
+    XSTATE_LAYOUT.as_ref().is_some_and(|layout| layout.enabled(component))
+}
+
+fn xgetbv() -> u64 {
+    let (lo, hi): (u32, u32);
+    // SAFETY: we only get here if cpuid says the OS has enabled XSAVE, which is when xgetbv is
+    // allowed; reading XCR0 doesn't touch memory or anything else
+    unsafe {
+        asm!("xgetbv", in("ecx") 0, out("eax") lo, out("edx") hi, options(nomem, nostack, preserves_flags));
+    }
+    (hi as u64) << 32 | lo as u64
+}
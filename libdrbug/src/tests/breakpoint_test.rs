@@ -7,10 +7,16 @@ use super::*;
 use crate::DrbugError;
 use crate::breakpoint::Breakable;
 use crate::pipe::Pipe;
+use crate::register::info::{
+    RegisterId,
+    register_info_by_id,
+};
 use crate::tests::util::{
+    addr_from_bytes,
     get_entry_point_offset,
     get_load_addr,
 };
+use crate::util::glob_match;
 
 #[rstest]
 fn test_create_breakpoint_site() -> Empty {
@@ -137,3 +143,444 @@ fn test_remove_breakpoints() -> Empty {
     assert_is_empty!(proc.breakpoint_sites());
     Ok(())
 }
+
+#[rstest]
+#[case("rip == 0", false)]
+#[case("*(u64*)rsp == 1", true)] // argc is on the top of the stack at the entry point
+fn test_conditional_breakpoint(#[case] condition: &str, #[case] should_stop: bool) -> Empty {
+    let mut channel = Pipe::new()?;
+    let opts = ProcessOptions {
+        stdout: channel.take_writer().map(|w| w.into()),
+        ..Default::default()
+    };
+    let mut proc = Process::launch(HELLO_PATH, opts)?;
+    let offset = get_entry_point_offset(Path::new(HELLO_PATH));
+    let load_addr = get_load_addr(proc.pid(), offset);
+
+    let mut site = proc.create_breakpoint_site(load_addr)?;
+    site.set_condition(Some(condition.parse()?));
+    site.enable()?;
+    proc.resume()?;
+    let reason = proc.wait_on_signal()?;
+
+    if should_stop {
+        assert_matches!(reason, ProcessState::Stopped { signal: Some(Signal::SIGTRAP) });
+        assert_eq!(proc.get_pc()?, load_addr);
+        proc.resume()?;
+        let reason = proc.wait_on_signal()?;
+        assert_matches!(reason, ProcessState::Exited { exit_code: 0 });
+    } else {
+        assert_matches!(reason, ProcessState::Exited { exit_code: 0 });
+    }
+
+    let data = channel.read()?;
+    assert_eq!(str::from_utf8(&data).unwrap(), "Hello, drb!\n");
+    Ok(())
+}
+
+#[rstest]
+#[case(None, 4, 4, 5)]
+#[case(Some("rdi % 2 == 1"), 1, 3, 2)] // only hits that pass the condition are counted
+fn test_breakpoint_ignore_count(
+    #[case] condition: Option<&str>,
+    #[case] ignore: usize,
+    #[case] expected_rdi: u64,
+    #[case] expected_hits: usize,
+) -> Empty {
+    let mut channel = Pipe::new()?;
+    let opts = ProcessOptions {
+        stdout: channel.take_writer().map(|w| w.into()),
+        ..Default::default()
+    };
+    let mut proc = Process::launch(COUNT_PATH, opts)?;
+    proc.resume()?;
+    proc.wait_on_signal()?;
+    let tick_addr = addr_from_bytes(&channel.read()?)?;
+
+    let mut site = proc.create_breakpoint_site(tick_addr)?;
+    site.set_condition(condition.map(str::parse::<Expression>).transpose()?);
+    site.set_ignore_count(ignore);
+    site.enable()?;
+    proc.resume()?;
+    let reason = proc.wait_on_signal()?;
+
+    assert_matches!(reason, ProcessState::Stopped { signal: Some(Signal::SIGTRAP) });
+    assert_eq!(proc.get_pc()?, tick_addr);
+    let rdi = proc.get_registers().read(register_info_by_id(&RegisterId::rdi))?;
+    assert_eq!(rdi, RegisterValue::U64(expected_rdi));
+    assert_eq!(site.hit_count(), expected_hits);
+    assert_eq!(site.ignore_count(), 0);
+    Ok(())
+}
+
+#[rstest]
+fn test_breakpoint_disable_after() -> Empty {
+    let mut channel = Pipe::new()?;
+    let opts = ProcessOptions {
+        stdout: channel.take_writer().map(|w| w.into()),
+        ..Default::default()
+    };
+    let mut proc = Process::launch(COUNT_PATH, opts)?;
+    proc.resume()?;
+    proc.wait_on_signal()?;
+    let tick_addr = addr_from_bytes(&channel.read()?)?;
+
+    let mut site = proc.create_breakpoint_site(tick_addr)?;
+    site.set_disable_after(Some(2));
+    site.enable()?;
+
+    for _ in 0..2 {
+        proc.resume()?;
+        let reason = proc.wait_on_signal()?;
+        assert_matches!(reason, ProcessState::Stopped { signal: Some(Signal::SIGTRAP) });
+        assert_eq!(proc.get_pc()?, tick_addr);
+    }
+    assert!(!site.enabled());
+    assert_none!(site.disable_after());
+
+    proc.resume()?;
+    let reason = proc.wait_on_signal()?;
+    assert_matches!(reason, ProcessState::Exited { exit_code: 0 });
+    assert_eq!(site.hit_count(), 2);
+
+    let data = channel.read()?;
+    assert_eq!(str::from_utf8(&data).unwrap(), "45");
+    Ok(())
+}
+
+#[rstest]
+fn test_temporary_breakpoint() -> Empty {
+    let mut proc = Process::launch(HELLO_PATH, Default::default())?;
+    let offset = get_entry_point_offset(Path::new(HELLO_PATH));
+    let load_addr = get_load_addr(proc.pid(), offset);
+
+    let mut site = proc.create_temporary_breakpoint_site(load_addr)?;
+    assert!(site.is_temporary());
+    site.enable()?;
+    proc.resume()?;
+    let reason = proc.wait_on_signal()?;
+
+    assert_matches!(reason, ProcessState::Stopped { signal: Some(Signal::SIGTRAP) });
+    assert_eq!(proc.get_pc()?, load_addr);
+    assert_is_empty!(proc.breakpoint_sites());
+    assert_eq!(site.hit_count(), 1);
+
+    proc.resume()?;
+    let reason = proc.wait_on_signal()?;
+    assert_matches!(reason, ProcessState::Exited { exit_code: 0 });
+    Ok(())
+}
+
+#[rstest]
+fn test_temporary_breakpoint_outlives_its_stop() -> Empty {
+    let mut channel = Pipe::new()?;
+    let opts = ProcessOptions {
+        stdout: channel.take_writer().map(|w| w.into()),
+        ..Default::default()
+    };
+    let mut proc = Process::launch(COUNT_PATH, opts)?;
+    proc.resume()?;
+    proc.wait_on_signal()?;
+    let tick_addr = addr_from_bytes(&channel.read()?)?;
+
+    let mut bp = proc.create_temporary_breakpoint(BreakpointLocation::Address(tick_addr))?;
+    bp.enable()?;
+    proc.resume()?;
+    proc.wait_on_signal()?;
+
+    // Still there while we're stopped at it, so the stop can be reported, but it can't stop the
+    // process again
+    assert_eq!(proc.get_pc()?, tick_addr);
+    assert_some_eq_x!(proc.triggered_breakpoint(), &bp);
+    assert_some!(proc.breakpoint(&bp.id()));
+    assert!(!bp.enabled());
+
+    proc.resume()?;
+    let reason = proc.wait_on_signal()?;
+    assert_matches!(reason, ProcessState::Exited { exit_code: 0 });
+    assert_none!(proc.breakpoint(&bp.id()));
+    assert_eq!(bp.hit_count(), 1);
+    assert_eq!(str::from_utf8(&channel.read()?).unwrap(), "45");
+    Ok(())
+}
+
+#[rstest]
+fn test_run_to() -> Empty {
+    let mut channel = Pipe::new()?;
+    let opts = ProcessOptions {
+        stdout: channel.take_writer().map(|w| w.into()),
+        ..Default::default()
+    };
+    let mut proc = Process::launch(COUNT_PATH, opts)?;
+    proc.resume()?;
+    proc.wait_on_signal()?;
+    let tick_addr = addr_from_bytes(&channel.read()?)?;
+
+    let reason = proc.run_to(tick_addr)?;
+
+    assert_matches!(reason, ProcessState::Stopped { signal: Some(Signal::SIGTRAP) });
+    assert_eq!(proc.get_pc()?, tick_addr);
+    assert_is_empty!(proc.breakpoint_sites());
+    Ok(())
+}
+
+// A user breakpoint at the same address doesn't get a say in whether we stop there, and the stop
+// isn't one of its hits
+#[rstest]
+#[case(Some("rdi == 5"), 0)]
+#[case(None, 3)]
+fn test_run_to_user_breakpoint(#[case] condition: Option<&str>, #[case] ignore: usize) -> Empty {
+    let mut channel = Pipe::new()?;
+    let opts = ProcessOptions {
+        stdout: channel.take_writer().map(|w| w.into()),
+        ..Default::default()
+    };
+    let mut proc = Process::launch(COUNT_PATH, opts)?;
+    proc.resume()?;
+    proc.wait_on_signal()?;
+    let tick_addr = addr_from_bytes(&channel.read()?)?;
+
+    let mut site = proc.create_breakpoint_site(tick_addr)?;
+    site.set_condition(condition.map(str::parse::<Expression>).transpose()?);
+    site.set_ignore_count(ignore);
+    site.enable()?;
+    let reason = proc.run_to(tick_addr)?;
+
+    assert_matches!(reason, ProcessState::Stopped { signal: Some(Signal::SIGTRAP) });
+    assert_eq!(proc.get_pc()?, tick_addr);
+    let rdi = proc.get_registers().read(register_info_by_id(&RegisterId::rdi))?;
+    assert_eq!(rdi, RegisterValue::U64(0));
+    assert_eq!(site.hit_count(), 0);
+    assert!(site.enabled());
+    assert_len_eq_x!(proc.breakpoint_sites(), 1);
+    Ok(())
+}
+
+#[rstest]
+fn test_run_to_stops_elsewhere() -> Empty {
+    let mut channel = Pipe::new()?;
+    let opts = ProcessOptions {
+        stdout: channel.take_writer().map(|w| w.into()),
+        ..Default::default()
+    };
+    let mut proc = Process::launch(COUNT_PATH, opts)?;
+    let entry_addr = get_load_addr(proc.pid(), get_entry_point_offset(Path::new(COUNT_PATH)));
+    proc.resume()?;
+    proc.wait_on_signal()?;
+    let tick_addr = addr_from_bytes(&channel.read()?)?;
+
+    // The entry point never runs again, so we stop at the breakpoint on `tick` instead, and the
+    // temporary breakpoint shouldn't be left behind
+    let mut site = proc.create_breakpoint_site(tick_addr)?;
+    site.enable()?;
+    let reason = proc.run_to(entry_addr)?;
+
+    assert_matches!(reason, ProcessState::Stopped { signal: Some(Signal::SIGTRAP) });
+    assert_eq!(proc.get_pc()?, tick_addr);
+    assert_len_eq_x!(proc.breakpoint_sites(), 1);
+    assert_some_eq_x!(proc.breakpoint_sites().get_by_addr(&tick_addr), site);
+    Ok(())
+}
+
+#[rstest]
+#[case("0x401000", BreakpointLocation::Address(VirtAddr(0x401000)))]
+#[case("401000", BreakpointLocation::Address(VirtAddr(0x401000)))]
+#[case("main", BreakpointLocation::Function("main".into()))]
+#[case("deadbeef", BreakpointLocation::Function("deadbeef".into()))]
+#[case("*parse*", BreakpointLocation::Pattern("*parse*".into()))]
+#[case("libc.so.6+0x1139", BreakpointLocation::ModuleOffset { module: "libc.so.6".into(), offset: 0x1139 })]
+#[case("/lib/a+b.so+1139", BreakpointLocation::ModuleOffset { module: "/lib/a+b.so".into(), offset: 0x1139 })]
+#[case("tick?", BreakpointLocation::Pattern("tick?".into()))]
+#[case("*operator+*", BreakpointLocation::Pattern("*operator+*".into()))]
+#[case("operator+", BreakpointLocation::Function("operator+".into()))]
+#[case("operator+=", BreakpointLocation::Function("operator+=".into()))]
+#[case("libc.so.6+main", BreakpointLocation::Function("libc.so.6+main".into()))]
+fn test_parse_breakpoint_location(#[case] input: &str, #[case] expected: BreakpointLocation) -> Empty {
+    assert_eq!(input.parse::<BreakpointLocation>()?, expected);
+    Ok(())
+}
+
+#[rstest]
+#[case("")]
+#[case("0xnope")]
+fn test_parse_breakpoint_location_fails(#[case] input: &str) {
+    assert_err!(input.parse::<BreakpointLocation>());
+}
+
+#[rstest]
+#[case("*", "anything", true)]
+#[case("main", "main", true)]
+#[case("main", "main2", false)]
+#[case("*tick*", "_ZN5count4tick17h0123456789abcdefE", true)]
+#[case("tick?", "tick1", true)]
+#[case("tick?", "tick", false)]
+#[case("a*b*c", "aXbYbZc", true)]
+#[case("a*b*c", "aXbYcZ", false)]
+fn test_glob_match(#[case] pattern: &str, #[case] text: &str, #[case] expected: bool) {
+    assert_eq!(glob_match(pattern, text), expected);
+}
+
+#[rstest]
+fn test_pattern_breakpoint() -> Empty {
+    let mut channel = Pipe::new()?;
+    let opts = ProcessOptions {
+        stdout: channel.take_writer().map(|w| w.into()),
+        ..Default::default()
+    };
+    let mut proc = Process::launch(COUNT_PATH, opts)?;
+    proc.resume()?;
+    proc.wait_on_signal()?;
+    let tick_addr = addr_from_bytes(&channel.read()?)?;
+
+    // Rust symbol names are mangled, so `tick` is really something like _ZN5count4tick17h...E
+    let mut bp = proc.create_breakpoint("*count4tick*".parse()?)?;
+    assert!(!bp.is_pending());
+    assert_len_eq_x!(bp.sites(), 1);
+    assert_eq!(bp.sites()[0].addr(), tick_addr);
+    assert_some_eq_x!(bp.sites()[0].breakpoint_id(), bp.id());
+
+    bp.enable()?;
+    for _ in 0..2 {
+        proc.resume()?;
+        let reason = proc.wait_on_signal()?;
+        assert_matches!(reason, ProcessState::Stopped { signal: Some(Signal::SIGTRAP) });
+        assert_eq!(proc.get_pc()?, tick_addr);
+    }
+    assert_eq!(bp.hit_count(), 2);
+    assert_eq!(bp.sites()[0].hit_count(), 2);
+
+    // Disabling the breakpoint disables all of its sites
+    bp.disable()?;
+    assert!(bp.sites().iter().all(|site| !site.enabled()));
+    proc.resume()?;
+    let reason = proc.wait_on_signal()?;
+    assert_matches!(reason, ProcessState::Exited { exit_code: 0 });
+    assert_eq!(str::from_utf8(&channel.read()?).unwrap(), "45");
+    Ok(())
+}
+
+#[rstest]
+fn test_pending_breakpoint() -> Empty {
+    let mut proc = Process::launch(COUNT_PATH, Default::default())?;
+    let mut bp = proc.create_breakpoint(BreakpointLocation::Function("no_such_function".into()))?;
+    bp.enable()?;
+
+    assert!(bp.is_pending());
+    assert!(bp.enabled());
+    assert_is_empty!(proc.breakpoint_sites());
+    assert_some_eq_x!(proc.breakpoint(&bp.id()), bp);
+    Ok(())
+}
+
+#[rstest]
+fn test_remove_breakpoint() -> Empty {
+    let mut proc = Process::launch(LOOP_PATH, Default::default())?;
+    let bp = proc.create_breakpoint(BreakpointLocation::Address(VirtAddr(42)))?;
+    assert_len_eq_x!(proc.breakpoint_sites(), 1);
+    assert_err!(proc.create_breakpoint(BreakpointLocation::Address(VirtAddr(42))));
+
+    assert_some_eq_x!(proc.remove_breakpoint(&bp.id())?, bp);
+    assert_none!(proc.breakpoint(&bp.id()));
+    assert_is_empty!(proc.breakpoint_sites());
+    assert_none!(proc.remove_breakpoint(&bp.id())?);
+    Ok(())
+}
+
+#[rstest]
+fn test_breakpoint_in_dlopened_library() -> Empty {
+    let mut channel = Pipe::new()?;
+    let opts = ProcessOptions {
+        stdout: channel.take_writer().map(|w| w.into()),
+        ..Default::default()
+    };
+    let mut proc = Process::launch(DLOPEN_PATH, opts)?;
+    proc.resume()?;
+    proc.wait_on_signal()?;
+
+    let mut bp = proc.create_breakpoint(BreakpointLocation::Function("plugin_fn".into()))?;
+    bp.enable()?;
+    assert!(bp.is_pending());
+
+    // The breakpoint gets resolved when the library is loaded...
+    proc.resume()?;
+    let reason = proc.wait_on_signal()?;
+    assert_matches!(reason, ProcessState::Stopped { signal: Some(Signal::SIGTRAP) });
+    assert_eq!(proc.trap_type(), Some(TrapType::SoftwareBreak));
+    assert_len_eq_x!(bp.sites(), 1);
+    assert_eq!(proc.get_pc()?, bp.sites()[0].addr());
+    assert_eq!(bp.hit_count(), 1);
+
+    // ...and goes back to pending once it's unloaded
+    proc.resume()?;
+    let reason = proc.wait_on_signal()?;
+    assert_matches!(reason, ProcessState::Stopped { signal: Some(Signal::SIGTRAP) });
+    assert!(bp.is_pending());
+    assert_is_empty!(proc.breakpoint_sites());
+
+    proc.resume()?;
+    let reason = proc.wait_on_signal()?;
+    assert_matches!(reason, ProcessState::Exited { exit_code: 0 });
+    assert_eq!(str::from_utf8(&channel.read()?).unwrap(), "42");
+    Ok(())
+}
+
+#[rstest]
+fn test_module_location_survives_relaunch() -> Empty {
+    let mut location = None;
+    for _ in 0..2 {
+        let mut channel = Pipe::new()?;
+        let opts = ProcessOptions {
+            stdout: channel.take_writer().map(|w| w.into()),
+            ..Default::default()
+        };
+        let mut proc = Process::launch(COUNT_PATH, opts)?;
+        proc.resume()?;
+        proc.wait_on_signal()?;
+        let tick_addr = addr_from_bytes(&channel.read()?)?;
+
+        // The first run figures out the module offset, and the second one (which is probably
+        // loaded somewhere else, thanks to ASLR) checks that it still points at `tick`
+        let location = location.get_or_insert_with(|| proc.module_location(tick_addr).unwrap().unwrap());
+        assert_matches!(location, BreakpointLocation::ModuleOffset { module, .. } if module.ends_with("/count"));
+
+        let bp = proc.create_breakpoint(location.clone())?;
+        assert_len_eq_x!(bp.sites(), 1);
+        assert_eq!(bp.sites()[0].addr(), tick_addr);
+    }
+    Ok(())
+}
+
+#[rstest]
+fn test_restart_keeps_breakpoints() -> Empty {
+    let mut channel = Pipe::new()?;
+    let opts = ProcessOptions {
+        stdout: channel.take_writer().map(|w| w.into()),
+        ..Default::default()
+    };
+    let mut proc = Process::launch(COUNT_PATH, opts)?;
+    proc.resume()?;
+    proc.wait_on_signal()?;
+    let tick_addr = addr_from_bytes(&channel.read()?)?;
+    let mut bp = proc.create_breakpoint(BreakpointLocation::Address(tick_addr))?;
+    bp.enable()?;
+    let bp_id = bp.id();
+
+    // The new process (probably) gets loaded somewhere else, so the breakpoint has to follow `tick`
+    // rather than staying at the old address
+    let old_pid = proc.pid();
+    proc.restart(None)?;
+    assert_ne!(proc.pid(), old_pid);
+    proc.resume()?;
+    proc.wait_on_signal()?;
+    let tick_addr = addr_from_bytes(&channel.read()?)?;
+
+    let bp = proc.breakpoint(&bp_id).unwrap();
+    assert!(bp.enabled());
+    assert_len_eq_x!(bp.sites(), 1);
+    assert_eq!(bp.sites()[0].addr(), tick_addr);
+
+    proc.resume()?;
+    proc.wait_on_signal()?;
+    assert_eq!(proc.get_pc()?, tick_addr);
+    Ok(())
+}
@@ -0,0 +1,72 @@
+use std::path::PathBuf;
+
+use super::*;
+use crate::DrbugResult;
+use crate::pipe::Pipe;
+use crate::process::ProcessOptions;
+use crate::tests::util::addr_from_bytes;
+
+fn coverage_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("drbug-{name}-{}.drcov", std::process::id()))
+}
+
+// Returns the module base from the header, and the (offset, size) of every block in the table
+fn parse_drcov(data: &[u8]) -> DrbugResult<(u64, Vec<(u32, u16)>)> {
+    let marker = b"BB Table: ";
+    let table = data.windows(marker.len()).position(|w| w == marker).unwrap();
+    let header = String::from_utf8_lossy(&data[..table]);
+    let module = header.lines().find(|line| line.starts_with(" 0, ")).unwrap();
+    let base = u64::from_str_radix(module.split(", ").nth(1).unwrap().trim_start_matches("0x"), 16)?;
+
+    let newline = table + data[table..].iter().position(|&b| b == b'\n').unwrap();
+    let count: usize = String::from_utf8_lossy(&data[table + marker.len()..newline])
+        .trim_end_matches(" bbs")
+        .parse()?;
+    let blocks: Vec<_> = data[newline + 1..]
+        .chunks_exact(8)
+        .map(|entry| {
+            (
+                u32::from_le_bytes(entry[..4].try_into().unwrap()),
+                u16::from_le_bytes(entry[4..6].try_into().unwrap()),
+            )
+        })
+        .collect();
+    assert_eq!(blocks.len(), count);
+    Ok((base, blocks))
+}
+
+#[rstest]
+fn test_coverage_drcov_output() -> Empty {
+    let path = coverage_path("recurse");
+    let mut channel = Pipe::new()?;
+    let opts = ProcessOptions {
+        stdout: channel.take_writer().map(|w| w.into()),
+        coverage: Some(path.clone()),
+        ..Default::default()
+    };
+    let mut proc = Process::launch(RECURSE_PATH, opts)?;
+    proc.resume()?;
+    proc.wait_on_signal()?;
+    let depth_addr = addr_from_bytes(&channel.read()?)?;
+
+    // `depth` gets called directly, so it's the start of a block, and nothing's called it yet; the
+    // trap should be there, but we shouldn't be able to see it
+    let (hits, total) = proc.coverage().unwrap();
+    assert_lt!(hits, total);
+    assert_eq!(proc.read_memory(depth_addr, 1)?, vec![0xcc]);
+    assert_ne!(proc.read_memory_without_traps(depth_addr, 1)?, vec![0xcc]);
+
+    // ...and the traps shouldn't change what the program does
+    proc.resume()?;
+    assert_eq!(proc.wait_on_signal()?, ProcessState::Exited { exit_code: 0 });
+    assert_eq!(channel.read()?, b"5");
+    assert!(proc.coverage().is_none());
+
+    let data = std::fs::read(&path)?;
+    std::fs::remove_file(&path)?;
+    assert!(data.starts_with(b"DRCOV VERSION: 2\n"));
+    let (base, blocks) = parse_drcov(&data)?;
+    assert_gt!(blocks.len(), 0);
+    assert!(blocks.iter().any(|(offset, _)| base + *offset as u64 == depth_addr.0));
+    Ok(())
+}
@@ -0,0 +1,48 @@
+use std::path::Path;
+
+use elf::ElfBytes;
+use elf::endian::AnyEndian;
+
+use super::*;
+use crate::DrbugError;
+use crate::pipe::Pipe;
+use crate::tests::util::addr_from_bytes;
+
+#[rstest]
+fn test_parse_elf() -> Empty {
+    let elf = Elf::open(Path::new(COUNT_PATH))?;
+
+    let file_data = std::fs::read(COUNT_PATH)?;
+    let expected = ElfBytes::<AnyEndian>::minimal_parse(&file_data).unwrap();
+    assert_eq!(elf.entry(), VirtAddr(expected.ehdr.e_entry));
+
+    let tick = elf.symbols().iter().find(|sym| sym.name.contains("count4tick")).unwrap();
+    assert_eq!(tick.type_, SymbolType::Function);
+    assert_gt!(tick.size, 0);
+    assert_some_eq_x!(elf.symbol_containing(tick.addr.add(1)), tick);
+    Ok(())
+}
+
+#[rstest]
+fn test_parse_elf_fails() {
+    assert_matches!(Elf::open(Path::new("Cargo.toml")), Err(DrbugError::InvalidElf(_)));
+}
+
+#[rstest]
+fn test_modules() -> Empty {
+    let mut channel = Pipe::new()?;
+    let opts = ProcessOptions {
+        stdout: channel.take_writer().map(|w| w.into()),
+        ..Default::default()
+    };
+    let mut proc = Process::launch(COUNT_PATH, opts)?;
+    proc.resume()?;
+    proc.wait_on_signal()?;
+    let tick_addr = addr_from_bytes(&channel.read()?)?;
+
+    let modules = proc.modules()?;
+    let exe = modules.iter().find(|m| m.path.ends_with("/count")).unwrap();
+    let tick = exe.elf.symbols().iter().find(|sym| sym.name.contains("count4tick")).unwrap();
+    assert_eq!(exe.symbol_addr(tick), tick_addr);
+    Ok(())
+}
@@ -0,0 +1,72 @@
+use super::*;
+use crate::DrbugError;
+use crate::register::info::{
+    RegisterId,
+    register_info_by_id,
+};
+
+#[rstest]
+#[case("rdi == 0x10")]
+#[case("$rdi == 16 && *(u32*)(rsi+8) > 5")]
+#[case("!(rax & 0b1) || -rbx <= ~0o7")]
+#[case("*rsp + (i8)rcx * 2 % 3 >> 1 != 0")]
+fn test_parse_expression(#[case] input: &str) {
+    let expr: Expression = input.parse().unwrap();
+    assert_eq!(expr.to_string(), input);
+}
+
+#[rstest]
+#[case("")]
+#[case("rdi ==")]
+#[case("(rdi == 1")]
+#[case("rdi == 1)")]
+#[case("rdi = 1")]
+#[case("bogus == 1")]
+#[case("xmm0 == 1")]
+#[case("(u32*)rsi == 1")]
+#[case("0xzz == rdi")]
+fn test_parse_expression_fails(#[case] input: &str) {
+    assert_err!(input.parse::<Expression>());
+}
+
+#[rstest]
+#[case("rdi == 0x10", true)]
+#[case("$rdi != 16", false)]
+#[case("dil == 0x10 && edi == 0x10", true)]
+#[case("rdi > 5 && rdi < 0x20", true)]
+#[case("rdi == 0 || rdi == 16", true)]
+#[case("(rdi + 8) * 2 == 48", true)]
+#[case("rdi % 3 == 1 && rdi / 3 == 5", true)]
+#[case("rdi << 4 == 0x100 && rdi >> 4 == 1", true)]
+#[case("(rdi | 1) ^ 0x11 == (rdi & 0)", true)]
+#[case("!rdi", false)]
+#[case("-1 < 0 && ~0 == -1", true)]
+#[case("(i8)0xff < 0 && (u8)0x1ff == 0xff", true)]
+#[case("*(u64*)rsp == 1 && *(u32*)rsp == 1 && *rsp == 1", true)] // argc is on the top of the stack at startup
+fn test_evaluate_expression(#[case] input: &str, #[case] expected: bool) -> Empty {
+    let mut proc = Process::launch(LOOP_PATH, Default::default())?;
+    let info = register_info_by_id(&RegisterId::rdi);
+    proc.get_registers_mut().write(info, RegisterValue::U64(0x10))?;
+
+    let expr: Expression = input.parse()?;
+    assert_eq!(expr.is_true(&proc)?, expected);
+    Ok(())
+}
+
+#[rstest]
+fn test_evaluate_expression_value() -> Empty {
+    let proc = Process::launch(LOOP_PATH, Default::default())?;
+    let expr: Expression = "(0x10 + 2) * -3".parse()?;
+
+    assert_eq!(expr.evaluate(&proc)?, -54);
+    Ok(())
+}
+
+#[rstest]
+fn test_evaluate_expression_division_by_zero() -> Empty {
+    let proc = Process::launch(LOOP_PATH, Default::default())?;
+    let expr: Expression = "rdi / 0 == 1".parse()?;
+
+    assert_matches!(expr.evaluate(&proc), Err(DrbugError::DivisionByZero));
+    Ok(())
+}
@@ -1,8 +1,15 @@
 mod breakpoint_test;
+mod coverage_test;
+mod elf_test;
+mod expression_test;
 mod memory_test;
 mod process_test;
+mod record_test;
 mod register_test;
+mod replay_test;
+mod trace_test;
 mod util;
+mod watchpoint_test;
 
 use assertables::*;
 use rstest::*;
@@ -10,8 +17,16 @@ use rstest::*;
 use crate::Empty;
 use crate::prelude::*;
 
+const COUNT_PATH: &str = "../target/debug/count";
+const DLOPEN_PATH: &str = "../target/debug/dlopen";
 const HELLO_PATH: &str = "../target/debug/hello";
 const LOOP_PATH: &str = "../target/debug/loop";
 const MEMORY_PATH: &str = "../target/debug/memory";
+const RANDOM_PATH: &str = "../target/debug/random";
+const RECURSE_PATH: &str = "../target/debug/recurse";
+const WATCH_PATH: &str = "../target/debug/watch";
+const AVX_TEST_BINARY: &str = "../target/asm/reg_avx";
+const AVX512_TEST_BINARY: &str = "../target/asm/reg_avx512";
 const READ_TEST_BINARY: &str = "../target/asm/reg_read";
+const UNWIND_TEST_BINARY: &str = "../target/asm/unwind";
 const WRITE_TEST_BINARY: &str = "../target/asm/reg_write";
@@ -1,3 +1,6 @@
+use std::path::Path;
+
+use iced_x86::Mnemonic;
 use nix::sys::signal::{
     Signal,
     kill,
@@ -5,7 +8,17 @@ use nix::sys::signal::{
 use nix::unistd::Pid;
 
 use super::*;
+use crate::pipe::Pipe;
 use crate::process::ProcessOptions;
+use crate::register::info::{
+    RegisterId,
+    register_info_by_id,
+};
+use crate::tests::util::{
+    addr_from_bytes,
+    get_entry_point_offset,
+    get_load_addr,
+};
 use crate::{
     DrbugError,
     Empty,
@@ -85,3 +98,284 @@ fn test_resume_process_terminated() -> Empty {
 
     Ok(())
 }
+
+#[rstest]
+fn test_launch_stop_at_entry() -> Empty {
+    let proc = Process::launch(LOOP_PATH, ProcessOptions { stop_at: StopAt::Entry, ..Default::default() })?;
+    let entry = get_load_addr(proc.pid(), get_entry_point_offset(Path::new(LOOP_PATH)));
+    assert_eq!(proc.entry_point()?, entry);
+    assert_eq!(proc.get_pc()?, entry);
+    assert!(proc.breakpoint_sites().is_empty()); // the internal breakpoint is gone
+    Ok(())
+}
+
+#[rstest]
+fn test_launch_stop_at_main() -> Empty {
+    let proc = Process::launch(COUNT_PATH, ProcessOptions { stop_at: StopAt::Main, ..Default::default() })?;
+    let main = proc.main_addr()?;
+    assert_eq!(proc.get_pc()?, main);
+
+    let modules = proc.modules()?;
+    let exe = modules.iter().find(|m| m.path.ends_with("/count")).unwrap();
+    assert_eq!(exe.elf.symbol_containing(VirtAddr(main.0 - exe.load_bias)).unwrap().name, "main");
+    Ok(())
+}
+
+#[rstest]
+#[case(StopAt::Exec)]
+#[case(StopAt::Main)]
+fn test_restart_stop_at_main(#[case] launch_stop_at: StopAt) -> Empty {
+    let mut proc = Process::launch(COUNT_PATH, ProcessOptions { stop_at: launch_stop_at, ..Default::default() })?;
+    let state = proc.restart(Some(StopAt::Main))?;
+    assert!(state.is_stopped());
+    assert_eq!(proc.get_pc()?, proc.main_addr()?);
+    Ok(())
+}
+
+#[rstest]
+fn test_step_over_plain_instruction() -> Empty {
+    let mut proc = Process::launch(LOOP_PATH, Default::default())?;
+    let instructions = Disassembler::new(&mut proc).disassemble(None, 1)?;
+    assert_ne!(instructions[0].mnemonic(), Mnemonic::Call);
+
+    proc.step_over_instruction()?;
+    assert_eq!(proc.get_pc()?, VirtAddr(instructions[0].next_ip()));
+    Ok(())
+}
+
+#[rstest]
+fn test_step_over_recursive_call() -> Empty {
+    let mut channel = Pipe::new()?;
+    let opts = ProcessOptions {
+        stdout: channel.take_writer().map(|w| w.into()),
+        ..Default::default()
+    };
+    let mut proc = Process::launch(RECURSE_PATH, opts)?;
+    proc.resume()?;
+    proc.wait_on_signal()?;
+    let depth_addr = addr_from_bytes(&channel.read()?)?;
+    proc.run_to(depth_addr)?;
+
+    // Walk through the outermost call of `depth(5)` until we get to the recursive call
+    let call = loop {
+        let instructions = Disassembler::new(&mut proc).disassemble(None, 1)?;
+        let instr = instructions[0];
+        if instr.mnemonic() == Mnemonic::Call && instr.near_branch_target() == depth_addr.0 {
+            break instr;
+        }
+        proc.step_instruction()?;
+    };
+
+    // The recursive calls all come back to the same return address; we should only stop once the
+    // call we stepped over returns, which is when `depth(4)` hands back 4
+    let sp = proc.get_sp()?;
+    proc.step_over_instruction()?;
+    assert_eq!(proc.get_pc()?, VirtAddr(call.next_ip()));
+    assert_eq!(proc.get_sp()?, sp);
+    let rax = proc.get_registers().read(register_info_by_id(&RegisterId::rax))?;
+    assert_eq!(rax, RegisterValue::U64(4));
+    assert!(proc.breakpoint_sites().is_empty());
+    Ok(())
+}
+
+// Same thing, but with a user breakpoint on the return address that would never stop the process
+// by itself; stepping over the call should still stop there, and leave the breakpoint alone
+#[rstest]
+#[case(Some("rax == 100"), 0)]
+#[case(None, 100)]
+fn test_step_over_call_to_user_breakpoint(#[case] condition: Option<&str>, #[case] ignore: usize) -> Empty {
+    let mut channel = Pipe::new()?;
+    let opts = ProcessOptions {
+        stdout: channel.take_writer().map(|w| w.into()),
+        ..Default::default()
+    };
+    let mut proc = Process::launch(RECURSE_PATH, opts)?;
+    proc.resume()?;
+    proc.wait_on_signal()?;
+    let depth_addr = addr_from_bytes(&channel.read()?)?;
+    proc.run_to(depth_addr)?;
+
+    let call = loop {
+        let instructions = Disassembler::new(&mut proc).disassemble(None, 1)?;
+        let instr = instructions[0];
+        if instr.mnemonic() == Mnemonic::Call && instr.near_branch_target() == depth_addr.0 {
+            break instr;
+        }
+        proc.step_instruction()?;
+    };
+
+    let mut site = proc.create_breakpoint_site(VirtAddr(call.next_ip()))?;
+    site.set_condition(condition.map(str::parse::<Expression>).transpose()?);
+    site.set_ignore_count(ignore);
+    site.enable()?;
+
+    let sp = proc.get_sp()?;
+    proc.step_over_instruction()?;
+    assert_eq!(proc.get_pc()?, VirtAddr(call.next_ip()));
+    assert_eq!(proc.get_sp()?, sp);
+    let rax = proc.get_registers().read(register_info_by_id(&RegisterId::rax))?;
+    assert_eq!(rax, RegisterValue::U64(4));
+    assert!(site.enabled());
+    assert_len_eq_x!(proc.breakpoint_sites(), 1);
+    Ok(())
+}
+
+#[rstest]
+fn test_step_out_from_function_entry() -> Empty {
+    let mut channel = Pipe::new()?;
+    let opts = ProcessOptions {
+        stdout: channel.take_writer().map(|w| w.into()),
+        ..Default::default()
+    };
+    let mut proc = Process::launch(COUNT_PATH, opts)?;
+    proc.resume()?;
+    proc.wait_on_signal()?;
+    let tick_addr = addr_from_bytes(&channel.read()?)?;
+    proc.run_to(tick_addr)?;
+
+    // Right at the start of the function, the return address is still on top of the stack
+    let sp = proc.get_sp()?;
+    let return_addr = VirtAddr(u64::from_le_bytes(proc.read_memory(sp, 8)?[..8].try_into()?));
+    assert_eq!(proc.return_frame()?, (return_addr, sp.add(8)));
+
+    proc.step_out()?;
+    assert_eq!(proc.get_pc()?, return_addr);
+    assert_eq!(proc.get_sp()?, sp.add(8));
+    let rax = proc.get_registers().read(register_info_by_id(&RegisterId::rax))?;
+    assert_eq!(rax, RegisterValue::U64(0)); // the first call is `tick(0)`
+    Ok(())
+}
+
+#[rstest]
+fn test_step_out_from_ret() -> Empty {
+    let mut channel = Pipe::new()?;
+    let opts = ProcessOptions {
+        stdout: channel.take_writer().map(|w| w.into()),
+        ..Default::default()
+    };
+    let mut proc = Process::launch(RECURSE_PATH, opts)?;
+    proc.resume()?;
+    proc.wait_on_signal()?;
+    let depth_addr = addr_from_bytes(&channel.read()?)?;
+    proc.run_to(depth_addr)?;
+
+    // Get all the way to the end of `depth(5)`, past the epilogue, where rbp has already been put back
+    // the way the caller had it
+    while Disassembler::new(&mut proc).disassemble(None, 1)?[0].mnemonic() != Mnemonic::Ret {
+        proc.step_over_instruction()?;
+    }
+
+    let sp = proc.get_sp()?;
+    let return_addr = VirtAddr(u64::from_le_bytes(proc.read_memory(sp, 8)?[..8].try_into()?));
+    assert_eq!(proc.return_frame()?, (return_addr, sp.add(8)));
+
+    proc.step_out()?;
+    assert_eq!(proc.get_pc()?, return_addr);
+    assert_eq!(proc.get_sp()?, sp.add(8));
+    let rax = proc.get_registers().read(register_info_by_id(&RegisterId::rax))?;
+    assert_eq!(rax, RegisterValue::U64(5));
+    Ok(())
+}
+
+#[rstest]
+fn test_step_out_without_frame_pointer() -> Empty {
+    let mut proc = Process::launch(UNWIND_TEST_BINARY, Default::default())?;
+    proc.resume()?;
+    proc.wait_on_signal()?;
+
+    // We're in the middle of `frameless`, under the `endbr64`, a push and 0x18 bytes of locals
+    let sp = proc.get_sp()?;
+    let return_addr = VirtAddr(u64::from_le_bytes(proc.read_memory(sp.add(0x20), 8)?[..8].try_into()?));
+    assert_eq!(proc.return_frame()?, (return_addr, sp.add(0x28)));
+
+    // The epilogue gives the stack back a piece at a time, but the frame stays put
+    while Disassembler::new(&mut proc).disassemble(None, 1)?[0].mnemonic() != Mnemonic::Ret {
+        proc.step_instruction()?;
+        assert_eq!(proc.return_frame()?, (return_addr, sp.add(0x28)));
+    }
+
+    proc.step_out()?;
+    assert_eq!(proc.get_pc()?, return_addr);
+    assert_eq!(proc.get_sp()?, sp.add(0x28));
+    let rax = proc.get_registers().read(register_info_by_id(&RegisterId::rax))?;
+    assert_eq!(rax, RegisterValue::U64(7));
+    Ok(())
+}
+
+#[rstest]
+fn test_step_out_of_recursive_call() -> Empty {
+    let mut channel = Pipe::new()?;
+    let opts = ProcessOptions {
+        stdout: channel.take_writer().map(|w| w.into()),
+        ..Default::default()
+    };
+    let mut proc = Process::launch(RECURSE_PATH, opts)?;
+    proc.resume()?;
+    proc.wait_on_signal()?;
+    let depth_addr = addr_from_bytes(&channel.read()?)?;
+    proc.run_to(depth_addr)?;
+
+    let call = loop {
+        let instructions = Disassembler::new(&mut proc).disassemble(None, 1)?;
+        let instr = instructions[0];
+        if instr.mnemonic() == Mnemonic::Call && instr.near_branch_target() == depth_addr.0 {
+            break instr;
+        }
+        proc.step_instruction()?;
+    };
+
+    // Go into `depth(4)` and get past its prologue, so that we have to unwind through it; the
+    // deeper calls all return to the same place, but we should only stop when `depth(4)` does
+    let sp = proc.get_sp()?;
+    for _ in 0..3 {
+        proc.step_instruction()?;
+    }
+    proc.step_out()?;
+    assert_eq!(proc.get_pc()?, VirtAddr(call.next_ip()));
+    assert_eq!(proc.get_sp()?, sp);
+    let rax = proc.get_registers().read(register_info_by_id(&RegisterId::rax))?;
+    assert_eq!(rax, RegisterValue::U64(4));
+    assert!(proc.breakpoint_sites().is_empty());
+    Ok(())
+}
+
+#[rstest]
+#[case::branch_trap(false)]
+#[case::instruction_at_a_time(true)]
+fn test_step_block(#[case] recording: bool) -> Empty {
+    let mut channel = Pipe::new()?;
+    let opts = ProcessOptions {
+        stdout: channel.take_writer().map(|w| w.into()),
+        disable_aslr: true, // so `depth` is in the same place after the restart
+        ..Default::default()
+    };
+    let mut proc = Process::launch(RECURSE_PATH, opts)?;
+    proc.resume()?;
+    proc.wait_on_signal()?;
+    let depth_addr = addr_from_bytes(&channel.read()?)?;
+    proc.run_to(depth_addr)?;
+
+    // Work out where the first taken branch in `depth(5)` goes the slow way, then start over
+    let mut next_ip = depth_addr.0;
+    while proc.get_pc()?.0 == next_ip {
+        next_ip = Disassembler::new(&mut proc).disassemble(None, 1)?[0].next_ip();
+        proc.step_instruction()?;
+    }
+    let target = proc.get_pc()?;
+    proc.restart(None)?;
+    proc.resume()?;
+    proc.wait_on_signal()?;
+    proc.run_to(depth_addr)?;
+
+    if recording {
+        proc.start_recording(DEFAULT_RECORD_LIMIT);
+    }
+    let state = proc.step_block()?;
+    assert!(state.is_stopped());
+    assert_eq!(proc.trap_type(), Some(TrapType::SingleStep));
+    assert_eq!(proc.get_pc()?, target);
+    if recording {
+        assert_gt!(proc.recorded_steps(), 1);
+    }
+    Ok(())
+}
@@ -0,0 +1,148 @@
+use super::*;
+use crate::pipe::Pipe;
+use crate::process::ProcessOptions;
+use crate::register::info::{
+    RegisterId,
+    register_info_by_id,
+};
+use crate::tests::util::addr_from_bytes;
+use crate::{
+    DrbugError,
+    DrbugResult,
+};
+
+fn launch_recurse() -> DrbugResult<(Process, VirtAddr)> {
+    let mut channel = Pipe::new()?;
+    let opts = ProcessOptions {
+        stdout: channel.take_writer().map(|w| w.into()),
+        ..Default::default()
+    };
+    let mut proc = Process::launch(RECURSE_PATH, opts)?;
+    proc.resume()?;
+    proc.wait_on_signal()?;
+    let depth_addr = addr_from_bytes(&channel.read()?)?;
+    Ok((proc, depth_addr))
+}
+
+fn read_gprs(proc: &Process) -> DrbugResult<Vec<RegisterValue>> {
+    let ids = [RegisterId::rax, RegisterId::rbx, RegisterId::rdi, RegisterId::rsp, RegisterId::rbp, RegisterId::rip];
+    ids.iter()
+        .map(|id| proc.get_registers().read(register_info_by_id(id)))
+        .collect()
+}
+
+#[rstest]
+fn test_reverse_step_restores_state() -> Empty {
+    let (mut proc, _) = launch_recurse()?;
+
+    // The recursion writes a bunch of return addresses and locals to the stack below us
+    let sp = proc.get_sp()?;
+    let stack_low = VirtAddr(sp.0 - 0x800);
+    let stack = proc.read_memory(stack_low, 0x900)?;
+    let regs = read_gprs(&proc)?;
+
+    proc.start_recording(DEFAULT_RECORD_LIMIT);
+    let mut pcs = vec![];
+    for _ in 0..200 {
+        pcs.push(proc.get_pc()?);
+        proc.step_instruction()?;
+    }
+    assert_eq!(proc.recorded_steps(), 200);
+    assert_ne!(proc.read_memory(stack_low, 0x900)?, stack);
+
+    while let Some(pc) = pcs.pop() {
+        proc.reverse_step_instruction()?;
+        assert_eq!(proc.get_pc()?, pc);
+    }
+    assert_eq!(proc.recorded_steps(), 0);
+    assert_eq!(proc.read_memory(stack_low, 0x900)?, stack);
+    assert_eq!(read_gprs(&proc)?, regs);
+    Ok(())
+}
+
+#[rstest]
+fn test_reverse_continue_stops_at_breakpoint() -> Empty {
+    let (mut proc, depth_addr) = launch_recurse()?;
+    proc.create_breakpoint_site(depth_addr)?.enable()?;
+    let rdi_info = register_info_by_id(&RegisterId::rdi);
+
+    proc.start_recording(DEFAULT_RECORD_LIMIT);
+    proc.resume()?;
+    proc.wait_on_signal()?;
+    assert_eq!(proc.get_pc()?, depth_addr);
+    assert_eq!(proc.get_registers().read(rdi_info)?, RegisterValue::U64(5));
+    proc.resume()?;
+    proc.wait_on_signal()?;
+    assert_eq!(proc.get_registers().read(rdi_info)?, RegisterValue::U64(4));
+
+    // Going backwards from `depth(4)` should take us back to the start of `depth(5)`
+    proc.reverse_continue()?;
+    assert_eq!(proc.get_pc()?, depth_addr);
+    assert_eq!(proc.get_registers().read(rdi_info)?, RegisterValue::U64(5));
+    Ok(())
+}
+
+#[rstest]
+fn test_step_out_is_recorded() -> Empty {
+    let (mut proc, depth_addr) = launch_recurse()?;
+    proc.run_to(depth_addr)?;
+    let regs = read_gprs(&proc)?;
+
+    // Anything that runs the process records it, not just single steps and continues
+    proc.start_recording(DEFAULT_RECORD_LIMIT);
+    proc.step_out()?;
+    assert_gt!(proc.recorded_steps(), 0);
+
+    proc.reverse_continue()?;
+    assert_eq!(proc.recorded_steps(), 0);
+    assert_eq!(read_gprs(&proc)?, regs);
+    Ok(())
+}
+
+#[rstest]
+fn test_record_limit() -> Empty {
+    let (mut proc, _) = launch_recurse()?;
+    proc.start_recording(10);
+    for _ in 0..20 {
+        proc.step_instruction()?;
+    }
+    assert_eq!(proc.recorded_steps(), 10);
+
+    proc.stop_recording();
+    assert!(!proc.is_recording());
+    assert_matches!(proc.reverse_step_instruction(), Err(DrbugError::NoRecordedHistory));
+    Ok(())
+}
+
+#[rstest]
+fn test_reverse_step_restores_vector_registers() -> Empty {
+    let ymm0 = register_info_by_id(&RegisterId::ymm0);
+    if !ymm0.available() {
+        return Ok(());
+    }
+
+    let mut channel = Pipe::new_exec_safe()?;
+    let opts = ProcessOptions {
+        stdout: channel.take_writer().map(|w| w.into()),
+        ..Default::default()
+    };
+    let mut proc = Process::launch(AVX_TEST_BINARY, opts)?;
+
+    // Skip ahead to the last trap, right before the `vzeroupper`, which clears the top half of ymm0
+    // (which lives in the XSAVE area, not the user area)
+    for _ in 0..2 {
+        proc.resume()?;
+        proc.wait_on_signal()?;
+    }
+    channel.read()?;
+    let before = proc.get_registers().read(ymm0)?;
+    assert_eq!(before, RegisterValue::B256(std::array::from_fn(|i| i as u8)));
+
+    proc.start_recording(DEFAULT_RECORD_LIMIT);
+    proc.step_instruction()?;
+    assert_ne!(proc.get_registers().read(ymm0)?, before);
+
+    proc.reverse_step_instruction()?;
+    assert_eq!(proc.get_registers().read(ymm0)?, before);
+    Ok(())
+}
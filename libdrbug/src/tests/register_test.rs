@@ -1,6 +1,7 @@
 use std::str::from_utf8;
 
 use super::*;
+use crate::DrbugError;
 use crate::pipe::Pipe;
 use crate::register::info::{
     RegisterId,
@@ -59,21 +60,82 @@ fn test_write_registers() -> Empty {
         assert_eq!(from_utf8(&output).unwrap(), "42.24");
     }
 
-    // long double currently unsupported
-    // {
-    //     let regs = proc.get_registers_mut();
-    //     regs.write_by_id(RegisterId::st0, RegisterValue::F64(42.24))?;
-    //     regs.write_by_id(RegisterId::fsw, RegisterValue::U16(0b0011100000000000))
-    //         ?;
-    //     regs.write_by_id(RegisterId::ftw, RegisterValue::U16(0b0011111111111111))
-    //         ?;
-    //     proc.resume()?;
-    //     proc.wait_on_signal()?;
+    {
+        // We have to set up the stack by hand here: TOP = 7 puts st0 in physical register 7, and
+        // ptrace gives us the abridged tag word, so setting bit 7 marks that register as in use
+        let regs = proc.get_registers_mut();
+        regs.write(register_info_by_id(&RegisterId::st0), RegisterValue::F64(42.24))?;
+        regs.write(register_info_by_id(&RegisterId::fsw), RegisterValue::U16(0b0011100000000000))?;
+        regs.write(register_info_by_id(&RegisterId::ftw), RegisterValue::U16(0b10000000))?;
+        proc.resume()?;
+        proc.wait_on_signal()?;
+
+        let output = channel.read()?;
+        assert_eq!(from_utf8(&output).unwrap(), "42.24");
+    }
+
+    Ok(())
+}
+
+#[rstest]
+fn test_write_registers_batched() -> Empty {
+    let mut channel = Pipe::new_exec_safe()?;
+    let mut proc = Process::launch(
+        WRITE_TEST_BINARY,
+        ProcessOptions {
+            stdout: channel.take_writer().map(|w| w.into()),
+            ..Default::default()
+        },
+    )?;
+    proc.resume()?;
+    proc.wait_on_signal()?;
+
+    // Neither write goes out until we resume, and the second one only touches the bottom half of
+    // the first, so if anything's wrong with the batching we'll see the wrong value printed
+    let rsi_info = register_info_by_id(&RegisterId::rsi);
+    let regs = proc.get_registers_mut();
+    regs.write(rsi_info, RegisterValue::U64(0xcafecafe))?;
+    regs.write(register_info_by_id(&RegisterId::si), RegisterValue::U16(0xbeef))?;
+    assert_eq!(regs.read(rsi_info)?, RegisterValue::U64(0xcafebeef));
+
+    // Once the process is running, the cached values are no good anymore
+    proc.resume()?;
+    assert_matches!(proc.get_registers().read(rsi_info), Err(DrbugError::RegistersStale));
+    assert_matches!(proc.get_registers_mut().write(rsi_info, RegisterValue::U64(0)), Err(DrbugError::RegistersStale));
+    proc.wait_on_signal()?;
+
+    let output = channel.read()?;
+    assert_eq!(from_utf8(&output).unwrap(), "0xcafebeef");
+    Ok(())
+}
+
+#[rstest]
+fn test_register_history() -> Empty {
+    let mut proc = Process::launch(READ_TEST_BINARY, Default::default())?;
+    let r13_info = register_info_by_id(&RegisterId::r13);
+    let regs = proc.get_registers_mut();
+    regs.record_stop()?;
+    assert!(!regs.changed_since_last_stop(r13_info));
+    assert_is_empty!(regs.changes_since_last_stop()?);
+
+    proc.resume()?;
+    proc.wait_on_signal()?;
+    let regs = proc.get_registers_mut();
+    regs.record_stop()?;
+
+    let history = regs.history(r13_info)?;
+    assert_len_eq_x!(history, 2);
+    assert_ne!(history[0], RegisterValue::U64(0xcafecafe));
+    assert_eq!(history[1], RegisterValue::U64(0xcafecafe));
 
-    //     let output = channel.read()?;
-    //     assert_eq!(from_utf8(&output)?, "42.24");
-    // }
+    // Sub-registers and the pc are left out of the list, but they still count as changed
+    assert!(regs.changed_since_last_stop(r13_info));
+    assert!(regs.changed_since_last_stop(register_info_by_id(&RegisterId::rip)));
+    let changes = regs.changes_since_last_stop()?;
+    assert_contains!(changes, &(RegisterId::r13, RegisterValue::U64(0xcafecafe)));
+    assert!(changes.iter().all(|(id, _)| *id != RegisterId::rip && *id != RegisterId::r13d));
 
+    assert_matches!(regs.history(register_info_by_id(&RegisterId::ymm0)), Err(DrbugError::NoRegisterHistory("ymm0")));
     Ok(())
 }
 
@@ -120,14 +182,256 @@ fn test_read_registers() -> Empty {
         assert_eq!(val, RegisterValue::F64(64.125));
     }
 
-    // long double currently unsupported
-    // {
-    //     proc.resume()?;
-    //     proc.wait_on_signal()?;
+    {
+        proc.resume()?;
+        proc.wait_on_signal()?;
+
+        let regs = proc.get_registers();
+        let info = register_info_by_id(&RegisterId::st0);
+        let val = regs.read(info)?;
+        assert_eq!(val, RegisterValue::F80(F80::from(64.125)));
+
+        let stack = regs.x87_stack()?;
+        assert_eq!(stack.top, 7);
+        assert_eq!(stack.depth(), 1);
+        assert_eq!(stack.registers[0].physical, 7);
+        assert_eq!(stack.registers[0].tag, X87Tag::Valid);
+        assert_eq!(stack.registers[0].value.to_f64(), 64.125);
+        assert!(stack.registers[1..].iter().all(|reg| reg.tag == X87Tag::Empty));
+    }
+    Ok(())
+}
+
+#[rstest]
+#[case(1.0, [0, 0, 0, 0, 0, 0, 0, 0x80, 0xff, 0x3f])]
+#[case(-2.0, [0, 0, 0, 0, 0, 0, 0, 0x80, 0x00, 0xc0])]
+#[case(0.0, [0; 10])]
+#[case(f64::INFINITY, [0, 0, 0, 0, 0, 0, 0, 0x80, 0xff, 0x7f])]
+// the smallest f64 denormal is a perfectly normal f80
+#[case(f64::from_bits(1), [0, 0, 0, 0, 0, 0, 0, 0x80, 0xcd, 0x3b])]
+fn test_f80_conversion(#[case] val: f64, #[case] bytes: [u8; 10]) {
+    let f80 = F80::from(val);
+    assert_eq!(f80.to_le_bytes(), bytes);
+    assert_eq!(f80.to_f64(), val);
+}
+
+#[rstest]
+#[case(64.125)]
+#[case(42.24)]
+#[case(-1e300)]
+#[case(f64::MIN_POSITIVE)]
+#[case(f64::MAX)]
+fn test_f80_round_trip(#[case] val: f64) {
+    assert_eq!(F80::from(val).to_f64(), val);
+}
+
+#[rstest]
+#[case("0x3fff8000000000000000", 1.0)]
+#[case(" 0xC0008000000000000000", -2.0)]
+// more precision than an f64 has, which only survives in the raw form
+#[case("0x3fff8000000000000001", 1.0)]
+fn test_f80_parse_raw(#[case] input: &str, #[case] approx: f64) -> Empty {
+    let f80: F80 = input.parse()?;
+    assert_eq!(f80.to_f64(), approx);
+    assert_eq!(f80.to_string(), format!("{approx} ({})", input.trim().to_lowercase()));
+    Ok(())
+}
+
+#[rstest]
+#[case("0x3fff")]
+#[case("0x3fff80000000000000000")]
+#[case("0x3fff800000000000000g")]
+fn test_f80_parse_raw_fails(#[case] input: &str) {
+    assert_matches!(input.parse::<F80>(), Err(DrbugError::InvalidFloat(_)));
+}
+
+#[rstest]
+fn test_f80_nan() {
+    assert!(F80::from(f64::NAN).to_f64().is_nan());
+    assert!(F80::from(f64::NAN).is_special());
+}
+
+#[rstest]
+#[case("f32x4", "[1, 2.5, -0.5, 0]")]
+#[case("u64", "[4620693218747482112, 3204448256]")]
+#[case("i32x4", "[1065353216, 1075838976, -1090519040, 0]")]
+#[case("u16x8", "[0, 16256, 0, 16416, 0, 48896, 0, 0]")]
+#[case("i8x16", "[0, 0, -128, 63, 0, 0, 32, 64, 0, 0, 0, -65, 0, 0, 0, 0]")]
+fn test_vector_lanes(#[case] format: &str, #[case] expected: &str) -> Empty {
+    let val = RegisterValue::from_lanes(
+        LaneType::F32,
+        &[RegisterValue::F32(1.0), RegisterValue::F32(2.5), RegisterValue::F32(-0.5), RegisterValue::F32(0.0)],
+    )?;
+    let format: LaneFormat = format.parse()?;
+    assert_eq!(val.lanes(format)?.to_string(), expected);
+    Ok(())
+}
+
+#[rstest]
+#[case("f32x8")]
+#[case("i64x1")]
+#[case("f80")]
+#[case("f32x0")]
+fn test_vector_lanes_bad_format(#[case] format: &str) {
+    let val = RegisterValue::B128([0; 16]);
+    assert_err!(format.parse::<LaneFormat>().and_then(|format| val.lanes(format)));
+}
+
+#[rstest]
+fn test_vector_with_lane() -> Empty {
+    let val = RegisterValue::B256([0; 32]);
+    let val = val.with_lane(LaneType::F64, 3, &RegisterValue::F64(1.5))?;
+    let val = val.with_lane(LaneType::U8, 0, &RegisterValue::U8(0xff))?;
+    assert_eq!(val.lanes("f64".parse()?)?.0[3], RegisterValue::F64(1.5));
+    assert_eq!(val.lanes("u8x32".parse()?)?.0[0], RegisterValue::U8(0xff));
+
+    assert_err!(val.with_lane(LaneType::F64, 4, &RegisterValue::F64(1.5)));
+    assert_err!(val.with_lane(LaneType::F64, 0, &RegisterValue::F32(1.5)));
+    assert_err!(RegisterValue::U64(0).with_lane(LaneType::U8, 0, &RegisterValue::U8(1)));
+    Ok(())
+}
+
+#[rstest]
+#[case(RegisterId::eflags, RegisterValue::U64(0x246), "[ PF ZF IF ]")]
+#[case(RegisterId::mxcsr, RegisterValue::U32(0x1f80), "[ IM DM ZM OM UM PM ]")]
+#[case(RegisterId::fcw, RegisterValue::U16(0x37f), "[ IM DM ZM OM UM PM PC=extended ]")]
+#[case(RegisterId::fsw, RegisterValue::U16(0x3800), "[ TOP=7 ]")]
+#[case(
+    RegisterId::dr7,
+    RegisterValue::U64(0b1001_0000_0000_0000_0001),
+    "[ L0 RW0=w LEN0=8 ]"
+)]
+#[case(RegisterId::dr6, RegisterValue::U64(0xffff4ff2), "[ B1 BS ]")]
+fn test_decode_flags(#[case] id: RegisterId, #[case] val: RegisterValue, #[case] expected: &str) {
+    let flags = Flags::decode(register_info_by_id(&id), &val).unwrap();
+    assert_eq!(flags.to_string(), expected);
+}
+
+#[rstest]
+fn test_decode_flags_unsupported() {
+    assert_none!(Flags::decode(register_info_by_id(&RegisterId::rax), &RegisterValue::U64(0)));
+}
+
+#[rstest]
+#[case(RegisterId::eflags, RegisterValue::U64(0x246), "+CF -ZF", RegisterValue::U64(0x207))]
+#[case(RegisterId::eflags, RegisterValue::U64(0x246), "+cf  +of", RegisterValue::U64(0xa47))]
+#[case(
+    RegisterId::mxcsr,
+    RegisterValue::U32(0x1f80),
+    "RC=up -PM",
+    RegisterValue::U32(0x4f80)
+)]
+#[case(RegisterId::fcw, RegisterValue::U16(0x37f), "PC=0 RC=3", RegisterValue::U16(0xc7f))]
+fn test_apply_flags(
+    #[case] id: RegisterId,
+    #[case] val: RegisterValue,
+    #[case] changes: &str,
+    #[case] expected: RegisterValue,
+) -> Empty {
+    let mut flags = Flags::decode(register_info_by_id(&id), &val).unwrap();
+    flags.apply(changes)?;
+    assert_eq!(flags.to_register_value(), expected);
+    Ok(())
+}
+
+#[rstest]
+#[case("+RC")]
+#[case("+XX")]
+#[case("RC=4")]
+#[case("RC=sideways")]
+#[case("ZF")]
+fn test_apply_flags_fails(#[case] changes: &str) {
+    let mut flags = Flags::decode(register_info_by_id(&RegisterId::mxcsr), &RegisterValue::U32(0x1f80)).unwrap();
+    assert_err!(flags.apply(changes));
+}
+
+#[rstest]
+fn test_avx_registers() -> Empty {
+    if !register_info_by_id(&RegisterId::ymm0).available() {
+        return Ok(());
+    }
+
+    let mut channel = Pipe::new_exec_safe()?;
+    let mut proc = Process::launch(
+        AVX_TEST_BINARY,
+        ProcessOptions {
+            stdout: channel.take_writer().map(|w| w.into()),
+            ..Default::default()
+        },
+    )?;
+    proc.resume()?;
+    proc.wait_on_signal()?;
+
+    {
+        let regs = proc.get_registers();
+        let val = regs.read(register_info_by_id(&RegisterId::ymm0))?;
+        assert_eq!(val, RegisterValue::B256(std::array::from_fn(|i| i as u8)));
+
+        let val = regs.read(register_info_by_id(&RegisterId::xmm0))?;
+        assert_eq!(val, RegisterValue::B128(std::array::from_fn(|i| i as u8)));
+    }
+
+    {
+        let regs = proc.get_registers_mut();
+        let bytes: [u8; 32] = std::array::from_fn(|i| 0xff - i as u8);
+        regs.write(register_info_by_id(&RegisterId::ymm1), RegisterValue::B256(bytes))?;
+        proc.resume()?;
+        proc.wait_on_signal()?;
+
+        assert_eq!(channel.read()?, bytes);
+    }
+
+    Ok(())
+}
+
+#[rstest]
+fn test_avx512_registers() -> Empty {
+    if !register_info_by_id(&RegisterId::zmm0).available() {
+        return Ok(());
+    }
+
+    let mut channel = Pipe::new_exec_safe()?;
+    let mut proc = Process::launch(
+        AVX512_TEST_BINARY,
+        ProcessOptions {
+            stdout: channel.take_writer().map(|w| w.into()),
+            ..Default::default()
+        },
+    )?;
+    proc.resume()?;
+    proc.wait_on_signal()?;
+
+    {
+        let regs = proc.get_registers();
+        let expected: [u8; 64] = std::array::from_fn(|i| i as u8);
+        assert_eq!(regs.read(register_info_by_id(&RegisterId::zmm0))?, RegisterValue::B512(expected));
+        assert_eq!(regs.read(register_info_by_id(&RegisterId::zmm16))?, RegisterValue::B512(expected));
+        assert_eq!(regs.read(register_info_by_id(&RegisterId::ymm0))?, RegisterValue::B256(expected[..32].try_into()?));
+        assert_eq!(
+            regs.read(register_info_by_id(&RegisterId::xmm16))?,
+            RegisterValue::B128(expected[..16].try_into()?)
+        );
+        assert_eq!(regs.read(register_info_by_id(&RegisterId::k1))?, RegisterValue::U64(0xa5a5));
+    }
+
+    {
+        let regs = proc.get_registers_mut();
+        let bytes: [u8; 64] = std::array::from_fn(|i| 0xff - i as u8);
+        regs.write(register_info_by_id(&RegisterId::zmm1), RegisterValue::B512(bytes))?;
+        proc.resume()?;
+        proc.wait_on_signal()?;
+
+        assert_eq!(channel.read()?, bytes);
+    }
+
+    {
+        let regs = proc.get_registers_mut();
+        regs.write(register_info_by_id(&RegisterId::k2), RegisterValue::U16(0x1234))?;
+        proc.resume()?;
+        proc.wait_on_signal()?;
+
+        assert_eq!(channel.read()?, [0x34, 0x12]);
+    }
 
-    //     let regs = proc.get_registers();
-    //     let val = regs.read_by_id(RegisterId::st0)?;
-    //     assert_eq!(val, RegisterValue::F64(64.125));
-    // }
     Ok(())
 }
@@ -0,0 +1,59 @@
+use std::path::PathBuf;
+
+use super::*;
+use crate::pipe::Pipe;
+use crate::process::ProcessOptions;
+use crate::{
+    DrbugError,
+    DrbugResult,
+};
+
+fn run_random(syscalls: SyscallMode) -> DrbugResult<Vec<u8>> {
+    let mut channel = Pipe::new()?;
+    let opts = ProcessOptions {
+        stdout: channel.take_writer().map(|w| w.into()),
+        syscalls,
+        ..Default::default()
+    };
+    let mut proc = Process::launch(RANDOM_PATH, opts)?;
+    proc.resume()?;
+    let state = proc.wait_on_signal()?;
+    assert_eq!(state, ProcessState::Exited { exit_code: 0 });
+    channel.read()
+}
+
+fn log_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("drbug-{name}-{}.log", std::process::id()))
+}
+
+#[rstest]
+fn test_replay_matches_recording() -> Empty {
+    // ASLR stays on, so the buffers the syscalls fill in are somewhere different in every run
+    let path = log_path("replay");
+    let recorded = run_random(SyscallMode::Record(path.clone()))?;
+    let replayed = run_random(SyscallMode::Replay(path.clone()))?;
+    let normal = run_random(SyscallMode::Normal)?;
+    std::fs::remove_file(&path)?;
+
+    assert_not_empty!(recorded);
+    assert_eq!(recorded, replayed);
+    assert_ne!(recorded, normal);
+    Ok(())
+}
+
+#[rstest]
+fn test_replay_invalid_log() -> Empty {
+    let path = log_path("invalid");
+    std::fs::write(&path, "318 16 deez\n")?;
+    let res = Process::launch(
+        RANDOM_PATH,
+        ProcessOptions {
+            syscalls: SyscallMode::Replay(path.clone()),
+            ..Default::default()
+        },
+    );
+    std::fs::remove_file(&path)?;
+
+    assert_matches!(res, Err(DrbugError::InvalidSyscallLog(..)));
+    Ok(())
+}
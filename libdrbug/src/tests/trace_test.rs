@@ -0,0 +1,67 @@
+use iced_x86::Mnemonic;
+
+use super::*;
+use crate::pipe::Pipe;
+use crate::process::ProcessOptions;
+use crate::register::info::RegisterId;
+use crate::tests::util::addr_from_bytes;
+use crate::{
+    DrbugError,
+    DrbugResult,
+};
+
+fn launch_at_depth() -> DrbugResult<(Process, VirtAddr)> {
+    let mut channel = Pipe::new()?;
+    let opts = ProcessOptions {
+        stdout: channel.take_writer().map(|w| w.into()),
+        ..Default::default()
+    };
+    let mut proc = Process::launch(RECURSE_PATH, opts)?;
+    proc.resume()?;
+    proc.wait_on_signal()?;
+    let depth_addr = addr_from_bytes(&channel.read()?)?;
+    proc.run_to(depth_addr)?;
+    Ok((proc, depth_addr))
+}
+
+#[rstest]
+fn test_text_trace() -> Empty {
+    let (mut proc, depth_addr) = launch_at_depth()?;
+    let mut writer = TraceWriter::new(vec![], TraceFormat::Text)?;
+    proc.trace(TraceEnd::Count(10), &mut writer)?;
+    let output = String::from_utf8(writer.finish()?).unwrap();
+
+    let lines: Vec<_> = output.lines().collect();
+    assert_eq!(lines.len(), 10);
+    assert!(lines[0].starts_with(&format!("{:#018x}: ", depth_addr.0)));
+    assert_contains!(lines[0], "rsp=");
+    Ok(())
+}
+
+#[rstest]
+fn test_binary_trace_round_trip() -> Empty {
+    let (mut proc, depth_addr) = launch_at_depth()?;
+    let (return_addr, _) = proc.return_frame()?;
+    let mut writer = TraceWriter::new(vec![], TraceFormat::Binary)?;
+    proc.trace(TraceEnd::Address(return_addr), &mut writer)?;
+    assert_eq!(proc.get_pc()?, return_addr);
+
+    // The first instruction is the start of the prologue of `depth(5)`, which makes room on the
+    // stack, and the last is the `ret` that brings us back out of it
+    let entries = read_trace(writer.finish()?.as_slice())?;
+    assert_eq!(entries[0].addr(), depth_addr);
+    assert_eq!(entries[0].bytes.len(), entries[0].instruction.len());
+    assert!(entries[0].changed.iter().any(|(id, _)| *id == RegisterId::rsp));
+    assert_eq!(entries.last().unwrap().instruction.mnemonic(), Mnemonic::Ret);
+    Ok(())
+}
+
+#[rstest]
+fn test_read_invalid_trace() {
+    assert_matches!(read_trace(&b"deez nuts"[..]), Err(DrbugError::InvalidTrace(..)));
+
+    let mut truncated = b"DRBTRACE\x01".to_vec();
+    truncated.extend_from_slice(&0x401000u64.to_le_bytes());
+    truncated.push(3);
+    assert_matches!(read_trace(truncated.as_slice()), Err(DrbugError::InvalidTrace(..)));
+}
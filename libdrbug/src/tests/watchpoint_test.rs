@@ -0,0 +1,193 @@
+use std::str;
+
+use nix::sys::signal::Signal;
+
+use super::*;
+use crate::DrbugError;
+use crate::pipe::Pipe;
+use crate::register::info::RegisterId;
+use crate::tests::util::addr_from_bytes;
+
+#[rstest]
+#[case(VirtAddr(0x1000), WatchMode::Write, 3)]
+#[case(VirtAddr(0x1000), WatchMode::ReadWrite, 16)]
+#[case(VirtAddr(0x1000), WatchMode::Execute, 8)]
+fn test_watchpoint_invalid_size(#[case] addr: VirtAddr, #[case] mode: WatchMode, #[case] size: usize) -> Empty {
+    let mut proc = Process::launch(LOOP_PATH, Default::default())?;
+    let res = proc.create_watchpoint(addr, mode, size);
+
+    assert!(matches!(res, Err(DrbugError::InvalidWatchpointSize(s)) if s == size));
+    Ok(())
+}
+
+#[rstest]
+fn test_watchpoint_unaligned() -> Empty {
+    let mut proc = Process::launch(LOOP_PATH, Default::default())?;
+    let res = proc.create_watchpoint(VirtAddr(0x1004), WatchMode::Write, 8);
+
+    assert_matches!(res, Err(DrbugError::UnalignedWatchpoint(VirtAddr(0x1004), 8)));
+    Ok(())
+}
+
+#[rstest]
+fn test_watchpoint_no_free_debug_registers() -> Empty {
+    let mut proc = Process::launch(LOOP_PATH, Default::default())?;
+    let base = VirtAddr(proc.get_pc()?.0 & !0b111);
+
+    for i in 0..4 {
+        proc.create_watchpoint(base.add(i * 8), WatchMode::ReadWrite, 8)?.enable()?;
+    }
+    let res = proc.create_watchpoint(base.add(32), WatchMode::ReadWrite, 8)?.enable();
+
+    assert_matches!(res, Err(DrbugError::NoFreeDebugRegisters));
+    Ok(())
+}
+
+#[rstest]
+fn test_watchpoint_shares_debug_registers() -> Empty {
+    let mut proc = Process::launch(LOOP_PATH, Default::default())?;
+    let addr = VirtAddr(proc.get_pc()?.0 & !0b111);
+    let dr7_info = register_info_by_id(&RegisterId::dr7);
+    let wp_bits = 0b1 | 0b1011 << 16; // L0, RW0=rw, LEN0=8
+
+    // The watchpoint shouldn't undo a write by hand that hasn't gone out to the process yet (LE
+    // doesn't turn anything on by itself), and the cache should see the watchpoint right away
+    proc.get_registers_mut().write(dr7_info, RegisterValue::U64(1 << 8))?;
+    proc.create_watchpoint(addr, WatchMode::ReadWrite, 8)?.enable()?;
+    assert_eq!(proc.get_registers().read(dr7_info)?, RegisterValue::U64(1 << 8 | wp_bits));
+
+    proc.step_instruction()?;
+    let RegisterValue::U64(dr7) = proc.get_registers().read(dr7_info)? else {
+        panic!("dr7 should be a u64");
+    };
+    assert_eq!(dr7 & 0xf0003, wp_bits);
+    Ok(())
+}
+
+#[rstest]
+fn test_watchpoint_write() -> Empty {
+    let mut channel = Pipe::new()?;
+    let opts = ProcessOptions {
+        stdout: channel.take_writer().map(|w| w.into()),
+        ..Default::default()
+    };
+    let mut proc = Process::launch(WATCH_PATH, opts)?;
+
+    proc.resume()?;
+    proc.wait_on_signal()?;
+    let a_addr = addr_from_bytes(&channel.read()?)?;
+
+    let mut wp = proc.create_watchpoint(a_addr, WatchMode::Write, 8)?;
+    wp.enable()?;
+    proc.resume()?;
+    let reason = proc.wait_on_signal()?;
+
+    assert_matches!(reason, ProcessState::Stopped { signal: Some(Signal::SIGTRAP) });
+    assert_some_eq_x!(proc.trap_type(), TrapType::HardwareBreak);
+    assert_some_eq_x!(proc.triggered_watchpoint(), &wp);
+    assert_eq!(wp.previous_data(), 0xcafecafe);
+    assert_eq!(wp.data(), 0xba5eba11);
+
+    proc.resume()?;
+    let reason = proc.wait_on_signal()?;
+    assert_matches!(reason, ProcessState::Exited { exit_code: 0 });
+
+    let output = channel.read()?;
+    assert_eq!(str::from_utf8(&output).unwrap(), "ba5eba11");
+    Ok(())
+}
+
+#[rstest]
+fn test_software_watchpoint_memory() -> Empty {
+    let mut channel = Pipe::new()?;
+    let opts = ProcessOptions {
+        stdout: channel.take_writer().map(|w| w.into()),
+        ..Default::default()
+    };
+    let mut proc = Process::launch(WATCH_PATH, opts)?;
+
+    proc.resume()?;
+    proc.wait_on_signal()?;
+    let a_addr = addr_from_bytes(&channel.read()?)?;
+
+    let mut wp = proc.create_software_watchpoint(WatchTarget::Memory { addr: a_addr, size: 8 })?;
+    wp.enable();
+    proc.resume()?;
+    let reason = proc.wait_on_signal()?;
+
+    assert_matches!(reason, ProcessState::Stopped { signal: Some(Signal::SIGTRAP) });
+    assert_some_eq_x!(proc.triggered_software_watchpoint(), &wp);
+    assert_eq!(wp.previous_data(), 0xcafecafeu64.to_le_bytes());
+    assert_eq!(wp.data(), 0xba5eba11u64.to_le_bytes());
+
+    wp.disable();
+    proc.resume()?;
+    let reason = proc.wait_on_signal()?;
+    assert_matches!(reason, ProcessState::Exited { exit_code: 0 });
+    Ok(())
+}
+
+#[rstest]
+fn test_software_watchpoint_register() -> Empty {
+    let mut proc = Process::launch(READ_TEST_BINARY, Default::default())?;
+    proc.resume()?;
+    proc.wait_on_signal()?;
+
+    let mut wp = proc.create_software_watchpoint(WatchTarget::Register(RegisterId::r13))?;
+    wp.enable();
+    proc.resume()?;
+    let reason = proc.wait_on_signal()?;
+
+    assert_matches!(reason, ProcessState::Stopped { signal: Some(Signal::SIGTRAP) });
+    assert_some_eq_x!(proc.trap_type(), TrapType::SingleStep);
+    assert_some_eq_x!(proc.triggered_software_watchpoint(), &wp);
+    assert_eq!(wp.previous_data(), 0xcafecafeu64.to_le_bytes());
+    assert_eq!(wp.data(), 0xcafeca2au64.to_le_bytes());
+    Ok(())
+}
+
+#[rstest]
+fn test_page_watchpoint() -> Empty {
+    let mut channel = Pipe::new()?;
+    let opts = ProcessOptions {
+        stdout: channel.take_writer().map(|w| w.into()),
+        ..Default::default()
+    };
+    let mut proc = Process::launch(WATCH_PATH, opts)?;
+
+    proc.resume()?;
+    proc.wait_on_signal()?;
+    let a_addr = addr_from_bytes(&channel.read()?)?;
+
+    let mut wp = proc.create_page_watchpoint(a_addr, 8)?;
+    wp.enable();
+    proc.resume()?;
+    let reason = proc.wait_on_signal()?;
+
+    assert_matches!(reason, ProcessState::Stopped { signal: Some(Signal::SIGTRAP) });
+    assert_some_eq_x!(proc.triggered_page_watchpoint(), &wp);
+    assert_some_eq_x!(wp.fault_addr(), a_addr);
+    assert_eq!(wp.previous_data(), 0xcafecafeu64.to_le_bytes());
+    assert_eq!(wp.data(), 0xba5eba11u64.to_le_bytes());
+
+    proc.remove_page_watchpoint(&wp.id())?;
+    proc.resume()?;
+    let reason = proc.wait_on_signal()?;
+    assert_matches!(reason, ProcessState::Exited { exit_code: 0 });
+
+    let output = channel.read()?;
+    assert_eq!(str::from_utf8(&output).unwrap(), "ba5eba11");
+    Ok(())
+}
+
+#[rstest]
+fn test_inject_syscall() -> Empty {
+    let mut proc = Process::launch(LOOP_PATH, Default::default())?;
+    let pc = proc.get_pc()?;
+
+    let res = proc.inject_syscall(libc::SYS_getpid, &[])?;
+
+    assert_eq!(res, proc.pid().as_raw() as i64);
+    assert_eq!(proc.get_pc()?, pc);
+    Ok(())
+}
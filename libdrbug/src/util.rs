@@ -23,3 +23,27 @@ pub(crate) fn copy_bytes<T: Sized>(dst: &mut [u8], src: &T) {
     let len = src_bytes.len().min(dst.len());
     dst[..len].copy_from_slice(&src_bytes[..len]);
 }
+
+// Shell-style matching where `*` matches any run of characters and `?` matches exactly one; when a
+// `*` doesn't work out, we backtrack and let it swallow one more character
+pub(crate) fn glob_match(pattern: &str, text: &str) -> bool {
+    let (pattern, text): (Vec<char>, Vec<char>) = (pattern.chars().collect(), text.chars().collect());
+    let (mut p, mut t) = (0, 0);
+    let mut last_star = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            last_star = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = last_star {
+            p = star_p + 1;
+            t = star_t + 1;
+            last_star = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
@@ -0,0 +1,215 @@
+use std::cell::Cell;
+use std::io::IoSliceMut;
+use std::rc::Rc;
+
+use nix::sys::uio::{
+    RemoteIoVec,
+    process_vm_readv,
+};
+use nix::unistd::Pid;
+
+use super::{
+    WatchMode,
+    next_watchpoint_id,
+};
+use crate::address::VirtAddr;
+use crate::breakpoint::Breakable;
+use crate::register::DebugRegisters;
+use crate::{
+    DrbugError,
+    DrbugResult,
+    Empty,
+    syscall_error,
+};
+
+// DR0-DR3 hold the addresses we're watching, and DR7 is the control register that says which
+// of those are enabled, what kind of access they trigger on, and how many bytes they cover.
+pub(crate) const DEBUG_ADDR_REG_COUNT: usize = 4;
+const DR7: usize = 7;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Watchpoint {
+    id: usize,
+    pid: Pid,
+    debug_regs: Rc<DebugRegisters>,
+    addr: VirtAddr,
+    mode: WatchMode,
+    size: usize,
+    is_enabled: Rc<Cell<bool>>,
+    hw_index: Rc<Cell<Option<usize>>>,
+    data: Rc<Cell<u64>>,
+    previous_data: Rc<Cell<u64>>,
+}
+
+impl Watchpoint {
+    pub(crate) fn new(
+        pid: Pid,
+        debug_regs: Rc<DebugRegisters>,
+        addr: VirtAddr,
+        mode: WatchMode,
+        size: usize,
+    ) -> DrbugResult<Self> {
+        // Execute "watchpoints" are really just hardware breakpoints, and the CPU requires them to
+        // have a length of 1; everything else can cover 1, 2, 4, or 8 (naturally-aligned) bytes.
+        if !matches!(size, 1 | 2 | 4 | 8) || (mode == WatchMode::Execute && size != 1) {
+            return Err(DrbugError::InvalidWatchpointSize(size));
+        }
+        if addr.0 % size as u64 != 0 {
+            return Err(DrbugError::UnalignedWatchpoint(addr, size));
+        }
+
+        let wp = Watchpoint {
+            id: next_watchpoint_id(),
+            pid,
+            debug_regs,
+            addr,
+            mode,
+            size,
+            is_enabled: Rc::new(Cell::new(false)),
+            hw_index: Rc::new(Cell::new(None)),
+            data: Rc::new(Cell::new(0)),
+            previous_data: Rc::new(Cell::new(0)),
+        };
+        wp.update_data()?;
+        Ok(wp)
+    }
+
+    pub fn data(&self) -> u64 {
+        self.data.get()
+    }
+
+    pub fn mode(&self) -> WatchMode {
+        self.mode
+    }
+
+    pub fn previous_data(&self) -> u64 {
+        self.previous_data.get()
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    // The same watchpoint (id and all) in a new process; it starts out disabled, since the debug
+    // registers in the new process are all clear
+    pub(crate) fn rebind(&self, pid: Pid, debug_regs: Rc<DebugRegisters>) -> Self {
+        let wp = Watchpoint {
+            pid,
+            debug_regs,
+            is_enabled: Rc::new(Cell::new(false)),
+            hw_index: Rc::new(Cell::new(None)),
+            data: Rc::new(Cell::new(0)),
+            previous_data: Rc::new(Cell::new(0)),
+            ..self.clone()
+        };
+        let _ = wp.update_data(); // the address might not be mapped yet, which is fine
+        wp
+    }
+
+    pub(crate) fn hw_index(&self) -> Option<usize> {
+        self.hw_index.get()
+    }
+
+    pub(crate) fn update_data(&self) -> Empty {
+        let mut buf = [0u8; 8];
+        let local_iov = IoSliceMut::new(&mut buf[..self.size]);
+        let remote_iov = RemoteIoVec { base: self.addr.0 as usize, len: self.size };
+        syscall_error!(process_vm_readv(self.pid, &mut [local_iov], &[remote_iov]))?;
+
+        self.previous_data.set(self.data.get());
+        self.data.set(u64::from_le_bytes(buf));
+        Ok(())
+    }
+}
+
+impl Breakable for Watchpoint {
+    fn addr(&self) -> VirtAddr {
+        self.addr
+    }
+
+    fn disable(&mut self) -> Empty {
+        if !self.is_enabled.get() {
+            return Ok(());
+        }
+
+        if let Some(index) = self.hw_index.take() {
+            clear_hardware_stoppoint(&self.debug_regs, index);
+        }
+
+        self.is_enabled.set(false);
+        Ok(())
+    }
+
+    fn enable(&mut self) -> Empty {
+        if self.is_enabled.get() {
+            return Ok(());
+        }
+
+        let index = set_hardware_stoppoint(&self.debug_regs, self.addr, self.mode, self.size)?;
+        self.hw_index.set(Some(index));
+
+        self.is_enabled.set(true);
+        Ok(())
+    }
+
+    fn enabled(&self) -> bool {
+        self.is_enabled.get()
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+fn encode_mode(mode: WatchMode) -> u64 {
+    match mode {
+        WatchMode::Execute => 0b00,
+        WatchMode::Write => 0b01,
+        WatchMode::ReadWrite => 0b11,
+    }
+}
+
+fn encode_size(size: usize) -> u64 {
+    // Yes, 8 bytes really is 0b10 and 4 bytes is 0b11, thanks Intel
+    match size {
+        1 => 0b00,
+        2 => 0b01,
+        4 => 0b11,
+        8 => 0b10,
+        _ => unreachable!("watchpoint size is validated on construction"),
+    }
+}
+
+// Each debug address register gets two enable bits (local and global) starting at bit 0 of DR7,
+// and four control bits (two for the mode and two for the size) starting at bit 16.
+fn control_mask(index: usize) -> u64 {
+    (0b11u64 << (index * 2)) | (0b1111u64 << (16 + index * 4))
+}
+
+// These go through the register cache, so they don't reach the process until it resumes; it also
+// means that `register read dr7` sees them right away, and that they get merged with anything the
+// user has written to the debug registers by hand, instead of one overwriting the other
+fn set_hardware_stoppoint(
+    debug_regs: &DebugRegisters,
+    addr: VirtAddr,
+    mode: WatchMode,
+    size: usize,
+) -> DrbugResult<usize> {
+    let control = debug_regs.read(DR7);
+    let index = (0..DEBUG_ADDR_REG_COUNT)
+        .find(|&i| control & (0b11u64 << (i * 2)) == 0)
+        .ok_or(DrbugError::NoFreeDebugRegisters)?;
+
+    debug_regs.write(index, addr.0);
+
+    let enable_bit = 1u64 << (index * 2);
+    let mode_bits = encode_mode(mode) << (16 + index * 4);
+    let size_bits = encode_size(size) << (18 + index * 4);
+    debug_regs.write(DR7, (control & !control_mask(index)) | enable_bit | mode_bits | size_bits);
+    Ok(index)
+}
+
+fn clear_hardware_stoppoint(debug_regs: &DebugRegisters, index: usize) {
+    debug_regs.write(index, 0);
+    debug_regs.write(DR7, debug_regs.read(DR7) & !control_mask(index));
+}
@@ -0,0 +1,77 @@
+mod hardware;
+mod page;
+mod software;
+
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{
+    AtomicUsize,
+    Ordering,
+};
+
+pub(crate) use self::hardware::DEBUG_ADDR_REG_COUNT;
+pub use self::hardware::Watchpoint;
+pub(crate) use self::page::PAGE_SIZE;
+pub use self::page::PageWatchpoint;
+pub use self::software::SoftwareWatchpoint;
+use crate::address::VirtAddr;
+use crate::register::info::{
+    RegisterId,
+    register_info_by_id,
+};
+use crate::{
+    DrbugError,
+    DrbugResult,
+};
+
+// Hardware and software watchpoints share an id space so the user can refer to either one by id
+static WP_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+fn next_watchpoint_id() -> usize {
+    WP_COUNT.fetch_add(1, Ordering::Relaxed) + 1
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum WatchMode {
+    Write,
+    ReadWrite,
+    Execute,
+}
+
+impl FromStr for WatchMode {
+    type Err = DrbugError;
+
+    fn from_str(s: &str) -> DrbugResult<Self> {
+        match s {
+            "w" | "write" => Ok(WatchMode::Write),
+            "rw" | "read_write" => Ok(WatchMode::ReadWrite),
+            "x" | "exec" | "execute" => Ok(WatchMode::Execute),
+            _ => Err(DrbugError::InvalidWatchMode(s.into())),
+        }
+    }
+}
+
+impl fmt::Display for WatchMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WatchMode::Write => write!(f, "write"),
+            WatchMode::ReadWrite => write!(f, "rw"),
+            WatchMode::Execute => write!(f, "exec"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum WatchTarget {
+    Memory { addr: VirtAddr, size: usize },
+    Register(RegisterId),
+}
+
+impl fmt::Display for WatchTarget {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WatchTarget::Memory { addr, size } => write!(f, "{addr:#x} ({size} bytes)"),
+            WatchTarget::Register(id) => write!(f, "{}", register_info_by_id(id).name),
+        }
+    }
+}
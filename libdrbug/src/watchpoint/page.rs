@@ -0,0 +1,109 @@
+use std::cell::{
+    Cell,
+    RefCell,
+};
+use std::rc::Rc;
+
+use super::next_watchpoint_id;
+use crate::address::VirtAddr;
+
+pub(crate) const PAGE_SIZE: u64 = 0x1000;
+
+// A page watchpoint revokes write permission on every page that overlaps the watched range, so
+// that any write to those pages causes a SIGSEGV in the inferior.  The process catches the fault,
+// checks whether it actually landed in the watched range, and then temporarily restores the
+// original permissions so that the write can complete.  This works for arbitrarily large regions
+// at full speed (as long as nothing else on those pages is written to very often).
+//
+// One caveat: writes performed by the kernel on behalf of the inferior (e.g., a `read` syscall into
+// a watched buffer) don't generate a SIGSEGV, they just fail with EFAULT.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PageWatchpoint {
+    id: usize,
+    addr: VirtAddr,
+    size: usize,
+    pages: Vec<(VirtAddr, i32)>, // page start address and original protection flags
+    is_enabled: Rc<Cell<bool>>,
+    is_armed: Rc<Cell<bool>>,
+    fault_addr: Rc<Cell<Option<VirtAddr>>>,
+    data: Rc<RefCell<Vec<u8>>>,
+    previous_data: Rc<RefCell<Vec<u8>>>,
+}
+
+impl PageWatchpoint {
+    pub(crate) fn new(addr: VirtAddr, size: usize, pages: Vec<(VirtAddr, i32)>, data: Vec<u8>) -> Self {
+        PageWatchpoint {
+            id: next_watchpoint_id(),
+            addr,
+            size,
+            pages,
+            is_enabled: Rc::new(Cell::new(false)),
+            is_armed: Rc::new(Cell::new(false)),
+            fault_addr: Rc::new(Cell::new(None)),
+            previous_data: Rc::new(RefCell::new(data.clone())),
+            data: Rc::new(RefCell::new(data)),
+        }
+    }
+
+    pub fn addr(&self) -> VirtAddr {
+        self.addr
+    }
+
+    pub fn contains(&self, addr: VirtAddr) -> bool {
+        self.addr <= addr && addr < self.addr.add(self.size)
+    }
+
+    pub fn data(&self) -> Vec<u8> {
+        self.data.borrow().clone()
+    }
+
+    pub fn disable(&mut self) {
+        self.is_enabled.set(false);
+    }
+
+    pub fn enable(&mut self) {
+        self.is_enabled.set(true);
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.is_enabled.get()
+    }
+
+    // The address of the most recent write into the watched range
+    pub fn fault_addr(&self) -> Option<VirtAddr> {
+        self.fault_addr.get()
+    }
+
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    pub fn previous_data(&self) -> Vec<u8> {
+        self.previous_data.borrow().clone()
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub(crate) fn is_armed(&self) -> bool {
+        self.is_armed.get()
+    }
+
+    pub(crate) fn page_prot(&self, page: VirtAddr) -> Option<i32> {
+        self.pages.iter().find(|(p, _)| *p == page).map(|(_, prot)| *prot)
+    }
+
+    pub(crate) fn pages(&self) -> &[(VirtAddr, i32)] {
+        &self.pages
+    }
+
+    pub(crate) fn set_armed(&self, armed: bool) {
+        self.is_armed.set(armed);
+    }
+
+    pub(crate) fn update_data(&self, fault_addr: VirtAddr, data: Vec<u8>) {
+        self.fault_addr.set(Some(fault_addr));
+        *self.previous_data.borrow_mut() = self.data.replace(data);
+    }
+}
@@ -0,0 +1,78 @@
+use std::cell::{
+    Cell,
+    RefCell,
+};
+use std::rc::Rc;
+
+use super::{
+    WatchTarget,
+    next_watchpoint_id,
+};
+
+// A software watchpoint doesn't need any help from the CPU; instead, the process single-steps the
+// inferior and compares the watched bytes after every instruction.  This is _much_ slower than a
+// hardware watchpoint, but it can watch as many bytes as we want, and it can watch registers.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SoftwareWatchpoint {
+    id: usize,
+    target: WatchTarget,
+    is_enabled: Rc<Cell<bool>>,
+    data: Rc<RefCell<Vec<u8>>>,
+    previous_data: Rc<RefCell<Vec<u8>>>,
+}
+
+impl SoftwareWatchpoint {
+    pub(crate) fn new(target: WatchTarget, data: Vec<u8>) -> Self {
+        SoftwareWatchpoint {
+            id: next_watchpoint_id(),
+            target,
+            is_enabled: Rc::new(Cell::new(false)),
+            previous_data: Rc::new(RefCell::new(data.clone())),
+            data: Rc::new(RefCell::new(data)),
+        }
+    }
+
+    pub fn data(&self) -> Vec<u8> {
+        self.data.borrow().clone()
+    }
+
+    pub fn disable(&mut self) {
+        self.is_enabled.set(false);
+    }
+
+    pub fn enable(&mut self) {
+        self.is_enabled.set(true);
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.is_enabled.get()
+    }
+
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    pub fn previous_data(&self) -> Vec<u8> {
+        self.previous_data.borrow().clone()
+    }
+
+    pub fn target(&self) -> WatchTarget {
+        self.target
+    }
+
+    // Reset the baseline without counting it as a change, e.g., because the user wrote to the
+    // watched location while the process was stopped
+    pub(crate) fn set_data(&self, data: Vec<u8>) {
+        *self.data.borrow_mut() = data;
+    }
+
+    // Returns true if the watched value changed since the last time we looked at it
+    pub(crate) fn update_data(&self, data: Vec<u8>) -> bool {
+        if *self.data.borrow() == data {
+            return false;
+        }
+
+        *self.previous_data.borrow_mut() = self.data.replace(data);
+        true
+    }
+}
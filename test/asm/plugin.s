@@ -0,0 +1,10 @@
+# A tiny shared library for testing breakpoints in code that gets dlopen'ed at runtime
+.global plugin_fn
+.type plugin_fn, @function
+
+.section .text
+
+plugin_fn:
+	movq $42, %rax
+	ret
+.size plugin_fn, .-plugin_fn
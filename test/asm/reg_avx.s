@@ -0,0 +1,41 @@
+.global main
+
+.section .data
+ymm_data: .byte 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25, 26, 27, 28, 29, 30, 31
+
+.section .text
+
+.macro trap
+	movq $62, %rax
+	movq %r12, %rdi
+	movq $5, %rsi
+	syscall
+.endm
+
+main:
+	push %rbp
+	movq %rsp, %rbp
+
+	# Get this program's PID, store it in r12, and then return control to the test
+	movq $39, %rax
+	syscall
+	movq %rax, %r12
+
+	vmovdqu ymm_data(%rip), %ymm0
+	trap
+
+	# echo whatever the test wrote into ymm1 back out on stdout
+	subq $32, %rsp
+	vmovdqu %ymm1, (%rsp)
+	movq $1, %rax
+	movq $1, %rdi
+	movq %rsp, %rsi
+	movq $32, %rdx
+	syscall
+	addq $32, %rsp
+	trap
+
+	vzeroupper
+	popq %rbp
+	movq $0, %rax
+	ret
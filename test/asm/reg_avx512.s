@@ -0,0 +1,59 @@
+.global main
+
+.section .data
+zmm_data:
+	.byte 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25, 26, 27, 28, 29, 30, 31
+	.byte 32, 33, 34, 35, 36, 37, 38, 39, 40, 41, 42, 43, 44, 45, 46, 47, 48, 49, 50, 51, 52, 53, 54, 55, 56, 57, 58, 59, 60, 61, 62, 63
+
+.section .text
+
+.macro trap
+	movq $62, %rax
+	movq %r12, %rdi
+	movq $5, %rsi
+	syscall
+.endm
+
+main:
+	push %rbp
+	movq %rsp, %rbp
+
+	# Get this program's PID, store it in r12, and then return control to the test
+	movq $39, %rax
+	syscall
+	movq %rax, %r12
+
+	# zmm0 is split across three different parts of the XSAVE area, zmm16 is all in one
+	vmovdqu64 zmm_data(%rip), %zmm0
+	vmovdqu64 zmm_data(%rip), %zmm16
+	movl $0xa5a5, %eax
+	kmovw %eax, %k1
+	trap
+
+	# echo whatever the test wrote into zmm1 back out on stdout
+	subq $64, %rsp
+	vmovdqu64 %zmm1, (%rsp)
+	movq $1, %rax
+	movq $1, %rdi
+	movq %rsp, %rsi
+	movq $64, %rdx
+	syscall
+	addq $64, %rsp
+	trap
+
+	# and the same for the opmask registers
+	subq $16, %rsp
+	kmovw %k2, %eax
+	movw %ax, (%rsp)
+	movq $1, %rax
+	movq $1, %rdi
+	movq %rsp, %rsi
+	movq $2, %rdx
+	syscall
+	addq $16, %rsp
+	trap
+
+	vzeroupper
+	popq %rbp
+	movq $0, %rax
+	ret
@@ -34,10 +34,10 @@ main:
 	movsd test_double(%rip), %xmm0
 	trap
 
-	# test an x87 (long double) register (currently unsupported)
-	// emms
-	// fldl test_double(%rip)
-	// trap
+	# test an x87 (long double) register
+	emms
+	fldl test_double(%rip)
+	trap
 
 	popq %rbp
 	movq $0, %rax
@@ -51,13 +51,13 @@ main:
 	call fflush@plt
 	trap
 
-	# test an x87 (long double) register (currently unsupported)
-	// subq $16, %rsp
-	// fstpt (%rsp)
-	// leaq long_float_format(%rip), %rdi
-	// print_rsi
-	// addq $16, %rsp
-	// trap
+	# test an x87 (long double) register
+	subq $16, %rsp
+	fstpt (%rsp)
+	leaq long_float_format(%rip), %rdi
+	print_rsi
+	addq $16, %rsp
+	trap
 
 	popq %rbp
 	movq $0, %rax
@@ -0,0 +1,29 @@
+# A function without a frame pointer, with an `endbr64` in front of its prologue (which is what
+# everything built with -fcf-protection looks like), and an epilogue that has to undo a `sub rsp`
+.global main
+.global frameless
+.type frameless, @function
+
+.section .text
+
+frameless:
+	endbr64
+	push %rbx
+	sub $0x18, %rsp
+	movq $7, %rbx
+
+	# Hand control back to the test from the middle of the function body
+	int3
+
+	movq %rbx, %rax
+	add $0x18, %rsp
+	pop %rbx
+	ret
+.size frameless, .-frameless
+
+main:
+	push %rbp
+	movq %rsp, %rbp
+	call frameless
+	pop %rbp
+	ret
@@ -0,0 +1,29 @@
+use std::hint::black_box;
+use std::io::{
+    Write,
+    stdout,
+};
+
+use nix::sys::signal::{
+    Signal,
+    raise,
+};
+
+#[inline(never)]
+fn tick(i: u64) -> u64 {
+    black_box(i)
+}
+
+fn main() {
+    print!("{:x}", tick as *const () as u64); // no leading 0x for ease of parsing
+    stdout().flush().unwrap();
+    raise(Signal::SIGTRAP).unwrap();
+
+    // The test sets breakpoints on `tick`, which gets called with 0, 1, 2, ... in rdi
+    let mut total = 0;
+    for i in 0..10 {
+        total += tick(i);
+    }
+    print!("{total}");
+    stdout().flush().unwrap();
+}
@@ -0,0 +1,36 @@
+use std::ffi::CString;
+use std::io::{
+    Write,
+    stdout,
+};
+
+use nix::sys::signal::{
+    Signal,
+    raise,
+};
+
+fn main() {
+    // The test stops here and sets a breakpoint on `plugin_fn`, which isn't loaded yet
+    raise(Signal::SIGTRAP).unwrap();
+
+    // The library is built from test/asm/plugin.s, and lives next to the other asm test targets
+    let exe = std::env::current_exe().unwrap();
+    let path = exe.parent().unwrap().join("../asm/libplugin.so");
+    let path = CString::new(path.to_str().unwrap()).unwrap();
+    let symbol = CString::new("plugin_fn").unwrap();
+
+    let result = unsafe {
+        let lib = libc::dlopen(path.as_ptr(), libc::RTLD_NOW);
+        assert!(!lib.is_null());
+        let plugin_fn =
+            std::mem::transmute::<*mut libc::c_void, extern "C" fn() -> u64>(libc::dlsym(lib, symbol.as_ptr()));
+        let result = plugin_fn();
+        libc::dlclose(lib);
+        result
+    };
+
+    // Stop again so the test can check that the library's breakpoint went away with it
+    raise(Signal::SIGTRAP).unwrap();
+    print!("{result}");
+    stdout().flush().unwrap();
+}
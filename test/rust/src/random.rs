@@ -0,0 +1,23 @@
+use std::io::{
+    Write,
+    stdout,
+};
+
+// Prints some random bytes and the current time, so no two runs print the same thing (unless the
+// syscalls get replayed).  The time comes from the raw syscall, since going through libc would use
+// the vDSO and never enter the kernel.
+fn main() {
+    let mut buf = [0u8; 16];
+    let n = unsafe { libc::getrandom(buf.as_mut_ptr().cast(), buf.len(), 0) };
+    assert_eq!(n, buf.len() as isize);
+
+    let mut ts = libc::timespec { tv_sec: 0, tv_nsec: 0 };
+    let res = unsafe { libc::syscall(libc::SYS_clock_gettime, libc::CLOCK_REALTIME, &mut ts) };
+    assert_eq!(res, 0);
+
+    for b in buf {
+        print!("{b:02x}");
+    }
+    print!(" {}.{:09}", ts.tv_sec, ts.tv_nsec);
+    stdout().flush().unwrap();
+}
@@ -0,0 +1,26 @@
+use std::hint::black_box;
+use std::io::{
+    Write,
+    stdout,
+};
+
+use nix::sys::signal::{
+    Signal,
+    raise,
+};
+
+// Returns its argument, the long way round; every level of recursion comes back to the same return
+// address, which is what makes stepping over the recursive call interesting
+#[inline(never)]
+fn depth(n: u64) -> u64 {
+    if n == 0 { 0 } else { depth(black_box(n - 1)) + 1 }
+}
+
+fn main() {
+    print!("{:x}", depth as *const () as u64); // no leading 0x for ease of parsing
+    stdout().flush().unwrap();
+    raise(Signal::SIGTRAP).unwrap();
+
+    print!("{}", depth(black_box(5)));
+    stdout().flush().unwrap();
+}
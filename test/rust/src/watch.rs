@@ -0,0 +1,22 @@
+use std::io::{
+    Write,
+    stdout,
+};
+use std::ptr::write_volatile;
+
+use nix::sys::signal::{
+    Signal,
+    raise,
+};
+
+fn main() {
+    let mut a: u64 = 0xcafecafe;
+    print!("{:x}", (&a as *const u64) as u64); // no leading 0x for ease of parsing
+    stdout().flush().unwrap();
+    raise(Signal::SIGTRAP).unwrap();
+
+    // Volatile so the compiler can't get clever and skip the write that we're watching for
+    unsafe { write_volatile(&mut a, 0xba5eba11) };
+    print!("{:x}", a);
+    stdout().flush().unwrap();
+}